@@ -0,0 +1,129 @@
+use std::{fs::{self, File}, io::{self, Write}, path::{Path, PathBuf}};
+
+/// Anzahl der Sicherungen, die pro Datei aufbewahrt werden.
+pub const BACKUP_COUNT: usize = 10;
+
+/// Endung der Sicherung, die vor dem Wiederherstellen vom aktuellen Stand angelegt wird.
+/// Sie zählt zu den Sicherungen, wird aber nicht selbst wiederhergestellt, sonst würde ein
+/// zweiter Aufruf nur zwischen zwei Fassungen hin- und herschalten.
+const REPLACED_SUFFIX: &str = ".replaced";
+
+/// Unterverzeichnis neben der gesicherten Datei.
+pub const BACKUP_DIR: &str = "backups";
+
+/// Schreibt `data` absturzsicher nach `path`: erst in eine temporäre Datei im selben
+/// Verzeichnis, dann fsync und umbenennen. Bei einem Fehler bleibt die alte Datei unverändert.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let tmp_path = dir.join(format!(".{}.tmp", file_name));
+
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        // Auch den Verzeichniseintrag sichern, sonst kann die Umbenennung verloren gehen
+        File::open(dir)?.sync_all()
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join(BACKUP_DIR)
+}
+
+/// Sicherungen von `path`, die neueste zuerst.
+pub fn list_backups(path: &Path) -> Vec<PathBuf> {
+    let prefix = match path.file_name() {
+        Some(name) => format!("{}.", name.to_string_lossy()),
+        None => return Vec::new(),
+    };
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_dir(path))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy().starts_with(&prefix)))
+                .collect()
+        })
+        .unwrap_or_default();
+    // Der Zeitstempel im Namen ist sortierbar
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Legt eine Sicherung der aktuellen Datei mit Zeitstempel an und löscht die ältesten,
+/// sodass höchstens `BACKUP_COUNT` übrig bleiben. Gleicht die Datei der neuesten Sicherung,
+/// wird keine weitere angelegt.
+pub fn backup(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let data = fs::read(path)?;
+    if list_backups(path).first().and_then(|newest| fs::read(newest).ok()).is_some_and(|newest| newest == data) {
+        return Ok(());
+    }
+    write_backup(path, &data, "").map(|_| ())
+}
+
+fn write_backup(path: &Path, data: &[u8], suffix: &str) -> io::Result<PathBuf> {
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let target = backup_dir(path).join(format!("{}.{}{}", file_name, timestamp, suffix));
+    write_atomic(&target, data)?;
+
+    for old in list_backups(path).into_iter().skip(BACKUP_COUNT) {
+        // Die neue Sicherung bleibt in jedem Fall erhalten
+        if old != target {
+            fs::remove_file(old)?;
+        }
+    }
+    Ok(target)
+}
+
+/// Eine wiederhergestellte Fassung, die der Aufrufer noch prüfen muss. Bis `commit` oder
+/// `revert` bleiben sowohl die wiederhergestellte Sicherung als auch der vorherige Stand erhalten.
+pub struct Restore {
+    path: PathBuf,
+    /// Die Sicherung, deren Inhalt jetzt in der Datei steht
+    pub backup: PathBuf,
+    /// Sicherung des Stands vor dem Wiederherstellen
+    replaced: Option<PathBuf>,
+}
+
+impl Restore {
+    /// Die Fassung ist gültig: die Sicherung aus der Liste nehmen, sodass ein erneuter
+    /// Aufruf von `restore_previous` die nächstältere Version liefert.
+    pub fn commit(self) -> io::Result<()> {
+        fs::remove_file(&self.backup)
+    }
+
+    /// Die Fassung ist unbrauchbar: den vorherigen Stand zurückschreiben.
+    pub fn revert(self) -> io::Result<()> {
+        match &self.replaced {
+            Some(replaced) => {
+                write_atomic(&self.path, &fs::read(replaced)?)?;
+                fs::remove_file(replaced)
+            }
+            None => fs::remove_file(&self.path),
+        }
+    }
+}
+
+/// Sichert den aktuellen Stand und schreibt die neueste Sicherung zurück. Gelöscht wird
+/// dabei nichts; das Ergebnis muss mit `Restore::commit` oder `Restore::revert` abgeschlossen werden.
+pub fn restore_previous(path: &Path) -> io::Result<Restore> {
+    let Some(newest) = list_backups(path).into_iter().find(|b| !b.to_string_lossy().ends_with(REPLACED_SUFFIX)) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "keine Sicherung vorhanden"));
+    };
+    let data = fs::read(&newest)?;
+    let replaced = if path.exists() { Some(write_backup(path, &fs::read(path)?, REPLACED_SUFFIX)?) } else { None };
+    write_atomic(path, &data)?;
+    Ok(Restore { path: path.to_path_buf(), backup: newest, replaced })
+}
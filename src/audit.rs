@@ -0,0 +1,479 @@
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{read_jack_connections, run_jack_tool};
+use crate::{atomic_file, meter, paths};
+
+/// Verlauf im Log-Verzeichnis, ein JSON-Objekt pro Zeile.
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Anzahl der Einträge, die im Speicher gehalten und angezeigt werden.
+const MAX_RECORDS: usize = 500;
+
+/// Ist die Datei größer, wird sie auf die Einträge im Speicher gekürzt.
+const MAX_FILE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Auslöser einer Änderung am JACK-Graphen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "name", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Use-Case über Button oder MIDI gestartet
+    Scenario(String),
+    /// Verbindung in der Oberfläche hergestellt oder unerwünschte Verbindungen entfernt
+    Manual,
+    /// Gespeicherter Verbindungszustand wiederhergestellt
+    Snapshot(String),
+    Undo,
+    Rollback,
+    /// Verbindungen eines Programms nach seinem Start für den aktiven Use-Case wiederhergestellt
+    Reconnect(String),
+    /// Von einem anderen JACK-Client geändert, etwa einer Patchbay oder einem Skript. Erkannt
+    /// am Unterschied zwischen dem zuletzt aufgezeichneten und dem aktuellen Zustand.
+    External,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Scenario(name) => write!(f, "Use-Case '{}'", name),
+            Trigger::Manual => write!(f, "manuell"),
+            Trigger::Snapshot(name) => write!(f, "Zustand '{}'", name),
+            Trigger::Undo => write!(f, "rückgängig"),
+            Trigger::Rollback => write!(f, "zurückgesetzt"),
+            Trigger::Reconnect(name) => write!(f, "automatisch wieder verbunden: {}", name),
+            Trigger::External => write!(f, "anderer JACK-Client"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Connect,
+    Disconnect,
+}
+
+/// Eine einzelne Verbindung, die hergestellt oder getrennt wurde.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub source: String,
+    pub target: String,
+    /// Programm, zu dem das Ziel gehört
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+}
+
+impl Change {
+    fn inverse(&self) -> Self {
+        Self {
+            kind: match self.kind {
+                ChangeKind::Connect => ChangeKind::Disconnect,
+                ChangeKind::Disconnect => ChangeKind::Connect,
+            },
+            ..self.clone()
+        }
+    }
+
+    fn apply(&self) -> Result<()> {
+        let command = match self.kind {
+            ChangeKind::Connect => "jack_connect",
+            ChangeKind::Disconnect => "jack_disconnect",
+        };
+        run_jack_tool(command, &[&self.source, &self.target]).map(|_| ())
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.kind {
+            ChangeKind::Connect => "verbunden",
+            ChangeKind::Disconnect => "getrennt",
+        };
+        write!(f, "{} {} -> {}", action, self.source, self.target)?;
+        if let Some(program) = &self.program {
+            write!(f, " [{}]", program)?;
+        }
+        Ok(())
+    }
+}
+
+/// Alle Änderungen einer Aktion mit dem Zustand des Graphen davor und danach.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub id: u64,
+    pub time: String,
+    pub trigger: Trigger,
+    pub changes: Vec<Change>,
+    /// Verbindungen vor bzw. nach der Aktion, `None`, wenn JACK nicht abgefragt werden konnte.
+    pub before: Option<Vec<(String, String)>>,
+    pub after: Option<Vec<(String, String)>>,
+    /// ID des Eintrags, den dieser Eintrag rückgängig macht.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverts: Option<u64>,
+}
+
+impl Record {
+    /// Beginnt eine Aktion und hält den aktuellen Zustand des Graphen fest.
+    pub fn begin(trigger: Trigger) -> Self {
+        Self {
+            id: 0,
+            time: String::new(),
+            trigger,
+            changes: Vec::new(),
+            before: read_jack_connections().ok(),
+            after: None,
+            reverts: None,
+        }
+    }
+
+    pub fn connected(&mut self, source: &str, target: &str, program: Option<&str>) {
+        self.push(ChangeKind::Connect, source, target, program);
+    }
+
+    pub fn disconnected(&mut self, source: &str, target: &str, program: Option<&str>) {
+        self.push(ChangeKind::Disconnect, source, target, program);
+    }
+
+    fn push(&mut self, kind: ChangeKind, source: &str, target: &str, program: Option<&str>) {
+        self.changes.push(Change {
+            kind,
+            source: source.to_string(),
+            target: target.to_string(),
+            program: program.map(str::to_string),
+        });
+    }
+
+    /// Führt die Änderung aus und nimmt sie bei Erfolg auf.
+    fn apply(&mut self, change: Change, errors: &mut Vec<Error>) {
+        match change.apply() {
+            Ok(()) => self.changes.push(change),
+            Err(e) => errors.push(e),
+        }
+    }
+}
+
+/// Verlauf aller Änderungen an JACK-Verbindungen, die der Manager vorgenommen hat.
+pub struct AuditLog {
+    records: Vec<Record>,
+    path: PathBuf,
+    next_id: u64,
+    /// Zeilen in der Datei, auch die nicht mehr im Speicher gehaltenen
+    file_lines: usize,
+}
+
+impl AuditLog {
+    /// Lädt den bisherigen Verlauf. Unlesbare Zeilen werden übersprungen.
+    pub fn load() -> Self {
+        Self::load_from(paths::log_dir().join(AUDIT_FILE))
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let mut records: Vec<Record> = Vec::new();
+        let mut file_lines = 0;
+        if let Ok(file) = fs::File::open(&path) {
+            for (index, line) in BufReader::new(file).lines().map_while(|line| line.ok()).enumerate() {
+                file_lines = index + 1;
+                match serde_json::from_str(&line) {
+                    Ok(record) => records.push(record),
+                    Err(e) => tracing::warn!(path = ?path, line = index + 1, "Verlaufseintrag nicht lesbar: {}", e),
+                }
+            }
+        }
+        let next_id = records.iter().map(|r| r.id).max().map_or(1, |id| id + 1);
+        let skip = records.len().saturating_sub(MAX_RECORDS);
+        records.drain(..skip);
+        Self { records, path, next_id, file_lines }
+    }
+
+    /// Der Verlauf, der älteste Eintrag zuerst.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Der zuletzt gestartete Use-Case, sofern seitdem nicht zurückgesetzt wurde.
+    pub fn active_scenario(&self) -> Option<&str> {
+        self.records.iter().rev().find_map(|r| match &r.trigger {
+            Trigger::Scenario(name) => Some(Some(name.as_str())),
+            Trigger::Rollback => Some(None),
+            _ => None,
+        })?
+    }
+
+    pub fn is_reverted(&self, id: u64) -> bool {
+        self.records.iter().any(|r| r.reverts == Some(id))
+    }
+
+    /// Schließt die Aktion ab und schreibt sie in den Verlauf. Aktionen ohne Änderungen werden
+    /// verworfen, außer sie nehmen eine frühere zurück; die gilt danach als erledigt. Hat ein
+    /// anderer Client seit dem letzten Eintrag Verbindungen geändert, steht das davor als
+    /// eigener Eintrag.
+    pub fn commit(&mut self, record: Record) -> Result<()> {
+        if record.changes.is_empty() && record.reverts.is_none() {
+            return Ok(());
+        }
+        let mut errors = Vec::new();
+        if let Some(external) = self.external_changes(&record)
+            && let Err(e) = self.push(external)
+        {
+            errors.push(e);
+        }
+        if let Err(e) = self.push(record) {
+            errors.push(e);
+        }
+        if self.file_lines > self.records.len()
+            && fs::metadata(&self.path).is_ok_and(|meta| meta.len() > MAX_FILE_BYTES)
+            && let Err(e) = self.compact()
+        {
+            errors.push(e);
+        }
+        Error::from_list(errors)
+    }
+
+    /// Was sich seit dem letzten Eintrag ohne den Manager geändert hat, als eigener Eintrag.
+    /// Verbindungen der eigenen Mess- und Aufnahme-Clients zählen nicht.
+    fn external_changes(&self, record: &Record) -> Option<Record> {
+        let last = self.records.last()?.after.clone()?;
+        let now = record.before.clone()?;
+        let routes = |connections: &[(String, String)]| -> HashSet<(String, String)> {
+            connections
+                .iter()
+                .filter(|(source, target)| !meter::is_internal_port(source) && !meter::is_internal_port(target))
+                .cloned()
+                .collect()
+        };
+        let (removed, added) = {
+            let (last, now) = (routes(&last), routes(&now));
+            (last.difference(&now).cloned().collect::<Vec<_>>(), now.difference(&last).cloned().collect::<Vec<_>>())
+        };
+        if removed.is_empty() && added.is_empty() {
+            return None;
+        }
+        let mut external = Record {
+            id: 0,
+            time: String::new(),
+            trigger: Trigger::External,
+            changes: Vec::new(),
+            before: Some(last),
+            after: Some(now),
+            reverts: None,
+        };
+        for (source, target) in removed {
+            external.disconnected(&source, &target, None);
+        }
+        for (source, target) in added {
+            external.connected(&source, &target, None);
+        }
+        Some(external)
+    }
+
+    /// Schließt einen Use-Case ab, der an einer erforderlichen Verbindung gescheitert ist.
+    /// `rollback` hat den Zustand davor bereits wiederhergestellt und steht als eigener
+    /// Eintrag dahinter, der den Use-Case zurücknimmt; so gilt er nicht als aktiv.
+    pub fn commit_failed(&mut self, mut record: Record, mut rollback: Record) -> Result<()> {
+        let mut errors = Vec::new();
+        // Der Zustand nach dem Use-Case ist der vor dem Zurücksetzen, nicht der jetzige
+        record.after = rollback.before.clone();
+        if !record.changes.is_empty() {
+            if let Err(e) = self.commit(record) {
+                errors.push(e);
+            }
+            // Mit Änderungen steht der Eintrag zuletzt im Speicher, auch wenn das Schreiben scheitert
+            rollback.reverts = self.records.last().map(|r| r.id);
+        }
+        if let Err(e) = self.commit(rollback) {
+            errors.push(e);
+        }
+        Error::from_list(errors)
+    }
+
+    fn push(&mut self, mut record: Record) -> Result<()> {
+        record.id = self.next_id;
+        self.next_id += 1;
+        record.time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        if record.after.is_none() {
+            record.after = read_jack_connections().ok();
+        }
+        tracing::info!(id = record.id, trigger = %record.trigger, changes = record.changes.len(), "Verbindungsänderungen aufgezeichnet");
+
+        let result = self.append(&record);
+        if result.is_ok() {
+            self.file_lines += 1;
+        }
+        self.records.push(record);
+        if self.records.len() > MAX_RECORDS {
+            self.records.remove(0);
+        }
+        result
+    }
+
+    fn append(&self, record: &Record) -> Result<()> {
+        let data = serde_json::to_string(record)
+            .map_err(|source| Error::Serialize { path: self.path.clone(), source })?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|source| Error::ConfigIo { path: dir.to_path_buf(), source })?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", data))
+            .map_err(|source| Error::ConfigIo { path: self.path.clone(), source })
+            .map_err(|e| e.context("Fehler beim Schreiben des Verlaufs"))
+    }
+
+    /// Schreibt die Datei neu mit den Einträgen, die im Speicher gehalten werden.
+    fn compact(&mut self) -> Result<()> {
+        let mut data = Vec::new();
+        for record in &self.records {
+            serde_json::to_writer(&mut data, record).map_err(|source| Error::Serialize { path: self.path.clone(), source })?;
+            data.push(b'\n');
+        }
+        atomic_file::write_atomic(&self.path, &data)
+            .map_err(|source| Error::ConfigIo { path: self.path.clone(), source })
+            .map_err(|e| e.context("Fehler beim Kürzen des Verlaufs"))?;
+        tracing::info!(path = ?self.path, removed = self.file_lines - self.records.len(), "Verlauf gekürzt");
+        self.file_lines = self.records.len();
+        Ok(())
+    }
+
+    /// Macht die letzte noch nicht rückgängig gemachte Aktion rückgängig.
+    pub fn undo_last(&mut self) -> Result<()> {
+        let Some(last) = self
+            .records
+            .iter()
+            .rev()
+            .find(|r| !matches!(r.trigger, Trigger::Undo | Trigger::Rollback) && !self.is_reverted(r.id))
+            .cloned()
+        else {
+            return Err(Error::NothingToUndo);
+        };
+
+        let mut record = Record::begin(Trigger::Undo);
+        record.reverts = Some(last.id);
+        let mut errors = Vec::new();
+        for change in last.changes.iter().rev() {
+            record.apply(change.inverse(), &mut errors);
+        }
+        if let Err(e) = self.commit(record) {
+            errors.push(e);
+        }
+        Error::from_list(errors)
+    }
+
+    /// Stellt die Verbindungen wieder her, die vor dem zuletzt gestarteten Use-Case bestanden.
+    /// Liefert den Namen des Use-Cases.
+    pub fn rollback_last_scenario(&mut self) -> Result<String> {
+        // Bereits zurückgenommene Use-Cases überspringen, sonst stellte ein zweiter Aufruf denselben Stand erneut her
+        let last = self.records.iter().rev().find_map(|r| match &r.trigger {
+            Trigger::Scenario(name) if !self.is_reverted(r.id) => Some((r.id, name.clone(), r.before.clone())),
+            _ => None,
+        });
+        let Some((scenario_id, name, before)) = last else {
+            return Err(Error::NothingToUndo);
+        };
+        let Some(before) = before else {
+            return Err(Error::SnapshotMissing(name));
+        };
+        let mut record = Record::begin(Trigger::Rollback);
+        record.reverts = Some(scenario_id);
+        let mut errors = Vec::new();
+        if let Err(e) = restore_snapshot(&mut record, &before) {
+            errors.push(e);
+        }
+        if let Err(e) = self.commit(record) {
+            errors.push(e);
+        }
+        Error::from_list(errors)?;
+        Ok(name)
+    }
+}
+
+/// Stellt die Verbindungen aus `snapshot` wieder her: trennt hinzugekommene und verbindet
+/// fehlende. Die Änderungen werden in `record` aufgenommen. Verbindungen der eigenen Mess-,
+/// Aufnahme- und Testton-Clients bleiben unberührt, Snapshots enthalten sie nicht.
+pub fn restore_snapshot(record: &mut Record, snapshot: &[(String, String)]) -> Result<()> {
+    let route = |(source, target): &(String, String)| !meter::is_internal_port(source) && !meter::is_internal_port(target);
+    let wanted: HashSet<(String, String)> = snapshot.iter().filter(|c| route(c)).cloned().collect();
+    let current: HashSet<(String, String)> = read_jack_connections()?.into_iter().filter(|c| route(c)).collect();
+    let mut errors = Vec::new();
+    // Erst trennen, damit ein Ziel nicht kurzzeitig zwei Quellen hat
+    for (source, target) in current.difference(&wanted) {
+        record.apply(Change { kind: ChangeKind::Disconnect, source: source.clone(), target: target.clone(), program: None }, &mut errors);
+    }
+    for (source, target) in wanted.difference(&current) {
+        record.apply(Change { kind: ChangeKind::Connect, source: source.clone(), target: target.clone(), program: None }, &mut errors);
+    }
+    Error::from_list(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(name: &str) -> AuditLog {
+        let path = paths::init_for_tests().join(format!("{}.jsonl", name));
+        let _ = fs::remove_file(&path);
+        AuditLog::load_from(path)
+    }
+
+    fn scenario(name: &str) -> Record {
+        let mut record = Record::begin(Trigger::Scenario(name.to_string()));
+        record.connected("mic:out", "mixer:in", Some("Mixer"));
+        record
+    }
+
+    #[test]
+    fn scenario_is_active_until_rollback() {
+        let mut log = log("audit-active");
+        assert_eq!(log.active_scenario(), None);
+        log.commit(scenario("live")).unwrap();
+        assert_eq!(log.active_scenario(), Some("live"));
+        log.commit(scenario("pause")).unwrap();
+        assert_eq!(log.active_scenario(), Some("pause"));
+    }
+
+    #[test]
+    fn failed_scenario_is_not_active() {
+        let mut log = log("audit-failed");
+        log.commit(scenario("live")).unwrap();
+        let mut rollback = Record::begin(Trigger::Rollback);
+        rollback.disconnected("mic:out", "mixer:in", None);
+        log.commit_failed(scenario("pause"), rollback).unwrap();
+
+        assert_eq!(log.active_scenario(), None);
+        let failed = &log.records()[1];
+        assert_eq!(failed.trigger, Trigger::Scenario("pause".to_string()));
+        assert!(log.is_reverted(failed.id));
+        assert_eq!(log.records()[2].reverts, Some(failed.id));
+    }
+
+    #[test]
+    fn failed_scenario_without_changes_records_only_rollback() {
+        let mut log = log("audit-failed-empty");
+        let mut rollback = Record::begin(Trigger::Rollback);
+        rollback.disconnected("mic:out", "mixer:in", None);
+        log.commit_failed(Record::begin(Trigger::Scenario("pause".to_string())), rollback).unwrap();
+        assert_eq!(log.records().len(), 1);
+        assert_eq!(log.records()[0].reverts, None);
+        assert_eq!(log.active_scenario(), None);
+    }
+
+    #[test]
+    fn reload_keeps_records_and_ids() {
+        let mut log = log("audit-reload");
+        log.commit(scenario("live")).unwrap();
+        log.commit(scenario("pause")).unwrap();
+        let reloaded = AuditLog::load_from(log.path.clone());
+        assert_eq!(reloaded.records().len(), 2);
+        assert_eq!(reloaded.next_id, 3);
+        assert_eq!(reloaded.active_scenario(), Some("pause"));
+    }
+}
@@ -0,0 +1,315 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::audit::{self, AuditLog, Trigger};
+use crate::error::Error;
+use crate::health::{HealthMonitor, XrunHistory};
+use crate::latency;
+use crate::logging;
+use crate::managed_audio_program::ManagedAudioProgram;
+use crate::meter::{Meter, MeterConfig};
+use crate::paths;
+use crate::recorder::{Recorder, RecorderConfig};
+use crate::project::{ImportMode, Project};
+use crate::snapshot::Snapshot;
+
+const USAGE: &str = "Verwendung: jackstreamingmanager [Optionen]
+
+Ohne Optionen wird die grafische Oberfläche gestartet.
+
+  --project <name>           Vor dem Start der Oberfläche auf das Projekt wechseln
+  --list-projects            Gespeicherte Projekte auflisten
+  --export <datei>           Aktuelles Setup in eine Projektdatei exportieren
+  --import <datei>           Projektdatei importieren (zusammenführen)
+  --import <datei> --replace Projektdatei importieren und aktuelles Setup ersetzen
+  --restore <programm>       Vorherige Version der Programmkonfiguration wiederherstellen
+  --save-snapshot <name>     Aktuelle JACK-Verbindungen unter diesem Namen speichern
+  --restore-snapshot <name>  Gespeicherte JACK-Verbindungen wiederherstellen
+  --meter <port>             Pegel des Ports eine Sekunde lang messen und ausgeben (mehrfach möglich)
+  --true-peak                Bei --meter zusätzlich den True-Peak schätzen
+  --record <sekunden>        Die in der Aufnahme ausgewählten Ports so lange aufnehmen
+  --latency <eingang> <port> Testsignal an den Eingang senden und die Umlauflatenz bis zum Port messen
+  --jack-status              Abtastrate, Puffergröße und DSP-Last des JACK-Servers ausgeben, dazu
+                             Xruns und Verlauf der laufenden bzw. zuletzt gestarteten Oberfläche
+  --config-dir <verzeichnis> Konfiguration, Laufzeitdaten und Logs in diesem Verzeichnis ablegen
+                             (auch über JACKSTREAMINGMANAGER_CONFIG_DIR)
+  --paths                    Verwendete Verzeichnisse anzeigen
+  --log <filter>             Log-Stufen, z.B. \"debug\" oder \"info,jackstreamingmanager::midi_control=debug\"
+                             (auch über JACKSTREAMINGMANAGER_LOG, Standard: info)
+  --help                     Diese Hilfe anzeigen";
+
+/// So lange misst `--meter`, mindestens drei Messfenster.
+const METER_DURATION: Duration = Duration::from_secs(1);
+
+/// Exit-Code für fehlerhafte Konfigurationsdateien, damit Skripte sie von anderen Fehlern unterscheiden können.
+const EXIT_INVALID_CONFIG: i32 = 3;
+
+/// Wertet die Kommandozeile aus. Gibt `Some(exit_code)` zurück, wenn sich das Programm
+/// danach beenden soll, und `None`, wenn die Oberfläche gestartet werden soll.
+pub fn run(args: &[String]) -> Option<i32> {
+    let mut export: Option<PathBuf> = None;
+    let mut import: Option<PathBuf> = None;
+    let mut replace = false;
+    let mut project: Option<String> = None;
+    let mut list_projects = false;
+    let mut restore: Option<String> = None;
+    let mut save_snapshot: Option<String> = None;
+    let mut restore_snapshot: Option<String> = None;
+    let mut meter = MeterConfig::default();
+    let mut record_seconds: Option<String> = None;
+    let mut latency_ports: Option<(String, String)> = None;
+    let mut jack_status = false;
+    let mut config_dir: Option<PathBuf> = None;
+    let mut show_paths = false;
+    let mut log_filter: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--export" => export = iter.next().map(PathBuf::from),
+            "--import" => import = iter.next().map(PathBuf::from),
+            "--replace" => replace = true,
+            "--project" => project = iter.next().cloned(),
+            "--list-projects" => list_projects = true,
+            "--restore" => restore = iter.next().cloned(),
+            "--save-snapshot" => save_snapshot = iter.next().cloned(),
+            "--restore-snapshot" => restore_snapshot = iter.next().cloned(),
+            "--meter" => meter.ports.extend(iter.next().cloned()),
+            "--true-peak" => meter.true_peak = true,
+            "--record" => record_seconds = iter.next().cloned(),
+            "--latency" => latency_ports = iter.next().cloned().zip(iter.next().cloned()),
+            "--jack-status" => jack_status = true,
+            "--config-dir" => config_dir = iter.next().map(PathBuf::from),
+            "--paths" => show_paths = true,
+            "--log" => log_filter = iter.next().cloned(),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Some(0);
+            }
+            other => {
+                eprintln!("Unbekannte Option: {}\n\n{}", other, USAGE);
+                return Some(2);
+            }
+        }
+    }
+
+    match paths::init(config_dir) {
+        Ok(notes) => {
+            for note in notes {
+                println!("{}", note);
+            }
+        }
+        Err(e) => {
+            return fail(&e);
+        }
+    }
+    match logging::init(log_filter) {
+        Ok(notes) => {
+            for note in notes {
+                eprintln!("{}", note);
+            }
+        }
+        Err(e) => {
+            return fail(&e);
+        }
+    }
+
+    let has_action = export.is_some()
+        || import.is_some()
+        || restore.is_some()
+        || save_snapshot.is_some()
+        || restore_snapshot.is_some()
+        || !meter.ports.is_empty()
+        || record_seconds.is_some()
+        || latency_ports.is_some()
+        || jack_status
+        || list_projects
+        || show_paths;
+
+    if let Some(name) = project
+        && let Err(e) = Project::switch_to(&name)
+    {
+        return fail(&e);
+    }
+
+    if show_paths {
+        println!("Konfiguration: {}", paths::config_dir().display());
+        println!("Laufzeitdaten: {}", paths::runtime_dir().display());
+        println!("Logs:          {}", paths::log_dir().display());
+    }
+
+    if list_projects {
+        let active = Project::active();
+        for name in Project::list() {
+            let marker = if name == active { "*" } else { " " };
+            println!("{} {}", marker, name);
+        }
+    }
+
+    if let Some(path) = export {
+        match Project::from_current(&Project::active()).and_then(|p| p.export(&path)) {
+            Ok(()) => println!("Projekt exportiert nach {:?}", path),
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if let Some(path) = import {
+        let mode = if replace { ImportMode::Replace } else { ImportMode::Merge };
+        match Project::read(&path).and_then(|p| p.import(mode)) {
+            Ok(conflicts) => {
+                for conflict in conflicts {
+                    println!("Konflikt: {}", conflict);
+                }
+                println!("Projekt importiert aus {:?}", path);
+            }
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if let Some(name) = restore {
+        let (mut programs, _) = ManagedAudioProgram::load_all();
+        let Some(prog) = programs.iter_mut().find(|p| p.config.program_name == name || p.config.id == name) else {
+            return fail(&Error::ProgramNotFound(name));
+        };
+        match prog.restore_previous_config() {
+            Ok(()) => println!("Vorherige Version von {} wiederhergestellt", prog.config.program_name),
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if let Some(name) = save_snapshot {
+        match Snapshot::capture(&name).and_then(|snapshot| snapshot.save()) {
+            Ok(()) => println!("Verbindungszustand '{}' gespeichert", name),
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if let Some(name) = restore_snapshot {
+        let mut record = audit::Record::begin(Trigger::Snapshot(name.clone()));
+        let result = Snapshot::load(&name).and_then(|snapshot| snapshot.restore(&mut record));
+        if let Err(e) = AuditLog::load().commit(record) {
+            eprintln!("{}", e.report());
+        }
+        match result {
+            Ok(skipped) => {
+                for connection in skipped {
+                    println!("Nicht zuzuordnen: {}", connection);
+                }
+                println!("Verbindungszustand '{}' wiederhergestellt", name);
+            }
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if !meter.ports.is_empty() {
+        let measured = Meter::start(&meter).and_then(|m| {
+            Error::from_list(m.sync())?;
+            std::thread::sleep(METER_DURATION);
+            Ok(m.levels())
+        });
+        match measured {
+            Ok(levels) => {
+                for (port, level) in levels {
+                    match level {
+                        Some(level) => println!("{}: {}", port, level),
+                        None => println!("{}: kein Signal", port),
+                    }
+                }
+            }
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if let Some(seconds) = record_seconds {
+        let Ok(seconds) = seconds.parse::<u64>() else {
+            eprintln!("--record erwartet eine Dauer in Sekunden\n\n{}", USAGE);
+            return Some(2);
+        };
+        let config = match RecorderConfig::load() {
+            Ok(config) => config,
+            Err(e) => return fail(&e),
+        };
+        if config.ports.is_empty() {
+            eprintln!("Keine Ports für die Aufnahme ausgewählt");
+            return Some(1);
+        }
+        let recorder = match Recorder::start(&config).and_then(|r| Error::from_list(r.sync()).map(|()| r)) {
+            Ok(recorder) => recorder,
+            Err(e) => return fail(&e),
+        };
+        recorder.record("Kommandozeile");
+        std::thread::sleep(Duration::from_secs(seconds));
+        let status = recorder.close();
+        if let Some(error) = status.error {
+            eprintln!("{}", error);
+            return Some(1);
+        }
+        if let Some(dir) = status.dir {
+            println!("Aufnahme in {}", dir.display());
+        }
+    }
+
+    if jack_status {
+        let history = match XrunHistory::load() {
+            Ok(history) => history,
+            Err(e) => return fail(&e),
+        };
+        match HealthMonitor::start() {
+            Ok(mut monitor) => {
+                // DSP-Last über eine Sekunde beobachten
+                std::thread::sleep(Duration::from_secs(1));
+                monitor.take_xruns();
+                let mut status = monitor.status();
+                // Ein eigener Client sähe nur die Xruns dieser Sekunde
+                if let Some(history) = &history {
+                    status.xruns = history.total;
+                }
+                println!("{}", status);
+            }
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+        match history {
+            Some(history) => {
+                for (event, count) in history.suspects() {
+                    println!("Verdächtig: {}: {} Xruns", event, count);
+                }
+                for line in history.lines() {
+                    println!("  {}", line);
+                }
+            }
+            None => println!("Kein Xrun-Verlauf vorhanden, die Oberfläche wurde noch nicht gestartet"),
+        }
+    }
+
+    if let Some((send, receive)) = latency_ports {
+        match latency::measure(&send, &receive, &|_| Ok(())) {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                return fail(&e);
+            }
+        }
+    }
+
+    if has_action { Some(0) } else { None }
+}
+
+fn fail(e: &Error) -> Option<i32> {
+    eprintln!("{}", e.report());
+    match e.root() {
+        Error::ConfigParse(_) | Error::ConfigInvalid(_) => Some(EXIT_INVALID_CONFIG),
+        _ => Some(1),
+    }
+}
@@ -0,0 +1,356 @@
+use std::{collections::HashSet, fmt, path::{Path, PathBuf}, sync::atomic::{AtomicU32, Ordering}, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::managed_audio_program::AudioProgramConfig;
+use crate::midi_control::{MidiAction, MidiConfig};
+
+/// Aktuelle Version des Konfigurationsformats. Bei jeder inkompatiblen Änderung
+/// erhöhen und in `MIGRATIONS` einen Schritt von der Vorgängerversion ergänzen.
+pub const CURRENT_VERSION: u32 = 2;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Längste Überlappung beim Umschalten. So lange wartet der Use-Case-Wechsel im Hintergrund,
+/// bevor er die alten Verbindungen trennt.
+pub const MAX_OVERLAP_MS: u64 = 5000;
+
+/// Migrationsschritte, jeweils von Version `n` auf `n + 1`.
+const MIGRATIONS: &[(u32, Migration)] = &[(0, migrate_v0_to_v1), (1, migrate_v1_to_v2)];
+
+/// Ein Problem in einer Konfigurationsdatei mit möglichst genauer Ortsangabe.
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn new(file: &Path, field: Option<String>, message: impl Into<String>) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            line: None,
+            column: None,
+            field,
+            message: message.into(),
+        }
+    }
+
+    fn from_json(file: &Path, field: Option<String>, error: &serde_json::Error) -> Self {
+        let (line, column) = if error.line() > 0 {
+            (Some(error.line()), Some(error.column()))
+        } else {
+            (None, None)
+        };
+        // serde_json hängt die Position an die Meldung an, die steht hier schon in eigenen Feldern
+        let message = error.to_string();
+        let message = message
+            .strip_suffix(&format!(" at line {} column {}", error.line(), error.column()))
+            .unwrap_or(&message)
+            .to_string();
+        Self {
+            file: file.to_path_buf(),
+            line,
+            column,
+            field,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, " [{}]", field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// v0: Konfigurationen ohne Versionsfeld. Der Aufbau ist identisch, es wird nur die Version ergänzt.
+fn migrate_v0_to_v1(_value: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// v1: Programme werden über eine feste ID statt über ihren Namen identifiziert.
+/// Das Verzeichnis wird beim Laden auf die ID umbenannt.
+fn migrate_v1_to_v2(value: &mut Value) -> Result<(), String> {
+    if value.get("id").and_then(|id| id.as_str()).is_none_or(|id| id.is_empty()) {
+        value["id"] = Value::from(new_program_id());
+    }
+    Ok(())
+}
+
+/// Erzeugt eine neue, vom Anzeigenamen unabhängige Programm-ID.
+pub fn new_program_id() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+    format!("{:x}{:04x}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed) & 0xFFFF)
+}
+
+/// Prüft, ob ein Programmname angezeigt und exportiert werden kann.
+/// Liefert die Fehlermeldung, falls nicht.
+pub fn check_program_name(name: &str) -> Option<String> {
+    if name.trim().is_empty() {
+        Some("Programmname ist leer".to_string())
+    } else if name.contains(['/', '\\']) || name.chars().any(char::is_control) {
+        Some(format!("Programmname '{}' enthält unzulässige Zeichen", name))
+    } else if name.starts_with('.') {
+        Some(format!("Programmname '{}' darf nicht mit einem Punkt beginnen", name))
+    } else {
+        None
+    }
+}
+
+/// Bringt eine Konfiguration auf `CURRENT_VERSION`. Liefert `true`, wenn migriert wurde.
+pub fn migrate(value: &mut Value, file: &Path) -> Result<bool, Vec<ConfigIssue>> {
+    let Some(object) = value.as_object_mut() else {
+        return Err(vec![ConfigIssue::new(file, None, "Konfiguration ist kein JSON-Objekt")]);
+    };
+    let mut version = match object.get("version") {
+        None => 0,
+        Some(v) => match v.as_u64() {
+            Some(v) => v as u32,
+            None => return Err(vec![ConfigIssue::new(file, Some("version".to_string()), "Version ist keine Zahl")]),
+        },
+    };
+    if version > CURRENT_VERSION {
+        return Err(vec![ConfigIssue::new(
+            file,
+            Some("version".to_string()),
+            format!("Version {} ist neuer als die unterstützte Version {}", version, CURRENT_VERSION),
+        )]);
+    }
+
+    let migrated = version < CURRENT_VERSION;
+    while version < CURRENT_VERSION {
+        let Some((_, migration)) = MIGRATIONS.iter().find(|(from, _)| *from == version) else {
+            return Err(vec![ConfigIssue::new(file, None, format!("Keine Migration von Version {} vorhanden", version))]);
+        };
+        migration(value).map_err(|e| vec![ConfigIssue::new(file, None, format!("Migration von Version {} fehlgeschlagen: {}", version, e))])?;
+        version += 1;
+        value["version"] = Value::from(version);
+    }
+    Ok(migrated)
+}
+
+/// Liest eine JSON-Datei und meldet Fehler mit Zeile, Spalte und Feldpfad.
+pub fn parse_json<T: DeserializeOwned>(text: &str, file: &Path) -> Result<T, Vec<ConfigIssue>> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let field = Some(e.path().to_string()).filter(|p| p != ".");
+        vec![ConfigIssue::from_json(file, field, e.inner())]
+    })
+}
+
+/// Wie `parse_json`, aber für einen bereits gelesenen JSON-Wert; ohne Zeilenangaben.
+pub fn from_value<T: DeserializeOwned>(value: Value, file: &Path) -> Result<T, Vec<ConfigIssue>> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let field = Some(e.path().to_string()).filter(|p| p != ".");
+        vec![ConfigIssue::from_json(file, field, e.inner())]
+    })
+}
+
+/// Lädt eine Programmkonfiguration inklusive Migration. Liefert zusätzlich, ob die Datei
+/// migriert wurde und neu gespeichert werden sollte. Inhaltliche Prüfung: `validate_program`.
+pub fn parse_program_config(text: &str, file: &Path) -> Result<(AudioProgramConfig, bool), Vec<ConfigIssue>> {
+    let mut value: Value = parse_json(text, file)?;
+    let migrated = migrate(&mut value, file)?;
+    // Ohne Migration direkt aus dem Text lesen, damit Zeile und Spalte erhalten bleiben
+    let config: AudioProgramConfig = if migrated {
+        from_value(value, file)?
+    } else {
+        parse_json(text, file)?
+    };
+    Ok((config, migrated))
+}
+
+/// Migriert eine eingebettete Programmkonfiguration (z.B. aus einer Projektdatei).
+pub fn migrate_program_value(mut value: Value, file: &Path) -> Result<AudioProgramConfig, Vec<ConfigIssue>> {
+    migrate(&mut value, file)?;
+    from_value(value, file)
+}
+
+/// Wandelt ein Suchmuster mit `*` in einen regulären Ausdruck um.
+pub fn pattern_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    regex::Regex::new(&format!("^{}$", pattern.replace('*', ".*")))
+}
+
+/// Prüft eine einzelne Programmkonfiguration auf inhaltliche Fehler.
+pub fn validate_program(config: &AudioProgramConfig, file: &Path) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    if config.id.is_empty() || check_program_name(&config.id).is_some() {
+        issues.push(ConfigIssue::new(file, Some("id".to_string()), format!("Ungültige Programm-ID '{}'", config.id)));
+    }
+    if let Some(message) = check_program_name(&config.program_name) {
+        issues.push(ConfigIssue::new(file, Some("program_name".to_string()), message));
+    }
+    if config.command_name.trim().is_empty() {
+        issues.push(ConfigIssue::new(file, Some("command_name".to_string()), "Kommando ist leer"));
+    }
+    for (index, port) in config.jack_ports.iter().enumerate() {
+        if port.source_name.trim().is_empty() {
+            issues.push(ConfigIssue::new(file, Some(format!("jack_ports[{}].source_name", index)), "Quelle ist leer"));
+        }
+        if port.overlap_ms > MAX_OVERLAP_MS {
+            issues.push(ConfigIssue::new(
+                file,
+                Some(format!("jack_ports[{}].overlap_ms", index)),
+                format!("Überlappung von {} ms ist zu lang (höchstens {} ms)", port.overlap_ms, MAX_OVERLAP_MS),
+            ));
+        }
+        for (field, pattern) in [("target_name", &port.target_name), ("target_search_name", &port.target_search_name)] {
+            if pattern.contains('*') && let Err(e) = pattern_regex(pattern) {
+                issues.push(ConfigIssue::new(
+                    file,
+                    Some(format!("jack_ports[{}].{}", index, field)),
+                    format!("Ungültiges Suchmuster '{}': {}", pattern, e),
+                ));
+            }
+        }
+    }
+    issues
+}
+
+/// Prüft programmübergreifende Zusammenhänge: doppelte Namen und Verweise aus den MIDI-Zuordnungen.
+pub fn validate_setup(programs: &[AudioProgramConfig], midi: &MidiConfig, file: &Path) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    for (index, config) in programs.iter().enumerate() {
+        if !ids.insert(config.id.as_str()) {
+            issues.push(ConfigIssue::new(
+                file,
+                Some(format!("programs[{}].id", index)),
+                format!("Programm-ID '{}' ist doppelt vergeben", config.id),
+            ));
+        }
+        if !names.insert(config.program_name.as_str()) {
+            issues.push(ConfigIssue::new(
+                file,
+                Some(format!("programs[{}].program_name", index)),
+                format!("Programmname '{}' ist doppelt vergeben", config.program_name),
+            ));
+        }
+    }
+    for (index, mapping) in midi.mappings.iter().enumerate() {
+        if let MidiAction::StartProgram(id) | MidiAction::StopProgram(id) = &mapping.action
+            && !ids.contains(id.as_str())
+        {
+            issues.push(ConfigIssue::new(
+                file,
+                Some(format!("midi.mappings[{}].action", index)),
+                format!("Unbekanntes Programm '{}'", id),
+            ));
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_audio_program::{JackPort, Switching};
+    use crate::midi_control::{MidiMapping, MidiMessageKind, MidiTrigger};
+
+    fn file() -> PathBuf {
+        PathBuf::from("config.json")
+    }
+
+    fn program(id: &str, name: &str) -> AudioProgramConfig {
+        AudioProgramConfig {
+            version: CURRENT_VERSION,
+            id: id.to_string(),
+            program_name: name.to_string(),
+            command_name: "jack_rec".to_string(),
+            start_params: Vec::new(),
+            jack_ports: vec![port("out_1", 0)],
+        }
+    }
+
+    fn port(source: &str, overlap_ms: u64) -> JackPort {
+        JackPort {
+            filter: "live".to_string(),
+            source_name: source.to_string(),
+            target_search_name: "system".to_string(),
+            target_name: "playback_1".to_string(),
+            optional: false,
+            switching: Switching::MakeBeforeBreak,
+            overlap_ms,
+            loudness: false,
+        }
+    }
+
+    fn fields(issues: &[ConfigIssue]) -> Vec<&str> {
+        issues.iter().filter_map(|issue| issue.field.as_deref()).collect()
+    }
+
+    #[test]
+    fn migrates_v1_to_stable_id() {
+        let text = r#"{"version": 1, "program_name": "Mikrofon", "command_name": "jack_rec", "jack_ports": []}"#;
+        let (config, migrated) = parse_program_config(text, &file()).unwrap();
+        assert!(migrated);
+        assert_eq!(config.version, CURRENT_VERSION);
+        assert_eq!(config.program_name, "Mikrofon");
+        assert!(!config.id.is_empty());
+        assert!(validate_program(&config, &file()).is_empty());
+    }
+
+    #[test]
+    fn migration_keeps_existing_id() {
+        let text = r#"{"version": 1, "id": "abc", "program_name": "Mikrofon", "command_name": "jack_rec"}"#;
+        let (config, _) = parse_program_config(text, &file()).unwrap();
+        assert_eq!(config.id, "abc");
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let text = r#"{"version": 2, "id": "abc", "program_name": "Mikrofon", "command_name": "jack_rec"}"#;
+        let (_, migrated) = parse_program_config(text, &file()).unwrap();
+        assert!(!migrated);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let text = r#"{"version": 99, "id": "abc", "program_name": "Mikrofon", "command_name": "jack_rec"}"#;
+        let issues = parse_program_config(text, &file()).unwrap_err();
+        assert_eq!(fields(&issues), ["version"]);
+    }
+
+    #[test]
+    fn reports_empty_source_and_long_overlap() {
+        let mut config = program("a", "Mikrofon");
+        config.jack_ports = vec![port(" ", 0), port("out_2", MAX_OVERLAP_MS), port("out_3", MAX_OVERLAP_MS + 1)];
+        let issues = validate_program(&config, &file());
+        assert_eq!(fields(&issues), ["jack_ports[0].source_name", "jack_ports[2].overlap_ms"]);
+    }
+
+    #[test]
+    fn reports_duplicate_ids_and_names() {
+        let programs = [program("a", "Mikrofon"), program("a", "Zuspieler"), program("b", "Mikrofon")];
+        let issues = validate_setup(&programs, &MidiConfig::default(), &file());
+        assert_eq!(fields(&issues), ["programs[1].id", "programs[2].program_name"]);
+    }
+
+    #[test]
+    fn reports_unknown_midi_program() {
+        let trigger = MidiTrigger { kind: MidiMessageKind::NoteOn, channel: 0, number: 36 };
+        let midi = MidiConfig {
+            mappings: vec![
+                MidiMapping { trigger, action: MidiAction::StartProgram("a".to_string()) },
+                MidiMapping { trigger, action: MidiAction::StopProgram("x".to_string()) },
+            ],
+            ..MidiConfig::default()
+        };
+        let issues = validate_setup(&[program("a", "Mikrofon")], &midi, &file());
+        assert_eq!(fields(&issues), ["midi.mappings[1].action"]);
+    }
+}
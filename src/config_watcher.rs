@@ -0,0 +1,120 @@
+use std::{path::Path, sync::mpsc, time::Duration};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{AudioProgramConfig, ManagedAudioProgram};
+
+/// Wartezeit nach dem letzten Ereignis, bevor neu geladen wird. Editoren und Ansible
+/// schreiben oft in mehreren Schritten.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Beobachtet das Konfigurationsverzeichnis, solange die Instanz lebt.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Ruft `on_change` in einem eigenen Thread auf, sobald sich eine `config.json`
+    /// oder die `midi.json` geändert hat.
+    pub fn start<F>(dir: &Path, on_change: F) -> Result<Self>
+    where
+        F: Fn() + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event
+                && event.paths.iter().any(|p| is_watched_file(p))
+            {
+                let _ = tx.send(());
+            }
+        })
+        .map_err(|source| Error::Watch { path: dir.to_path_buf(), source })?;
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|source| Error::Watch { path: dir.to_path_buf(), source })?;
+
+        std::thread::spawn(move || {
+            while rx.recv().is_ok() {
+                while rx.recv_timeout(DEBOUNCE).is_ok() {}
+                on_change();
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+fn is_watched_file(path: &Path) -> bool {
+    // Sicherungen liegen in eigenen Unterverzeichnissen, temporäre Dateien beginnen mit einem Punkt
+    let in_backup = path
+        .parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|n| n == crate::atomic_file::BACKUP_DIR);
+    !in_backup && path.file_name().is_some_and(|n| n == "config.json" || n == "midi.json")
+}
+
+/// Ergebnis eines Abgleichs der Programme im Speicher mit dem Konfigurationsverzeichnis.
+#[derive(Default)]
+pub struct SyncResult {
+    pub changed: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Programme mit ungespeicherten Änderungen, deren Datei sich ebenfalls geändert hat:
+    /// ID und Fassung auf der Platte.
+    pub conflicts: Vec<(String, AudioProgramConfig)>,
+    pub errors: Vec<Error>,
+}
+
+impl SyncResult {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_empty() && self.removed.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+/// Übernimmt Änderungen auf der Platte in `programs`. Programme mit ungespeicherten
+/// Änderungen werden nicht überschrieben, sondern als Konflikt gemeldet. Die Platte wird
+/// nur gelesen; Fassungen, die die Anwendung selbst geschrieben hat, gelten nicht als Änderung.
+pub fn sync_programs(programs: &mut Vec<ManagedAudioProgram>) -> SyncResult {
+    let mut result = SyncResult::default();
+    let (on_disk, errors) = ManagedAudioProgram::read_all();
+    result.errors = errors;
+
+    for (disk, jack_node_name) in on_disk.iter() {
+        match programs.iter_mut().find(|p| p.config.id == disk.id) {
+            Some(prog) if prog.config == *disk || prog.saved_config.as_ref() == Some(disk) => {}
+            Some(prog) if prog.dirty => {
+                tracing::warn!(program = %prog.config.program_name, "Konfiguration extern geändert, es gibt ungespeicherte Änderungen");
+                result.conflicts.push((prog.config.id.clone(), disk.clone()));
+            }
+            Some(prog) => {
+                prog.config = disk.clone();
+                prog.saved_config = Some(disk.clone());
+                tracing::info!(program = %prog.config.program_name, "Konfiguration extern geändert, neu geladen");
+                result.changed.push(prog.config.program_name.clone());
+            }
+            None => {
+                tracing::info!(program = %disk.program_name, "Programm extern hinzugefügt");
+                result.added.push(disk.program_name.clone());
+                let mut prog = ManagedAudioProgram::from_config(disk.clone());
+                prog.saved_config = Some(disk.clone());
+                prog.jack_node_name = jack_node_name.clone();
+                programs.push(prog);
+            }
+        }
+    }
+
+    // Programme, deren Verzeichnis gelöscht wurde. Neue, nie gespeicherte Programme sind als geändert markiert.
+    programs.retain(|prog| {
+        let deleted = !prog.dirty
+            && !on_disk.iter().any(|(d, _)| d.id == prog.config.id)
+            && !prog.dir().exists();
+        if deleted {
+            tracing::info!(program = %prog.config.program_name, "Programm extern entfernt");
+            result.removed.push(prog.config.program_name.clone());
+        }
+        !deleted
+    });
+
+    result
+}
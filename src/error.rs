@@ -0,0 +1,245 @@
+use std::{io, num::ParseIntError, path::PathBuf};
+
+use crate::config_schema::ConfigIssue;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Fehler der Anwendung. Die Meldungen sind deutsch; Oberfläche, CLI und API können
+/// anhand der Variante reagieren und eigene Texte verwenden. Die Ursache steht nicht
+/// in der Meldung selbst, sondern in `source()`; die vollständige Kette liefert `report()`.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{program} läuft bereits (PID {pid})")]
+    AlreadyRunning { program: String, pid: u32 },
+
+    #[error("Noch laufende Programme zuerst beenden: {}", .0.join(", "))]
+    ProgramsRunning(Vec<String>),
+
+    #[error("{program} läuft nicht")]
+    NotRunning { program: String },
+
+    #[error("Fehler beim Starten von '{command}'")]
+    Spawn {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Prozess mit PID {pid} konnte nicht beendet werden")]
+    Kill {
+        pid: u32,
+        #[source]
+        source: Option<io::Error>,
+    },
+
+    #[error("Fehler beim Zugriff auf die PID-Datei {path:?}")]
+    PidIo {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Ungültige PID in {path:?}")]
+    PidInvalid {
+        path: PathBuf,
+        #[source]
+        source: ParseIntError,
+    },
+
+    #[error("PID-Datei {path:?} existiert nicht")]
+    PidMissing { path: PathBuf },
+
+    #[error("Fehler beim Zugriff auf {path:?}")]
+    ConfigIo {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// Die Datei ist kein gültiges JSON oder passt nicht zum Format.
+    #[error("{}", join_issues(.0))]
+    ConfigParse(Vec<ConfigIssue>),
+
+    /// Die Datei ist lesbar, aber inhaltlich fehlerhaft.
+    #[error("{}", join_issues(.0))]
+    ConfigInvalid(Vec<ConfigIssue>),
+
+    #[error("Fehler beim Erzeugen von {path:?}")]
+    Serialize {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Verzeichnis {path:?} existiert bereits")]
+    AlreadyExists { path: PathBuf },
+
+    #[error("Fehler beim Laden der Konfiguration für '{dir}'")]
+    LoadProgram {
+        dir: String,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("Programm nicht gefunden: {0}")]
+    ProgramNotFound(String),
+
+    #[error("{program} hat keine Verbindung Nr. {index}")]
+    ConnectionNotFound { program: String, index: i32 },
+
+    #[error("Ungültiger Name: '{0}'")]
+    InvalidName(String),
+
+    #[error("JACK-Werkzeug {command} konnte nicht ausgeführt werden")]
+    Jack {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{command} fehlgeschlagen: {message}")]
+    JackCommand { command: String, message: String },
+
+    #[error("JACK: {action} fehlgeschlagen")]
+    JackClient {
+        action: String,
+        #[source]
+        source: jack::Error,
+    },
+
+    #[error("Fehler beim Schreiben der Aufnahme {path:?}")]
+    Recording {
+        path: PathBuf,
+        #[source]
+        source: hound::Error,
+    },
+
+    #[error("Zu wenig freier Speicher für die Aufnahme in {path:?}: {free_mb} MB")]
+    DiskFull { path: PathBuf, free_mb: u64 },
+
+    #[error("Testton-Frequenz {frequency_hz} Hz liegt nicht zwischen 0 und {max_hz} Hz")]
+    ToneFrequency { frequency_hz: f32, max_hz: f32 },
+
+    #[error("Messpuffer der Latenzmessung übergelaufen, bitte wiederholen")]
+    LatencyOverflow,
+
+    #[error("JACK-Port nicht gefunden: {port}")]
+    PortNotFound { port: String },
+
+    #[error("Ungültiges Suchmuster '{pattern}'")]
+    Pattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+
+    #[error("Kein JACK-Port passt auf '{pattern}'")]
+    Resolution { pattern: String },
+
+    #[error("Es gibt keine Verbindungsänderung, die rückgängig gemacht werden kann")]
+    NothingToUndo,
+
+    #[error("Vor dem Start von '{0}' konnte der Verbindungszustand nicht erfasst werden")]
+    SnapshotMissing(String),
+
+    #[error("Abgebrochen")]
+    Cancelled,
+
+    #[error("{0}")]
+    Midi(String),
+
+    #[error("Fehler beim Überwachen von {path:?}")]
+    Watch {
+        path: PathBuf,
+        #[source]
+        source: notify::Error,
+    },
+
+    #[error("{0}")]
+    Paths(String),
+
+    #[error("Ungültiger Log-Filter '{filter}'")]
+    LogFilter {
+        filter: String,
+        #[source]
+        source: tracing_subscriber::filter::ParseError,
+    },
+
+    #[error("Log-Datei in {path:?} konnte nicht angelegt werden")]
+    LogFile {
+        path: PathBuf,
+        #[source]
+        source: tracing_appender::rolling::InitError,
+    },
+
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("{} Fehler", .0.len())]
+    Multiple(Vec<Error>),
+}
+
+fn join_issues(issues: &[ConfigIssue]) -> String {
+    issues.iter().map(|issue| issue.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+impl Error {
+    /// `Ok`, wenn die Liste leer ist, sonst der einzelne Fehler bzw. `Multiple`.
+    pub fn from_list(mut errors: Vec<Error>) -> Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+
+    /// Ergänzt den Fehler um eine Beschreibung dessen, was gerade versucht wurde.
+    pub fn context(self, context: impl Into<String>) -> Error {
+        Error::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Der eigentliche Fehler ohne umgebenden Kontext.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } | Error::LoadProgram { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Meldung mit allen Ursachen, mehrere Fehler zeilenweise.
+    pub fn report(&self) -> String {
+        match self {
+            Error::Multiple(errors) => errors.iter().map(Error::report).collect::<Vec<_>>().join("\n"),
+            Error::Context { context, source } => format!("{}: {}", context, source.report()),
+            Error::LoadProgram { source, .. } => format!("{}: {}", self, source.report()),
+            error => {
+                let mut text = error.to_string();
+                let mut source = std::error::Error::source(error);
+                while let Some(cause) = source {
+                    text.push_str(": ");
+                    text.push_str(&cause.to_string());
+                    source = cause.source();
+                }
+                text
+            }
+        }
+    }
+}
+
+/// Ergänzt einen Fehler um eine Beschreibung dessen, was gerade versucht wurde.
+pub trait ResultExt<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|e| e.context(context))
+    }
+}
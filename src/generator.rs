@@ -0,0 +1,213 @@
+use std::{
+    f32::consts::PI,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use jack::{AudioOut, Client, ClientOptions, Control, Port, ProcessHandler, ProcessScope};
+
+use crate::error::{Error, Result};
+
+/// Name des JACK-Clients mit den Testton-Ausgängen.
+const GENERATOR_CLIENT: &str = "jackstreamingmanager-generator";
+
+/// Länge eines Pieptons und der Pause danach bei der Kanalkennung (Sekunden).
+const BEEP_S: f32 = 0.15;
+
+/// Pause nach der Pieptonfolge eines Kanals (Sekunden).
+const SEQUENCE_PAUSE_S: f32 = 1.0;
+
+/// Ein- und Ausblenden der Pieptöne gegen Knacksen (Sekunden).
+const RAMP_S: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    PinkNoise,
+    /// Jeder Kanal piept so oft, wie seine Nummer angibt, danach eine Pause
+    Identification,
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Waveform::Sine => write!(f, "Sinus"),
+            Waveform::PinkNoise => write!(f, "Rosa Rauschen"),
+            Waveform::Identification => write!(f, "Kanalkennung"),
+        }
+    }
+}
+
+/// Einstellungen des Testtons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// Frequenz des Sinus und der Pieptöne
+    pub frequency_hz: f32,
+    /// Spitzenwert beim Sinus, RMS beim Rauschen (dBFS)
+    pub level_db: f32,
+}
+
+impl fmt::Display for Tone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.waveform {
+            Waveform::PinkNoise => write!(f, "{} {:.0} dBFS", self.waveform, self.level_db),
+            _ => write!(f, "{} {:.0} Hz {:.0} dBFS", self.waveform, self.frequency_hz, self.level_db),
+        }
+    }
+}
+
+/// Zustand eines Ausgangs im Audio-Thread.
+struct Output {
+    port: Port<AudioOut>,
+    /// Kanalnummer ab 1 für die Kanalkennung
+    number: usize,
+}
+
+struct Process {
+    outputs: Vec<Output>,
+    tone: Tone,
+    amplitude: f32,
+    rate: f32,
+    phase: f32,
+    /// Samples seit dem Start, für die Pieptonfolge
+    position: u64,
+    /// Zustand des Zufallsgenerators (xorshift) und des Rosa-Filters
+    random: u32,
+    pink: [f32; 3],
+}
+
+impl Process {
+    fn white(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// Rosa Rauschen nach Paul Kellet (sparsame Variante), etwa -3 dB je Oktave.
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        self.pink[0] = 0.99765 * self.pink[0] + white * 0.0990460;
+        self.pink[1] = 0.96300 * self.pink[1] + white * 0.2965164;
+        self.pink[2] = 0.57000 * self.pink[2] + white * 1.0526913;
+        // Auf einen RMS von 1 normiert, damit der Pegel dem eingestellten RMS entspricht
+        (self.pink[0] + self.pink[1] + self.pink[2] + white * 0.1848) / 1.71
+    }
+
+    /// Hüllkurve der Kanalkennung für Kanal `number` an der aktuellen Position.
+    fn beep_envelope(&self, number: usize) -> f32 {
+        let time = self.position as f32 / self.rate;
+        let sequence = number as f32 * 2.0 * BEEP_S + SEQUENCE_PAUSE_S;
+        let offset = time % sequence;
+        if offset >= number as f32 * 2.0 * BEEP_S || offset % (2.0 * BEEP_S) >= BEEP_S {
+            return 0.0;
+        }
+        let within = offset % (2.0 * BEEP_S);
+        (within / RAMP_S).min((BEEP_S - within) / RAMP_S).min(1.0)
+    }
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        let frames = ps.n_frames() as usize;
+        let step = 2.0 * PI * self.tone.frequency_hz / self.rate;
+        for frame in 0..frames {
+            let carrier = match self.tone.waveform {
+                Waveform::PinkNoise => self.pink(),
+                _ => self.phase.sin(),
+            };
+            self.phase = (self.phase + step) % (2.0 * PI);
+            for index in 0..self.outputs.len() {
+                let envelope = match self.tone.waveform {
+                    Waveform::Identification => self.beep_envelope(self.outputs[index].number),
+                    _ => 1.0,
+                };
+                self.outputs[index].port.as_mut_slice(ps)[frame] = (carrier * envelope * self.amplitude).clamp(-1.0, 1.0);
+            }
+            self.position += 1;
+        }
+        Control::Continue
+    }
+}
+
+/// Eigener JACK-Client, der einen Testton auf die angegebenen Ziele gibt, einen Ausgang je
+/// Ziel. Die Verbindungen verschwinden mit dem Client, wenn die Instanz verworfen wird;
+/// nach Ablauf der Dauer muss der Aufrufer sie verwerfen.
+pub struct Generator {
+    _client: jack::AsyncClient<(), Process>,
+    tone: Tone,
+    targets: Vec<String>,
+    until: Instant,
+}
+
+impl Generator {
+    pub fn start(tone: Tone, targets: &[String], duration: Duration) -> Result<Self> {
+        let jack_error = |action: &str| {
+            let action = action.to_string();
+            move |source| Error::JackClient { action, source }
+        };
+        let (client, _) = Client::new(GENERATOR_CLIENT, ClientOptions::NO_START_SERVER)
+            .map_err(jack_error("Testton anmelden"))?;
+        let max_hz = client.sample_rate() as f32 / 2.0;
+        if !(tone.frequency_hz > 0.0 && tone.frequency_hz < max_hz) {
+            return Err(Error::ToneFrequency { frequency_hz: tone.frequency_hz, max_hz });
+        }
+        let mut outputs = Vec::new();
+        for index in 0..targets.len() {
+            let port = client
+                .register_port(&output_name(index), AudioOut)
+                .map_err(jack_error("Testton-Ausgang anlegen"))?;
+            outputs.push(Output { port, number: index + 1 });
+        }
+        let process = Process {
+            outputs,
+            tone,
+            amplitude: 10f32.powf(tone.level_db.min(0.0) / 20.0),
+            rate: client.sample_rate() as f32,
+            phase: 0.0,
+            position: 0,
+            random: 0x9e37_79b9,
+            pink: [0.0; 3],
+        };
+        let client = client.activate_async((), process).map_err(jack_error("Testton aktivieren"))?;
+        let own = client.as_client().name().to_string();
+        let mut errors = Vec::new();
+        for (index, target) in targets.iter().enumerate() {
+            let output = format!("{}:{}", own, output_name(index));
+            if let Err(source) = client.as_client().connect_ports_by_name(&output, target) {
+                errors.push(Error::JackClient { action: format!("Testton mit {} verbinden", target), source });
+            }
+        }
+        Error::from_list(errors)?;
+        tracing::info!(tone = %tone, targets = ?targets, seconds = duration.as_secs(), "Testton gestartet");
+        Ok(Self { _client: client, tone, targets: targets.to_vec(), until: Instant::now() + duration })
+    }
+
+    pub fn tone(&self) -> Tone {
+        self.tone
+    }
+
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.until.saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        Instant::now() >= self.until
+    }
+}
+
+impl Drop for Generator {
+    fn drop(&mut self) {
+        tracing::info!(targets = ?self.targets, "Testton beendet");
+    }
+}
+
+fn output_name(index: usize) -> String {
+    format!("out_{}", index + 1)
+}
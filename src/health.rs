@@ -0,0 +1,385 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, fs,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::{DateTime, Local};
+use jack::{Client, ClientOptions, ClientStatus, Control, NotificationHandler};
+
+use crate::error::{Error, Result};
+use crate::status::{ProgramState, ProgramStatus};
+use crate::{atomic_file, config_schema, paths};
+
+/// Name des JACK-Clients, der den Server beobachtet.
+const HEALTH_CLIENT: &str = "jackstreamingmanager-health";
+
+/// So viele Xruns und Programmereignisse bleiben im Verlauf.
+const HISTORY: usize = 200;
+
+/// So viele Xruns innerhalb von `BURST_WINDOW_S` Sekunden gelten als Häufung.
+pub const BURST_COUNT: usize = 3;
+const BURST_WINDOW_S: i64 = 10;
+
+/// Ab dieser DSP-Last (Prozent) wird gewarnt.
+pub const HIGH_LOAD: f32 = 80.0;
+
+/// Ein Xrun wird dem letzten Programmereignis zugeordnet, wenn er höchstens so viele Sekunden danach auftritt.
+const CORRELATION_WINDOW_S: i64 = 30;
+
+/// Kennzahlen des JACK-Servers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatus {
+    pub server: String,
+    pub sample_rate: usize,
+    pub buffer_size: u32,
+    /// Prozent
+    pub dsp_load: f32,
+    /// Xruns seit dem Start der Beobachtung
+    pub xruns: u64,
+}
+
+impl ServerStatus {
+    /// Dauer eines Puffers in Millisekunden.
+    pub fn period_ms(&self) -> f64 {
+        f64::from(self.buffer_size) * 1000.0 / self.sample_rate.max(1) as f64
+    }
+}
+
+impl fmt::Display for ServerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "JACK-Server '{}': {} Hz, {} Frames ({:.1} ms), DSP-Last {:.0} %, {} Xruns",
+            self.server,
+            self.sample_rate,
+            self.buffer_size,
+            self.period_ms(),
+            self.dsp_load,
+            self.xruns
+        )
+    }
+}
+
+struct Notifications {
+    xruns: Arc<Mutex<Vec<DateTime<Local>>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl NotificationHandler for Notifications {
+    fn xrun(&mut self, _: &Client) -> Control {
+        self.xruns.lock().unwrap().push(Local::now());
+        Control::Continue
+    }
+
+    fn shutdown(&mut self, _: ClientStatus, reason: &str) {
+        tracing::warn!(reason, "JACK-Server beendet");
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Eigener JACK-Client ohne Ports, der Xruns mitzählt und Kennzahlen des Servers abfragt.
+pub struct HealthMonitor {
+    client: jack::AsyncClient<Notifications, ()>,
+    server: String,
+    pending: Arc<Mutex<Vec<DateTime<Local>>>>,
+    stopped: Arc<AtomicBool>,
+    xruns: u64,
+}
+
+impl HealthMonitor {
+    pub fn start() -> Result<Self> {
+        let jack_error = |action: &str| {
+            let action = action.to_string();
+            move |source| Error::JackClient { action, source }
+        };
+        let (client, _) = Client::new(HEALTH_CLIENT, ClientOptions::NO_START_SERVER)
+            .map_err(jack_error("Serverbeobachtung anmelden"))?;
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let notifications = Notifications { xruns: pending.clone(), stopped: stopped.clone() };
+        let client = client.activate_async(notifications, ()).map_err(jack_error("Serverbeobachtung aktivieren"))?;
+        Ok(Self { client, server: server_name(), pending, stopped, xruns: 0 })
+    }
+
+    /// Der Server wurde beendet, die Instanz ist nicht mehr brauchbar.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Xruns seit dem letzten Aufruf.
+    pub fn take_xruns(&mut self) -> Vec<DateTime<Local>> {
+        let xruns = std::mem::take(&mut *self.pending.lock().unwrap());
+        self.xruns += xruns.len() as u64;
+        xruns
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        let client = self.client.as_client();
+        ServerStatus {
+            server: self.server.clone(),
+            sample_rate: client.sample_rate(),
+            buffer_size: client.buffer_size(),
+            dsp_load: client.cpu_load(),
+            xruns: self.xruns,
+        }
+    }
+}
+
+/// Name des Servers, mit dem sich die Clients verbinden.
+fn server_name() -> String {
+    std::env::var("JACK_DEFAULT_SERVER").ok().filter(|name| !name.is_empty()).unwrap_or_else(|| "default".to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramEvent {
+    Started,
+    Stopped,
+    Crashed,
+}
+
+impl fmt::Display for ProgramEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramEvent::Started => write!(f, "gestartet"),
+            ProgramEvent::Stopped => write!(f, "gestoppt"),
+            ProgramEvent::Crashed => write!(f, "abgestürzt"),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entry {
+    Xrun {
+        #[serde(with = "local_time")]
+        time: DateTime<Local>,
+    },
+    Program {
+        #[serde(with = "local_time")]
+        time: DateTime<Local>,
+        program: String,
+        event: ProgramEvent,
+    },
+}
+
+impl Entry {
+    fn time(&self) -> DateTime<Local> {
+        match self {
+            Entry::Xrun { time } | Entry::Program { time, .. } => *time,
+        }
+    }
+}
+
+/// Zeitpunkte im Verlauf als RFC 3339 mit Zeitzone.
+mod local_time {
+    use chrono::{DateTime, Local};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&time.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Local>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&text).map(|time| time.with_timezone(&Local)).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Verlauf der Xruns zusammen mit Start und Ende der Programme, damit sich erkennen lässt,
+/// nach welchem Programm die Aussetzer beginnen. Hängt nur von den übergebenen Zeitpunkten ab.
+/// Die Oberfläche legt ihn im Log-Verzeichnis ab, damit `--jack-status` ihn anzeigen kann.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct XrunHistory {
+    /// Nach Zeit sortiert, älteste zuerst
+    entries: VecDeque<Entry>,
+    /// Alle Xruns, auch die aus dem Verlauf gefallenen
+    #[serde(default)]
+    pub total: u64,
+    /// Letzter bekannter Zustand je Programm
+    #[serde(skip)]
+    states: HashMap<String, ProgramState>,
+    /// Seit dem letzten `save` geändert
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl XrunHistory {
+    /// Leerer Verlauf, der beim ersten `save` einen älteren aus der Datei ersetzt.
+    pub fn new() -> Self {
+        Self { changed: true, ..Self::default() }
+    }
+
+    fn path() -> PathBuf {
+        paths::log_dir().join("xruns.json")
+    }
+
+    /// Der zuletzt von der Oberfläche abgelegte Verlauf, `None` ohne Datei.
+    pub fn load() -> Result<Option<Self>> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path).map_err(|source| Error::ConfigIo { path: path.clone(), source })?;
+        config_schema::parse_json(&text, &path).map(Some).map_err(Error::ConfigParse)
+    }
+
+    /// Schreibt den Verlauf, wenn er sich seit dem letzten Aufruf geändert hat.
+    pub fn save(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.changed) {
+            return Ok(());
+        }
+        let path = Self::path();
+        let data = serde_json::to_vec_pretty(self).map_err(|source| Error::Serialize { path: path.clone(), source })?;
+        atomic_file::write_atomic(&path, &data).map_err(|source| Error::ConfigIo { path, source })
+    }
+
+
+    /// Leitet aus den Zuständen der Programme Start- und Endereignisse ab. Beim ersten
+    /// Aufruf wird nur der Zustand übernommen.
+    pub fn observe(&mut self, statuses: &[ProgramStatus], now: DateTime<Local>) {
+        let first = self.states.is_empty();
+        for status in statuses {
+            let previous = self.states.insert(status.program.clone(), status.state);
+            let running = |state: ProgramState| !matches!(state, ProgramState::Stopped | ProgramState::Crashed);
+            let event = match previous {
+                _ if first => None,
+                Some(previous) if running(previous) == running(status.state) => None,
+                _ if status.state == ProgramState::Crashed => Some(ProgramEvent::Crashed),
+                _ if running(status.state) => Some(ProgramEvent::Started),
+                None => None,
+                Some(_) => Some(ProgramEvent::Stopped),
+            };
+            if let Some(event) = event {
+                self.push(Entry::Program { time: now, program: status.program.clone(), event });
+            }
+        }
+    }
+
+    pub fn record(&mut self, xruns: &[DateTime<Local>]) {
+        for &time in xruns {
+            self.push(Entry::Xrun { time });
+        }
+        self.total += xruns.len() as u64;
+    }
+
+    /// Sortiert ein, denn Xruns kommen gesammelt und mit eigenem Zeitpunkt an.
+    fn push(&mut self, entry: Entry) {
+        let index = self.entries.partition_point(|e| e.time() <= entry.time());
+        self.entries.insert(index, entry);
+        self.changed = true;
+        while self.entries.len() > HISTORY {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Zahl der Xruns in den letzten Sekunden vor `now`.
+    pub fn recent(&self, now: DateTime<Local>) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry, Entry::Xrun { time } if (now - *time).num_seconds() < BURST_WINDOW_S))
+            .count()
+    }
+
+    /// Das letzte Programmereignis vor dem Eintrag an `index`, falls es nicht zu lange her ist.
+    fn cause(&self, index: usize) -> Option<(&str, ProgramEvent, i64)> {
+        let time = self.entries[index].time();
+        self.entries.iter().take(index).rev().find_map(|entry| match entry {
+            Entry::Program { time: event_time, program, event } => {
+                let seconds = (time - *event_time).num_seconds();
+                (seconds <= CORRELATION_WINDOW_S).then_some((program.as_str(), *event, seconds))
+            }
+            Entry::Xrun { .. } => None,
+        })
+    }
+
+    /// Verlauf als Text, das Neueste zuerst.
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.entries.len())
+            .rev()
+            .map(|index| match &self.entries[index] {
+                Entry::Program { time, program, event } => format!("{} {} {}", time.format("%H:%M:%S"), program, event),
+                Entry::Xrun { time } => match self.cause(index) {
+                    Some((program, event, seconds)) => {
+                        format!("{} Xrun, {} s nachdem {} {} wurde", time.format("%H:%M:%S"), seconds, program, event)
+                    }
+                    None => format!("{} Xrun", time.format("%H:%M:%S")),
+                },
+            })
+            .collect()
+    }
+
+    /// Programmereignisse mit der Zahl der Xruns, die ihnen zugeordnet wurden, die häufigsten zuerst.
+    pub fn suspects(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for index in 0..self.entries.len() {
+            if !matches!(self.entries[index], Entry::Xrun { .. }) {
+                continue;
+            }
+            let Some((program, event, _)) = self.cause(index) else { continue };
+            let key = format!("{} {}", program, event);
+            match counts.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((key, 1)),
+            }
+        }
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        counts
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(program: &str, state: ProgramState) -> ProgramStatus {
+        ProgramStatus {
+            program: program.to_string(),
+            state,
+            pid: None,
+            uptime: None,
+            cpu: None,
+            memory: None,
+            jack_node_name: program.to_string(),
+            routes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn entries_are_merged_in_time_order() {
+        let start = Local::now();
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+        let mut history = XrunHistory::new();
+        history.observe(&[status("baresip", ProgramState::Stopped)], at(0));
+        history.observe(&[status("baresip", ProgramState::Running)], at(10));
+        // Xruns kommen gesammelt und nach dem Programmereignis an, auch solche von davor
+        history.record(&[at(12), at(5)]);
+        history.record(&[at(11), at(2)]);
+        let times: Vec<DateTime<Local>> = history.entries.iter().map(Entry::time).collect();
+        assert!(times.is_sorted());
+        assert_eq!(times, [at(2), at(5), at(10), at(11), at(12)]);
+        assert_eq!(history.total, 4);
+    }
+
+    #[test]
+    fn xrun_before_event_has_no_cause() {
+        let start = Local::now();
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+        let mut history = XrunHistory::new();
+        history.observe(&[status("baresip", ProgramState::Stopped)], at(0));
+        history.observe(&[status("baresip", ProgramState::Running)], at(10));
+        history.record(&[at(13), at(4)]);
+        // Nur Ereignisse vor dem Xrun kommen als Ursache in Frage, nie mit negativem Abstand
+        let lines = history.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("Xrun, 3 s nachdem baresip gestartet wurde"), "{}", lines[0]);
+        assert!(lines[2].ends_with(" Xrun"), "{}", lines[2]);
+        assert_eq!(history.suspects(), [("baresip gestartet".to_string(), 1)]);
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    f32::consts::PI,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use jack::{AudioIn, AudioOut, Client, ClientOptions, Control, LatencyType, Port, ProcessHandler, ProcessScope, RingBuffer, RingBufferWriter};
+
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{JackPortInfo, ManagedAudioProgram, resolve_connection};
+use crate::meter;
+
+/// Name des JACK-Clients für die Latenzmessung.
+const LATENCY_CLIENT: &str = "jackstreamingmanager-latency";
+
+/// So viele Testsignale werden nacheinander gesendet.
+const PULSES: usize = 5;
+
+/// Abstand der Testsignale, begrenzt zugleich die messbare Latenz (Sekunden).
+const PULSE_INTERVAL_S: f32 = 1.0;
+
+/// Länge und Frequenzbereich des Testsignals, ein Sweep lässt sich auch nach einem Codec
+/// noch sicher wiedererkennen.
+const CHIRP_S: f32 = 0.02;
+const CHIRP_FROM_HZ: f32 = 500.0;
+const CHIRP_TO_HZ: f32 = 4000.0;
+
+/// Pegel des Testsignals (linear, -6 dBFS).
+const CHIRP_LEVEL: f32 = 0.5;
+
+/// Mindestkorrelation, ab der ein Testsignal als erkannt gilt.
+const DETECTION: f32 = 0.5;
+
+/// Ergebnis einer Umlaufmessung, je Testsignal die Latenz in Frames.
+#[derive(Debug, Clone)]
+pub struct LatencyReport {
+    pub send: String,
+    pub receive: String,
+    pub sample_rate: u32,
+    /// `None` für Testsignale, die nicht zurückkamen
+    pub latencies: Vec<Option<u32>>,
+}
+
+impl LatencyReport {
+    fn detected(&self) -> Vec<u32> {
+        let mut detected: Vec<u32> = self.latencies.iter().flatten().copied().collect();
+        detected.sort_unstable();
+        detected
+    }
+
+    /// Median der erkannten Testsignale in Frames.
+    pub fn frames(&self) -> Option<u32> {
+        let detected = self.detected();
+        detected.get(detected.len() / 2).copied()
+    }
+
+    pub fn millis(&self, frames: u32) -> f64 {
+        f64::from(frames) * 1000.0 / f64::from(self.sample_rate)
+    }
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let detected = self.detected();
+        let (Some(frames), Some(min), Some(max)) = (self.frames(), detected.first(), detected.last()) else {
+            return write!(f, "{} -> {}: Testsignal nicht erkannt", self.send, self.receive);
+        };
+        write!(
+            f,
+            "{} -> {}: {} Frames = {:.1} ms (min {}, max {}, {} von {} Testsignalen erkannt)",
+            self.send,
+            self.receive,
+            frames,
+            self.millis(frames),
+            min,
+            max,
+            detected.len(),
+            self.latencies.len()
+        )
+    }
+}
+
+/// Von JACK gemeldete Portlatenzen einer konfigurierten Verbindung, jeweils Minimum und Maximum in Frames.
+#[derive(Debug, Clone)]
+pub struct RouteLatency {
+    pub program: String,
+    pub source: String,
+    pub target: String,
+    /// Seit das Signal der Quelle in den Graphen gekommen ist
+    pub capture: (u32, u32),
+    /// Bis das Signal des Ziels den Graphen verlässt
+    pub playback: (u32, u32),
+    pub sample_rate: u32,
+}
+
+impl fmt::Display for RouteLatency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = |(min, max): (u32, u32)| {
+            let ms = |frames: u32| f64::from(frames) * 1000.0 / f64::from(self.sample_rate);
+            if min == max {
+                format!("{} ({:.1} ms)", min, ms(min))
+            } else {
+                format!("{}–{} ({:.1}–{:.1} ms)", min, max, ms(min), ms(max))
+            }
+        };
+        write!(
+            f,
+            "{}: {} -> {}: Quelle {}, Ziel {} Frames",
+            self.program,
+            self.source,
+            self.target,
+            range(self.capture),
+            range(self.playback)
+        )
+    }
+}
+
+struct Process {
+    output: Port<AudioOut>,
+    input: Port<AudioIn>,
+    chirp: Vec<f32>,
+    interval: usize,
+    /// Frames seit dem Start, Sende- und Empfangsseite zählen gleich
+    frame: usize,
+    ring: RingBufferWriter,
+    /// Vorab angelegt, damit im Audio-Thread nichts alloziert wird
+    scratch: Vec<u8>,
+    overflow: Arc<AtomicBool>,
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        for (index, sample) in self.output.as_mut_slice(ps).iter_mut().enumerate() {
+            let frame = self.frame + index;
+            let offset = frame % self.interval;
+            // Das erste Intervall bleibt still, bis die Verbindungen hergestellt sind
+            let pulse = frame / self.interval;
+            *sample = if (1..=PULSES).contains(&pulse) && offset < self.chirp.len() { self.chirp[offset] } else { 0.0 };
+        }
+        let input = self.input.as_slice(ps);
+        let bytes = input.len() * 4;
+        // Fehlende Samples würden die Zuordnung der Frames verschieben, die Messung ist dann ungültig
+        if bytes > self.scratch.len() || self.ring.space() < bytes {
+            self.overflow.store(true, Ordering::Relaxed);
+        } else {
+            for (index, sample) in input.iter().enumerate() {
+                self.scratch[index * 4..index * 4 + 4].copy_from_slice(&sample.to_ne_bytes());
+            }
+            self.ring.write_buffer(&self.scratch[..bytes]);
+        }
+        self.frame += input.len();
+        Control::Continue
+    }
+}
+
+/// Sendet mehrere Testsignale an den Eingang `send` und sucht sie im Signal von `receive`,
+/// ähnlich wie `jack_iodelay`. `receive` ist ein Ausgang oder ein Eingang, dessen Quellen
+/// mitgehört werden. `progress` wird etwa einmal je Sekunde mit der Zahl der noch
+/// ausstehenden Testsignale aufgerufen; liefert es einen Fehler, wird abgebrochen.
+pub fn measure(send: &str, receive: &str, progress: &dyn Fn(usize) -> Result<()>) -> Result<LatencyReport> {
+    let jack_error = |action: &str| {
+        let action = action.to_string();
+        move |source| Error::JackClient { action, source }
+    };
+    let (client, _) = Client::new(LATENCY_CLIENT, ClientOptions::NO_START_SERVER)
+        .map_err(jack_error("Latenzmessung anmelden"))?;
+    let rate = client.sample_rate();
+    let output = client.register_port("out", AudioOut).map_err(jack_error("Testsignal-Ausgang anlegen"))?;
+    let input = client.register_port("in", AudioIn).map_err(jack_error("Messeingang anlegen"))?;
+    let (mut reader, writer) = RingBuffer::new(rate * 4 * 2)
+        .map_err(jack_error("Messpuffer anlegen"))?
+        .into_reader_writer();
+    let overflow = Arc::new(AtomicBool::new(false));
+    let chirp = chirp(rate as f32);
+    let interval = (PULSE_INTERVAL_S * rate as f32) as usize;
+    let process = Process {
+        output,
+        input,
+        chirp: chirp.clone(),
+        interval,
+        frame: 0,
+        ring: writer,
+        // Reicht für die größte Puffergröße, die JACK zulässt
+        scratch: vec![0; 8192 * 4],
+        overflow: overflow.clone(),
+    };
+    let client = client.activate_async((), process).map_err(jack_error("Latenzmessung aktivieren"))?;
+    let own = client.as_client().name().to_string();
+    client
+        .as_client()
+        .connect_ports_by_name(&format!("{}:out", own), send)
+        .map_err(jack_error(&format!("Testsignal an {} senden", send)))?;
+    let mut errors = Vec::new();
+    if !meter::follow(client.as_client(), &format!("{}:in", own), receive, &mut errors) {
+        return Err(Error::PortNotFound { port: receive.to_string() });
+    }
+    Error::from_list(errors)?;
+    tracing::info!(send, receive, "Latenzmessung gestartet");
+
+    // Nach dem letzten Testsignal noch ein Intervall aufnehmen, damit es zurückkommen kann
+    let needed = (PULSES + 2) * interval;
+    let mut captured: Vec<f32> = Vec::with_capacity(needed);
+    let mut bytes = vec![0u8; 4096 * 4];
+    let mut reported = usize::MAX;
+    while captured.len() < needed {
+        let pending = (PULSES + 1).saturating_sub(captured.len() / interval).min(PULSES);
+        if pending != reported {
+            progress(pending)?;
+            reported = pending;
+        }
+        let available = reader.space().min(bytes.len()) / 4 * 4;
+        if available == 0 {
+            std::thread::sleep(Duration::from_millis(20));
+            continue;
+        }
+        let read = reader.read_buffer(&mut bytes[..available]);
+        captured.extend(bytes[..read].chunks_exact(4).map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])));
+    }
+    drop(client);
+    if overflow.load(Ordering::Relaxed) {
+        return Err(Error::LatencyOverflow);
+    }
+
+    let latencies = (1..=PULSES)
+        .map(|pulse| detect(&chirp, &captured[pulse * interval..(pulse + 1) * interval + chirp.len()]))
+        .collect();
+    let report = LatencyReport { send: send.to_string(), receive: receive.to_string(), sample_rate: rate as u32, latencies };
+    tracing::info!("Latenz {}", report);
+    Ok(report)
+}
+
+/// Linearer Sweep mit Hann-Fenster.
+fn chirp(rate: f32) -> Vec<f32> {
+    let length = (CHIRP_S * rate) as usize;
+    let sweep = (CHIRP_TO_HZ - CHIRP_FROM_HZ) / CHIRP_S;
+    (0..length)
+        .map(|n| {
+            let t = n as f32 / rate;
+            let window = 0.5 * (1.0 - (2.0 * PI * n as f32 / length as f32).cos());
+            (2.0 * PI * (CHIRP_FROM_HZ * t + 0.5 * sweep * t * t)).sin() * window * CHIRP_LEVEL
+        })
+        .collect()
+}
+
+/// Versatz in `signal`, an dem das Testsignal am deutlichsten vorkommt (normierte Kreuzkorrelation).
+fn detect(chirp: &[f32], signal: &[f32]) -> Option<u32> {
+    let reference: f32 = chirp.iter().map(|s| s * s).sum();
+    let mut energy: f32 = signal.iter().take(chirp.len()).map(|s| s * s).sum();
+    let mut best = (0.0, 0);
+    for lag in 0..=signal.len().saturating_sub(chirp.len()) {
+        if lag > 0 {
+            let leaving = signal[lag - 1];
+            let entering = signal[lag + chirp.len() - 1];
+            energy = (energy - leaving * leaving + entering * entering).max(0.0);
+        }
+        if energy <= f32::EPSILON {
+            continue;
+        }
+        let dot: f32 = chirp.iter().zip(&signal[lag..]).map(|(c, s)| c * s).sum();
+        // Auch ein verpoltes Signal zählt
+        let correlation = dot.abs() / (reference * energy).sqrt();
+        if correlation > best.0 {
+            best = (correlation, lag);
+        }
+    }
+    (best.0 >= DETECTION).then_some(best.1 as u32)
+}
+
+/// Fragt für jede konfigurierte Verbindung, deren Ports gerade vorhanden sind, die von JACK
+/// gemeldeten Latenzen ab.
+pub fn route_latencies(programs: &[ManagedAudioProgram]) -> Result<Vec<RouteLatency>> {
+    let (client, _) = Client::new(LATENCY_CLIENT, ClientOptions::NO_START_SERVER)
+        .map_err(|source| Error::JackClient { action: "Portlatenzen abfragen".to_string(), source })?;
+    let ports: Vec<JackPortInfo> = client
+        .ports(None, None, jack::PortFlags::empty())
+        .into_iter()
+        .map(|name| JackPortInfo { name, properties: Vec::new() })
+        .collect();
+    let names: Vec<String> = programs.iter().map(|p| p.jack_node_name.clone()).collect();
+    let mut routes = Vec::new();
+    for prog in programs {
+        for port in prog.config.jack_ports.iter() {
+            let Ok((source, target)) = resolve_connection(&ports, &names, prog.jack_node_name.clone(), port) else { continue };
+            let (Some(source_port), Some(target_port)) = (client.port_by_name(&source), client.port_by_name(&target)) else { continue };
+            routes.push(RouteLatency {
+                program: prog.config.program_name.clone(),
+                source,
+                target,
+                capture: source_port.get_latency_range(LatencyType::Capture),
+                playback: target_port.get_latency_range(LatencyType::Playback),
+                sample_rate: client.sample_rate() as u32,
+            });
+        }
+    }
+    Ok(routes)
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    filter::LevelFilter,
+    fmt::time::ChronoLocal,
+    layer::{Context, SubscriberExt},
+    util::SubscriberInitExt,
+};
+
+use crate::error::{Error, Result};
+use crate::paths;
+
+/// Umgebungsvariable für den Log-Filter, z.B. `info,jackstreamingmanager::midi_control=debug`.
+pub const LOG_ENV: &str = "JACKSTREAMINGMANAGER_LOG";
+
+const DEFAULT_FILTER: &str = "info";
+
+/// Eine Datei pro Tag, ältere werden nach zwei Wochen gelöscht.
+const MAX_LOG_FILES: usize = 14;
+
+/// Anzahl der Einträge, die der Log-Tab anzeigen kann.
+const MAX_ENTRIES: usize = 2000;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+static ENTRIES: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Ein Log-Eintrag der laufenden Sitzung für die Anzeige in der Oberfläche.
+#[derive(Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub level: Level,
+    /// Name des Programms, auf das sich der Eintrag bezieht (Feld `program`).
+    pub program: Option<String>,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// `max_level` ist die ausführlichste Stufe, die noch angezeigt wird. Ein leeres `program` passt auf alle Einträge.
+    pub fn matches(&self, max_level: Level, program: &str) -> bool {
+        self.level <= max_level && (program.is_empty() || self.program.as_deref() == Some(program))
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:<5} ", self.time, self.level)?;
+        if let Some(program) = &self.program {
+            write!(f, "[{}] ", program)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Richtet das Logging ein: Log-Datei in `paths::log_dir()`, Speicher für den Log-Tab
+/// und Warnungen auf stderr. `filter` hat Vorrang vor der Umgebungsvariable.
+/// Liefert Hinweise, wenn die Log-Datei nicht angelegt werden konnte.
+pub fn init(filter: Option<String>) -> Result<Vec<String>> {
+    let filter = filter
+        .or_else(|| std::env::var(LOG_ENV).ok().filter(|f| !f.is_empty()))
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    let env_filter = EnvFilter::try_new(&filter).map_err(|source| Error::LogFilter { filter, source })?;
+
+    let mut notes = Vec::new();
+    let dir = paths::log_dir();
+    let file_layer = match RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("manager")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+    {
+        Ok(appender) => Some(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_timer(ChronoLocal::new(TIME_FORMAT.to_string()))
+                .with_writer(appender),
+        ),
+        Err(source) => {
+            notes.push(Error::LogFile { path: dir, source }.report());
+            None
+        }
+    };
+    // Die Kommandozeile gibt ihre Meldungen selbst aus, auf stderr nur, was schiefgeht
+    let stderr_layer = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_timer(ChronoLocal::new(TIME_FORMAT.to_string()))
+        .with_filter(LevelFilter::WARN);
+
+    // Schlägt nur fehl, wenn bereits ein Subscriber eingerichtet ist; der bleibt dann aktiv
+    let _ = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(file_layer)
+        .with(stderr_layer)
+        .with(MemoryLayer)
+        .try_init();
+    Ok(notes)
+}
+
+/// Einträge der laufenden Sitzung, der neueste zuletzt.
+pub fn entries() -> Vec<LogEntry> {
+    ENTRIES.lock().map(|entries| entries.iter().cloned().collect()).unwrap_or_default()
+}
+
+/// Zählt bei jedem neuen Eintrag hoch, damit die Oberfläche nur bei Änderungen neu zeichnet.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Sammelt die Ereignisse für den Log-Tab.
+struct MemoryLayer;
+
+impl<S: Subscriber> Layer<S> for MemoryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = EntryVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        for field in visitor.fields {
+            message.push(' ');
+            message.push_str(&field);
+        }
+        let entry = LogEntry {
+            time: chrono::Local::now().format(TIME_FORMAT).to_string(),
+            level: *event.metadata().level(),
+            program: visitor.program,
+            message,
+        };
+        if let Ok(mut entries) = ENTRIES.lock() {
+            if entries.len() >= MAX_ENTRIES {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+            GENERATION.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Default)]
+struct EntryVisitor {
+    message: String,
+    program: Option<String>,
+    fields: Vec<String>,
+}
+
+impl EntryVisitor {
+    fn record(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = value,
+            "program" => self.program = Some(value),
+            name => self.fields.push(format!("{}={}", name, value)),
+        }
+    }
+}
+
+impl Visit for EntryVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
@@ -0,0 +1,540 @@
+use std::{
+    collections::VecDeque,
+    f64::consts::PI,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::mpsc,
+};
+
+use jack::{AudioIn, Client, ClientOptions, Control, Port, ProcessHandler, ProcessScope};
+
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{get_jack_name, JackPort, JackPortInfo, ManagedAudioProgram};
+use crate::{atomic_file, meter, paths};
+
+/// Name des JACK-Clients für die Lautheitsmessung.
+const LOUDNESS_CLIENT: &str = "jackstreamingmanager-loudness";
+
+/// Unterverzeichnis der Logs, in dem je Sendung eine CSV- und eine JSON-Datei liegen.
+pub const LOG_DIR: &str = "loudness";
+
+/// Länge eines Messblocks (Millisekunden). Momentary umfasst 4, Short-Term 30 Blöcke.
+const BLOCK_MS: usize = 100;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+/// Gates nach EBU R128 / ITU-R BS.1770 (LUFS bzw. LU).
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+const RANGE_GATE: f64 = -20.0;
+
+/// Erst nach so vielen Momentary-Werten über dem absoluten Gate (eine Minute) wird die
+/// integrierte Lautheit mit dem Zielbereich verglichen.
+const MIN_TARGET_BLOCKS: usize = 600;
+
+/// Zielbereich der integrierten Lautheit.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LoudnessConfig {
+    pub target_lufs: f64,
+    pub tolerance_lu: f64,
+}
+
+impl Default for LoudnessConfig {
+    /// EBU R128
+    fn default() -> Self {
+        Self { target_lufs: -23.0, tolerance_lu: 1.0 }
+    }
+}
+
+/// Aktuelle Werte eines Streams in LUFS bzw. LU, `None` solange nicht genug gemessen wurde.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Reading {
+    pub stream: String,
+    pub momentary: Option<f64>,
+    pub short_term: Option<f64>,
+    pub integrated: Option<f64>,
+    pub range: Option<f64>,
+    pub max_momentary: Option<f64>,
+    pub max_short_term: Option<f64>,
+    /// Integrierte Lautheit im Zielbereich, sobald genug gemessen wurde
+    pub in_target: Option<bool>,
+}
+
+/// Streams sind die verwalteten Programme mit Verbindungen, die `loudness` gesetzt haben.
+/// Gemessen wird das Ziel dieser Verbindungen, also das, was tatsächlich gesendet wird.
+/// Liefert Name und Anzahl der Kanäle je Stream.
+pub fn layout(programs: &[ManagedAudioProgram]) -> Vec<(String, usize)> {
+    programs
+        .iter()
+        .map(|prog| (prog.config.program_name.clone(), loudness_routes(prog).count()))
+        .filter(|(_, channels)| *channels > 0)
+        .collect()
+}
+
+fn loudness_routes(prog: &ManagedAudioProgram) -> impl Iterator<Item = &JackPort> {
+    prog.config.jack_ports.iter().filter(|port| port.loudness)
+}
+
+fn to_lufs(energy: f64) -> f64 {
+    if energy > 0.0 { -0.691 + 10.0 * energy.log10() } else { f64::NEG_INFINITY }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Biquad in Direktform I.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn run(&mut self, x0: f64) -> f64 {
+        let y0 = self.b[0] * x0 + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x0, self.x[0]];
+        self.y = [y0, self.y[0]];
+        y0
+    }
+}
+
+/// K-Bewertung nach ITU-R BS.1770: Kuhschwanz-Filter für den Kopfeinfluss, danach Hochpass.
+/// Die Koeffizienten werden für die tatsächliche Abtastrate berechnet.
+fn k_weighting(rate: f64) -> [Biquad; 2] {
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        x: [0.0; 2],
+        y: [0.0; 2],
+    };
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        x: [0.0; 2],
+        y: [0.0; 2],
+    };
+    [shelf, high_pass]
+}
+
+struct Input {
+    port: Port<AudioIn>,
+    stream: usize,
+    filters: [Biquad; 2],
+}
+
+/// Bildet im Audio-Thread je Stream die mittlere Energie der K-bewerteten Kanäle über einen
+/// Block und reicht sie ohne Sperre weiter.
+struct Process {
+    inputs: Vec<Input>,
+    energy: Vec<f64>,
+    frames: usize,
+    block_frames: usize,
+    blocks: mpsc::SyncSender<(usize, f64)>,
+}
+
+/// Summe der Quadrate der K-bewerteten Samples eines Kanals.
+fn weighted_energy(filters: &mut [Biquad; 2], samples: &[f32]) -> f64 {
+    let [shelf, high_pass] = filters;
+    samples
+        .iter()
+        .map(|&sample| {
+            let weighted = high_pass.run(shelf.run(f64::from(sample)));
+            weighted * weighted
+        })
+        .sum()
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        for input in self.inputs.iter_mut() {
+            self.energy[input.stream] += weighted_energy(&mut input.filters, input.port.as_slice(ps));
+        }
+        self.frames += ps.n_frames() as usize;
+        if self.frames >= self.block_frames {
+            for (stream, energy) in self.energy.iter_mut().enumerate() {
+                // Ist der Empfänger im Rückstand, geht ein Block verloren statt den Audio-Thread aufzuhalten
+                let _ = self.blocks.try_send((stream, *energy / self.frames as f64));
+                *energy = 0.0;
+            }
+            self.frames = 0;
+        }
+        Control::Continue
+    }
+}
+
+/// Messwerte eines Streams seit Beginn der Sendung.
+#[derive(Default)]
+struct Measurement {
+    /// Energie der letzten Blöcke, höchstens so viele wie für Short-Term nötig
+    blocks: VecDeque<f64>,
+    /// Momentary- und Short-Term-Energien über dem absoluten Gate, je Block einer
+    momentary: Vec<f64>,
+    short_term: Vec<f64>,
+    max_momentary: Option<f64>,
+    max_short_term: Option<f64>,
+}
+
+impl Measurement {
+    fn push(&mut self, energy: f64) {
+        self.blocks.push_back(energy);
+        if self.blocks.len() > SHORT_TERM_BLOCKS {
+            self.blocks.pop_front();
+        }
+        if let Some(momentary) = self.window(MOMENTARY_BLOCKS) {
+            let lufs = to_lufs(momentary);
+            self.max_momentary = Some(self.max_momentary.map_or(lufs, |max| max.max(lufs)));
+            if lufs > ABSOLUTE_GATE {
+                self.momentary.push(momentary);
+            }
+        }
+        if let Some(short_term) = self.window(SHORT_TERM_BLOCKS) {
+            let lufs = to_lufs(short_term);
+            self.max_short_term = Some(self.max_short_term.map_or(lufs, |max| max.max(lufs)));
+            if lufs > ABSOLUTE_GATE {
+                self.short_term.push(short_term);
+            }
+        }
+    }
+
+    /// Mittlere Energie der letzten `blocks` Blöcke.
+    fn window(&self, blocks: usize) -> Option<f64> {
+        if self.blocks.len() < blocks {
+            return None;
+        }
+        mean(self.blocks.iter().skip(self.blocks.len() - blocks).copied())
+    }
+
+    fn integrated(&self) -> Option<f64> {
+        let gate = to_lufs(mean(self.momentary.iter().copied())?) + RELATIVE_GATE;
+        mean(self.momentary.iter().copied().filter(|e| to_lufs(*e) > gate)).map(to_lufs)
+    }
+
+    /// Loudness Range: Abstand zwischen 10. und 95. Perzentil der Short-Term-Werte.
+    fn range(&self) -> Option<f64> {
+        let gate = to_lufs(mean(self.short_term.iter().copied())?) + RANGE_GATE;
+        let mut values: Vec<f64> = self.short_term.iter().map(|e| to_lufs(*e)).filter(|l| *l > gate).collect();
+        if values.len() < 2 {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+        Some(percentile(0.95) - percentile(0.10))
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ShowSummary<'a> {
+    show: &'a str,
+    started: String,
+    updated: String,
+    target_lufs: f64,
+    tolerance_lu: f64,
+    streams: &'a [Reading],
+}
+
+/// Log einer Sendung: eine Zeile je Stream und Aufruf in der CSV-Datei, die Zusammenfassung
+/// wird in der JSON-Datei laufend überschrieben.
+struct ShowLog {
+    show: String,
+    started: chrono::DateTime<chrono::Local>,
+    csv: File,
+    json: PathBuf,
+}
+
+/// Misst die Lautheit der Streams nach EBU R128 über einen eigenen JACK-Client.
+/// Die Messung endet, wenn die Instanz verworfen wird.
+pub struct LoudnessMonitor {
+    client: jack::AsyncClient<(), Process>,
+    layout: Vec<(String, usize)>,
+    blocks: mpsc::Receiver<(usize, f64)>,
+    measurements: Vec<Measurement>,
+    config: LoudnessConfig,
+    log: Option<ShowLog>,
+}
+
+impl LoudnessMonitor {
+    pub fn start(programs: &[ManagedAudioProgram], config: &LoudnessConfig) -> Result<Self> {
+        let jack_error = |action: &str| {
+            let action = action.to_string();
+            move |source| Error::JackClient { action, source }
+        };
+        let (client, _) = Client::new(LOUDNESS_CLIENT, ClientOptions::NO_START_SERVER)
+            .map_err(jack_error("Lautheitsmessung anmelden"))?;
+        let layout = layout(programs);
+        let rate = client.sample_rate();
+        let mut inputs = Vec::new();
+        for (stream, (_, channels)) in layout.iter().enumerate() {
+            for channel in 0..*channels {
+                let port = client
+                    .register_port(&input_name(stream, channel), AudioIn)
+                    .map_err(jack_error("Messeingang anlegen"))?;
+                inputs.push(Input { port, stream, filters: k_weighting(rate as f64) });
+            }
+        }
+        let (sender, blocks) = mpsc::sync_channel(1024);
+        let process = Process {
+            inputs,
+            energy: vec![0.0; layout.len()],
+            frames: 0,
+            block_frames: rate * BLOCK_MS / 1000,
+            blocks: sender,
+        };
+        let client = client.activate_async((), process).map_err(jack_error("Lautheitsmessung aktivieren"))?;
+        tracing::info!(streams = layout.len(), "Lautheitsmessung gestartet");
+        Ok(Self {
+            client,
+            measurements: layout.iter().map(|_| Measurement::default()).collect(),
+            layout,
+            blocks,
+            config: config.clone(),
+            log: None,
+        })
+    }
+
+    /// Startet die Messung mit den aktuellen Streams neu, etwa wenn ein Stream hinzukam.
+    /// Messwerte unveränderter Streams und das Protokoll der laufenden Sendung bleiben erhalten.
+    pub fn restart(mut self, programs: &[ManagedAudioProgram], config: &LoudnessConfig) -> Result<Self> {
+        self.poll();
+        let Self { client, layout, mut measurements, log, .. } = self;
+        // Erst abmelden, sonst bekäme der neue Client einen anderen Namen
+        drop(client);
+        let mut monitor = Self::start(programs, config)?;
+        for (index, stream) in monitor.layout.iter().enumerate() {
+            if let Some(previous) = layout.iter().position(|s| s == stream) {
+                monitor.measurements[index] = std::mem::take(&mut measurements[previous]);
+            }
+        }
+        monitor.log = log;
+        Ok(monitor)
+    }
+
+    /// Name und Kanalzahl der gemessenen Streams. Weicht `layout(programs)` davon ab,
+    /// muss die Messung neu gestartet werden.
+    pub fn layout(&self) -> &[(String, usize)] {
+        &self.layout
+    }
+
+    /// Legt jeden Messeingang auf das aktuelle Ziel seiner Verbindung.
+    pub fn sync(&self, programs: &[ManagedAudioProgram]) -> Vec<Error> {
+        let client = self.client.as_client();
+        let ports: Vec<JackPortInfo> = client
+            .ports(None, None, jack::PortFlags::empty())
+            .into_iter()
+            .map(|name| JackPortInfo { name, properties: Vec::new() })
+            .collect();
+        let names: Vec<String> = programs.iter().map(|p| p.jack_node_name.clone()).collect();
+        let mut errors = Vec::new();
+        let streams = programs.iter().filter(|prog| loudness_routes(prog).next().is_some());
+        for (stream, prog) in streams.enumerate() {
+            for (channel, route) in loudness_routes(prog).enumerate() {
+                // Läuft das Ziel nicht, bleibt der Eingang unverbunden
+                let target = get_jack_name(&ports, &names, prog.jack_node_name.clone(), route).unwrap_or_default();
+                let own = format!("{}:{}", client.name(), input_name(stream, channel));
+                meter::follow(client, &own, &target, &mut errors);
+            }
+        }
+        errors
+    }
+
+    /// Übernimmt die Blöcke, die der Audio-Thread seit dem letzten Aufruf gemessen hat.
+    pub fn poll(&mut self) {
+        while let Ok((stream, energy)) = self.blocks.try_recv() {
+            if let Some(measurement) = self.measurements.get_mut(stream) {
+                measurement.push(energy);
+            }
+        }
+    }
+
+    pub fn readings(&self) -> Vec<Reading> {
+        self.layout
+            .iter()
+            .zip(self.measurements.iter())
+            .map(|((stream, _), measurement)| {
+                let integrated = measurement.integrated();
+                Reading {
+                    stream: stream.clone(),
+                    momentary: measurement.window(MOMENTARY_BLOCKS).map(to_lufs),
+                    short_term: measurement.window(SHORT_TERM_BLOCKS).map(to_lufs),
+                    integrated,
+                    range: measurement.range(),
+                    max_momentary: measurement.max_momentary,
+                    max_short_term: measurement.max_short_term,
+                    in_target: integrated
+                        .filter(|_| measurement.momentary.len() >= MIN_TARGET_BLOCKS)
+                        .map(|lufs| (lufs - self.config.target_lufs).abs() <= self.config.tolerance_lu),
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_config(&mut self, config: &LoudnessConfig) {
+        self.config = config.clone();
+    }
+
+    /// Sendung, für die gerade gemessen und protokolliert wird.
+    pub fn show(&self) -> Option<&str> {
+        self.log.as_ref().map(|log| log.show.as_str())
+    }
+
+    /// Beginnt eine neue Sendung: die integrierte Messung startet von vorn, und es werden neue
+    /// Log-Dateien angelegt. Die Zusammenfassung der vorigen Sendung bleibt stehen.
+    pub fn start_show(&mut self, show: &str) -> Result<()> {
+        self.log = None;
+        for measurement in self.measurements.iter_mut() {
+            *measurement = Measurement::default();
+        }
+        let started = chrono::Local::now();
+        let dir = paths::log_dir().join(LOG_DIR);
+        let safe: String = show.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        let base = dir.join(format!("{}_{}", started.format("%Y-%m-%d_%H-%M-%S"), safe));
+        let csv_path = base.with_extension("csv");
+        let mut csv = fs::create_dir_all(&dir)
+            .and_then(|()| OpenOptions::new().create(true).append(true).open(&csv_path))
+            .map_err(|source| Error::ConfigIo { path: csv_path.clone(), source })?;
+        writeln!(csv, "zeit,stream,momentary_lufs,short_term_lufs,integrated_lufs,range_lu")
+            .map_err(|source| Error::ConfigIo { path: csv_path, source })?;
+        tracing::info!(show, "Lautheitsprotokoll begonnen");
+        self.log = Some(ShowLog { show: show.to_string(), started, csv, json: base.with_extension("json") });
+        Ok(())
+    }
+
+    /// Schreibt die aktuellen Werte ins Protokoll der laufenden Sendung.
+    pub fn log(&mut self) -> Result<()> {
+        let readings = self.readings();
+        let Some(log) = self.log.as_mut() else { return Ok(()) };
+        let now = chrono::Local::now();
+        let value = |v: Option<f64>| v.filter(|v| v.is_finite()).map(|v| format!("{:.1}", v)).unwrap_or_default();
+        let mut lines = String::new();
+        for reading in readings.iter() {
+            lines.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                now.format("%Y-%m-%d %H:%M:%S"),
+                reading.stream.replace(',', " "),
+                value(reading.momentary),
+                value(reading.short_term),
+                value(reading.integrated),
+                value(reading.range)
+            ));
+        }
+        let csv_path = log.json.with_extension("csv");
+        log.csv.write_all(lines.as_bytes()).map_err(|source| Error::ConfigIo { path: csv_path, source })?;
+
+        let summary = ShowSummary {
+            show: &log.show,
+            started: log.started.format("%Y-%m-%d %H:%M:%S").to_string(),
+            updated: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            target_lufs: self.config.target_lufs,
+            tolerance_lu: self.config.tolerance_lu,
+            streams: &readings,
+        };
+        let data = serde_json::to_vec_pretty(&summary).map_err(|source| Error::Serialize { path: log.json.clone(), source })?;
+        atomic_file::write_atomic(&log.json, &data).map_err(|source| Error::ConfigIo { path: log.json.clone(), source })
+    }
+}
+
+fn input_name(stream: usize, channel: usize) -> String {
+    format!("stream{}_{}", stream + 1, channel + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: usize = 48000;
+
+    /// Stereo-Messung wie im Audio-Thread, gespeist mit erzeugten Signalen.
+    struct Signal {
+        measurement: Measurement,
+        filters: [[Biquad; 2]; 2],
+        position: usize,
+    }
+
+    impl Signal {
+        fn new() -> Self {
+            Self { measurement: Measurement::default(), filters: [k_weighting(RATE as f64); 2], position: 0 }
+        }
+
+        /// Sinus mit 1 kHz und `db` dBFS auf beiden Kanälen.
+        fn sine(&mut self, db: f64, seconds: usize) -> &mut Self {
+            let amplitude = 10f64.powf(db / 20.0);
+            let block = RATE * BLOCK_MS / 1000;
+            for _ in 0..seconds * 1000 / BLOCK_MS {
+                let samples: Vec<f32> = (self.position..self.position + block)
+                    .map(|i| (amplitude * (2.0 * PI * 1000.0 * (i % RATE) as f64 / RATE as f64).sin()) as f32)
+                    .collect();
+                self.position += block;
+                let energy: f64 = self.filters.iter_mut().map(|filters| weighted_energy(filters, &samples)).sum();
+                self.measurement.push(energy / block as f64);
+            }
+            self
+        }
+    }
+
+    fn assert_near(value: Option<f64>, expected: f64, tolerance: f64) {
+        let value = value.expect("kein Messwert");
+        assert!((value - expected).abs() <= tolerance, "{value:.2} statt {expected:.1} ± {tolerance}");
+    }
+
+    // Testfälle aus EBU Tech 3341 (Lautheit) und Tech 3342 (Loudness Range)
+
+    #[test]
+    fn sine_at_minus_23_dbfs() {
+        let mut signal = Signal::new();
+        signal.sine(-23.0, 20);
+        let measurement = &signal.measurement;
+        assert_near(measurement.window(MOMENTARY_BLOCKS).map(to_lufs), -23.0, 0.1);
+        assert_near(measurement.window(SHORT_TERM_BLOCKS).map(to_lufs), -23.0, 0.1);
+        assert_near(measurement.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn sine_at_minus_33_dbfs() {
+        let mut signal = Signal::new();
+        signal.sine(-33.0, 20);
+        assert_near(signal.measurement.integrated(), -33.0, 0.1);
+    }
+
+    #[test]
+    fn relative_gate() {
+        let mut signal = Signal::new();
+        signal.sine(-36.0, 10).sine(-23.0, 60).sine(-36.0, 10);
+        assert_near(signal.measurement.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn absolute_and_relative_gate() {
+        let mut signal = Signal::new();
+        signal.sine(-72.0, 10).sine(-36.0, 10).sine(-23.0, 60).sine(-36.0, 10).sine(-72.0, 10);
+        assert_near(signal.measurement.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn loudness_range() {
+        let mut signal = Signal::new();
+        signal.sine(-20.0, 20).sine(-30.0, 20);
+        assert_near(signal.measurement.range(), 10.0, 1.0);
+        let mut signal = Signal::new();
+        signal.sine(-20.0, 20).sine(-15.0, 20);
+        assert_near(signal.measurement.range(), 5.0, 1.0);
+    }
+
+    #[test]
+    fn silence_has_no_integrated_value() {
+        let mut signal = Signal::new();
+        signal.sine(-80.0, 5);
+        assert_eq!(signal.measurement.integrated(), None);
+    }
+}
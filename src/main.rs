@@ -1,33 +1,79 @@
 slint::include_modules!();
 
-use std::{path::PathBuf, process::Command, sync::{Arc, Mutex, MutexGuard}, vec};
-
-use slint::{StandardListViewItem, VecModel, ModelRc, SharedString};
-
+use std::{path::PathBuf, sync::{Arc, Mutex, MutexGuard, atomic::{AtomicBool, Ordering}}, time::{Duration, Instant}, vec};
+
+use slint::{Model, StandardListViewItem, VecModel, ModelRc, SharedString};
+
+mod atomic_file;
+mod audit;
+mod cli;
+mod config_schema;
+mod config_watcher;
+mod error;
+mod generator;
+mod health;
+mod latency;
+mod logging;
+mod loudness;
 mod managed_audio_program;
-
+mod matrix;
+mod meter;
+mod midi_control;
+mod patchbay;
+mod paths;
+mod project;
+mod recorder;
+mod silence;
+mod snapshot;
+mod status;
+mod worker;
+
+use audit::{AuditLog, Trigger};
+use config_watcher::ConfigWatcher;
+use error::{Error, ResultExt};
+use generator::{Generator, Tone, Waveform};
+use health::{HealthMonitor, XrunHistory};
+use loudness::{LoudnessMonitor, Reading};
 use managed_audio_program::ManagedAudioProgram;
-
-use crate::managed_audio_program::{read_jack_connections, read_jack_ports, AudioProgramConfig, JackPort};
+use meter::{Level, Meter, MeterConfig};
+use midi_control::{MidiAction, MidiConfig, MidiController, MidiLearnState};
+use project::{ImportMode, Project};
+use recorder::{AudioFormat, FileLayout, Recorder, RecorderConfig};
+use silence::{SilenceDetector, SilenceEvent, SilenceState, SilenceWatch};
+use snapshot::Snapshot;
+use status::{ProgramState, ProgramStatus, RouteState, StatusMonitor};
+use worker::{Progress, Worker, WorkerEvent};
+
+use crate::managed_audio_program::{get_jack_name, owner_of, read_jack_connections, read_jack_ports, resolve_connection, run_jack_tool, AudioProgramConfig, JackPort, Switching};
 use std::collections::HashSet;
 
 
 fn main() {
-    // Ports beim Start einlesen
-    let ports = read_jack_ports();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(exit_code) = cli::run(&args) {
+        std::process::exit(exit_code);
+    }
+
+    // Ports beim Start einlesen. Ohne JACK startet die Oberfläche trotzdem und zeigt den Grund an.
+    let (ports, jack_unavailable) = match read_jack_ports() {
+        Ok(ports) => (ports, String::new()),
+        Err(e) => (Vec::new(), jack_unavailable_message(&e)),
+    };
 
     let mut start_errors: Vec<String> = Vec::new();
 
     // Programme verwalten
     // Hier alle vorhandenen Konfigurationen laden
     let result = ManagedAudioProgram::load_all();
-    start_errors.extend(result.1);
+    start_errors.extend(result.1.iter().map(log_error));
     let audio_programs: Arc<Mutex<Vec<ManagedAudioProgram>>> = Arc::new(Mutex::new(result.0));
 
     // Beispiel: Programm hinzufügen und starten
     // Nur Beispiel hinzufügen, wenn keine Programme geladen wurden
     if audio_programs.lock().unwrap().is_empty() {
         let config = AudioProgramConfig {
+            version: config_schema::CURRENT_VERSION,
+            id: config_schema::new_program_id(),
             program_name: "baresip stream".to_string(),
             command_name: "baresip".to_string(),
             start_params: vec![],
@@ -37,15 +83,14 @@ fn main() {
                     source_name: "system:capture_1".to_string(),
                     target_search_name: "baresip-*".to_string(),
                     target_name: "baresip:input".to_string(),
+                    optional: false,
+                    switching: Switching::default(),
+                    overlap_ms: 0,
+                    loudness: false,
                 }
             ],
         };
-        let prog = ManagedAudioProgram {
-            config,
-            process: None,
-            pid_file: PathBuf::new(),
-            jack_node_name: "".to_string(),
-        };
+        let mut prog = ManagedAudioProgram::from_config(config);
         let _ = prog.save_config();
 
         audio_programs.lock().unwrap().push(prog);
@@ -80,7 +125,14 @@ fn main() {
         })
         .unwrap_or_default();
 
-    let ui = MainWindow::new().unwrap();
+    let ui = match MainWindow::new() {
+        Ok(ui) => ui,
+        Err(e) => {
+            eprintln!("Fehler beim Öffnen des Fensters: {}", e);
+            std::process::exit(1);
+        }
+    };
+    ui.set_jack_unavailable(jack_unavailable.into());
 
     ui.set_use_cases(
         ModelRc::new(VecModel::from(
@@ -168,66 +220,198 @@ fn main() {
         .unwrap_or_default()
     );
 
+    // MIDI-Steuerung
+    let midi_config = Arc::new(Mutex::new(MidiConfig::load().unwrap_or_else(|e| {
+        start_errors.push(log_error(&e));
+        MidiConfig::default()
+    })));
+    start_errors.extend(check_setup(&audio_programs, &midi_config));
+    let midi_learn: MidiLearnState = Arc::new(Mutex::new(None));
+    let midi_controller: Arc<Mutex<Option<MidiController>>> = Arc::new(Mutex::new(None));
+    {
+        let config = midi_config.lock().unwrap();
+        ui.set_midi_input_port(config.input_port.clone().into());
+        ui.set_midi_output_port(config.output_port.clone().into());
+        set_midi_mappings(&ui, &config, &audio_programs.lock().unwrap());
+    }
+
+    // Verlauf der Verbindungsänderungen
+    let audit_log = Arc::new(Mutex::new(AuditLog::load()));
+    set_audit_entries(&ui, &audit_log.lock().unwrap());
+    start_errors.extend(connect_midi(&midi_config, &midi_learn, &midi_controller, &audio_programs, &audit_log, ui.as_weak()));
+
+    set_projects(&ui);
 
+    // Log-Tab aktualisieren, sobald neue Einträge vorliegen
+    ui.set_log_dir(paths::log_dir().display().to_string().into());
+    set_log_entries(&ui);
+    let log_timer = slint::Timer::default();
     {
-        let audio_programs = audio_programs.clone();
         let ui_handle = ui.as_weak();
-        ui.on_start_use_case(move |use_case| {
-            let mut all_errors = Vec::new();
-            let mut programs = audio_programs.lock().unwrap();
-            // Collect indices to connect after mutable borrow ends
-            let mut to_connect = Vec::new();
-            for (app_index, prog) in programs.iter_mut().enumerate() {
-                let result = prog.start();
-                if let Err(e) = result {
-                    all_errors.push(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
-                }
+        let mut shown = logging::generation();
+        log_timer.start(slint::TimerMode::Repeated, Duration::from_millis(500), move || {
+            let generation = logging::generation();
+            if generation != shown
+                && let Some(ui) = ui_handle.upgrade()
+            {
+                shown = generation;
+                set_log_entries(&ui);
+            }
+        });
+    }
+    {
+        let ui_handle = ui.as_weak();
+        ui.on_log_filter_changed(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                set_log_entries(&ui);
+            }
+        });
+    }
 
-                for (jack_index, port) in prog.config.jack_ports.iter().enumerate() {
-                    if port.filter == use_case.to_string() {
-                        to_connect.push((app_index as i32, jack_index as i32));
+    // Prozesse starten und JACK-Verbindungen ändern, ohne die Oberfläche zu blockieren
+    let worker = {
+        let ui_handle = ui.as_weak();
+        Worker::start(move |event| {
+            let ui_handle = ui_handle.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                let Some(ui) = ui_handle.upgrade() else { return };
+                match event {
+                    WorkerEvent::Busy(label) => {
+                        ui.set_busy(true);
+                        ui.set_busy_text(label.into());
                     }
+                    WorkerEvent::Progress(message) => ui.set_busy_text(message.into()),
+                    WorkerEvent::Idle => ui.set_busy(false),
                 }
-            }
-            // Drop the mutable borrow before calling connect_jack_ports
-            drop(programs);
-            let mut programs = audio_programs.lock().unwrap();
-            for (app_index, jack_index) in &to_connect {
-                if let Err(errors) = connect_jack_ports(&mut programs, *app_index, *jack_index) {
-                    all_errors.extend(errors);
+            });
+        })
+    };
+    {
+        let worker = worker.clone();
+        ui.on_cancel_job(move || worker.cancel());
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let midi_config = midi_config.clone();
+        let midi_controller = midi_controller.clone();
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_start_use_case(move |use_case| {
+            let audio_programs = audio_programs.clone();
+            let midi_config = midi_config.clone();
+            let midi_controller = midi_controller.clone();
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            let use_case = use_case.to_string();
+            worker.run(format!("Use-Case '{}' starten", use_case), move |progress| {
+                tracing::info!(use_case = %use_case, "Use-Case starten");
+                let mut record = audit::Record::begin(Trigger::Scenario(use_case.clone()));
+                let mut all_errors = Vec::new();
+                // Die Starts laufen ohne Sperre, damit die Oberfläche währenddessen an die Programme kommt
+                let count = audio_programs.lock().unwrap().len();
+                for index in 0..count {
+                    if progress.is_cancelled() {
+                        break;
+                    }
+                    let Some(name) = audio_programs.lock().unwrap().get(index).map(|p| p.config.program_name.clone()) else { break };
+                    progress.report(format!("Starte {}", name));
+                    match launch_program(&audio_programs, index) {
+                        // Beim Wechsel des Use-Cases laufen die meisten Programme schon
+                        Ok(()) | Err(Error::AlreadyRunning { .. }) => {}
+                        Err(e) => all_errors.push(e.context(format!("Fehler beim Starten des Programms {}", name))),
+                    }
                 }
-            }
-            if let Err(errors) = disconnect_unwanted_jack_ports(programs, &use_case) {
-                all_errors.extend(errors);
-            }
-            if let Some(ui) = ui_handle.upgrade() {
-                if !all_errors.is_empty() {
-                    ui.set_output(all_errors.join("\n").into());
+                let result = progress
+                    .check()
+                    .context(format!("Use-Case '{}' nicht angewendet, es wurde nichts verändert", use_case))
+                    .map_err(UseCaseError::from)
+                    .and_then(|()| {
+                        let switchover = apply_use_case(&mut audio_programs.lock().unwrap(), &use_case, &mut record, progress)?;
+                        // Die Überlappung ohne Sperre abwarten, sonst steht die Oberfläche bis zu MAX_OVERLAP_MS
+                        Ok(switchover.finish(&mut record)?)
+                    });
+                // Das Zurücksetzen eines gescheiterten Use-Cases ist ein eigener Eintrag, damit er nicht als aktiv gilt
+                let rollback = match result {
+                    Ok(()) => None,
+                    Err(UseCaseError::NotApplied(e)) => {
+                        all_errors.push(e);
+                        None
+                    }
+                    Err(UseCaseError::Failed { error, before }) => {
+                        let mut errors = vec![error];
+                        let mut rollback = audit::Record::begin(Trigger::Rollback);
+                        if let Err(e) = audit::restore_snapshot(&mut rollback, &before) {
+                            errors.push(e.context("Fehler beim Wiederherstellen des vorherigen Zustands"));
+                        }
+                        tracing::warn!(use_case = %use_case, "Use-Case fehlgeschlagen, vorheriger Zustand wiederhergestellt");
+                        if let Err(e) = Error::from_list(errors) {
+                            all_errors.push(e.context(format!("Use-Case '{}' fehlgeschlagen, vorheriger Zustand wiederhergestellt", use_case)));
+                        }
+                        Some(rollback)
+                    }
+                };
+                let committed = match rollback {
+                    Some(rollback) => audit_log.lock().unwrap().commit_failed(record, rollback),
+                    None => audit_log.lock().unwrap().commit(record),
+                };
+                if let Err(e) = committed {
+                    all_errors.push(e);
                 }
-            }
+                let output = Error::from_list(all_errors).err().map(|e| log_error(&e));
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    set_audit_entries(&ui, &audit_log.lock().unwrap());
+                    let mut output = output;
+                    // Nach einem gescheiterten Use-Case leuchtet der weiter, der noch gilt
+                    let active = audit_log.lock().unwrap().active_scenario().unwrap_or_default().to_string();
+                    if let Some(controller) = midi_controller.lock().unwrap().as_mut()
+                        && let Err(e) = controller.show_active_use_case(&midi_config.lock().unwrap(), &active)
+                    {
+                        output = Some(log_error(&e));
+                    }
+                    if let Some(output) = output {
+                        ui.set_output(output.into());
+                    }
+                });
+            });
         });
     }
 
     {
         let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
         let ui_handle = ui.as_weak();
         ui.on_remove_unwanted_connections(move || {
-            let mut all_errors = Vec::new();
-            if let Ok(programs) = audio_programs.lock() {
-                if let Err(errors) = disconnect_unwanted_jack_ports(programs, &"") {
-                    all_errors.extend(errors);
+            let audio_programs = audio_programs.clone();
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run("Unerwünschte Verbindungen entfernen", move |_| {
+                let mut output = String::new();
+                let mut record = audit::Record::begin(Trigger::Manual);
+                if let Ok(programs) = audio_programs.lock()
+                    && let Err(e) = disconnect_unwanted_jack_ports(&programs, "", &mut record)
+                {
+                    output = log_error(&e);
                 }
-            }
-            if let Some(ui) = ui_handle.upgrade() {
-                ui.set_output(all_errors.join("\n").into());
-                
-                let filters = get_filters(audio_programs.lock().unwrap());
-                ui.set_use_cases(
-                    ModelRc::new(VecModel::from(
-                        filters.iter().map(|s| SharedString::from(s.clone())).collect::<Vec<SharedString>>()
-                    ))
-                );
-            }
+                if let Err(e) = audit_log.lock().unwrap().commit(record) {
+                    output = log_error(&e);
+                }
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    ui.set_output(output.into());
+                    set_audit_entries(&ui, &audit_log.lock().unwrap());
+
+                    let filters = get_filters(audio_programs.lock().unwrap());
+                    ui.set_use_cases(
+                        ModelRc::new(VecModel::from(
+                            filters.iter().map(|s| SharedString::from(s.clone())).collect::<Vec<SharedString>>()
+                        ))
+                    );
+                });
+            });
         });
     }
 
@@ -238,17 +422,15 @@ fn main() {
             let mut programs = audio_programs.lock().unwrap();
             let new_name = format!("Neues Programm {}", programs.len() + 1);
             let config = AudioProgramConfig {
+                version: config_schema::CURRENT_VERSION,
+                id: config_schema::new_program_id(),
                 program_name: new_name.clone(),
                 command_name: "".to_string(),
                 start_params: vec![],
                 jack_ports: vec![],
             };
-            let prog = ManagedAudioProgram {
-                config,
-                process: None,
-                pid_file: PathBuf::new(),
-                jack_node_name: "".to_string(),
-            };
+            let mut prog = ManagedAudioProgram::from_config(config);
+            prog.dirty = true;
             programs.push(prog);
 
             // Model für Slint aktualisieren
@@ -269,7 +451,12 @@ fn main() {
             let idx = ui_handle.upgrade().map(|ui| ui.get_program_selected()).unwrap_or(0) as usize;
             let mut programs = audio_programs.lock().unwrap();
             if idx < programs.len() {
-                programs[idx].delete_config();
+                if let Err(e) = programs[idx].delete_config() {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        ui.set_output(e.context(format!("Fehler beim Löschen von {}", programs[idx].config.program_name)).report().into());
+                    }
+                    return;
+                }
                 // Entferne das Programm
                 programs.remove(idx);
                 // Aktualisiere das Model für Slint
@@ -323,9 +510,22 @@ fn main() {
         ui.on_program_name_changed(move || {
             let idx = ui_handle.upgrade().map(|ui| ui.get_program_selected()).unwrap_or(0) as usize;
             let mut programs = audio_programs.lock().unwrap();
-            if let Some(prog) = programs.get_mut(idx) {
-                if let Some(ui) = ui_handle.upgrade() {
-                    prog.config.program_name = ui.get_edit_program_name().to_string();
+            if let Some(ui) = ui_handle.upgrade() {
+                // Umbenennen ändert nur den Anzeigenamen, das Verzeichnis ist nach der ID benannt
+                let name = ui.get_edit_program_name().to_string();
+                if let Some(message) = config_schema::check_program_name(&name) {
+                    ui.set_output(message.into());
+                    return;
+                }
+                if programs.iter().enumerate().any(|(i, p)| i != idx && p.config.program_name == name) {
+                    ui.set_output(format!("Programmname '{}' ist bereits vergeben", name).into());
+                    return;
+                }
+                if let Some(prog) = programs.get_mut(idx) {
+                    prog.config.program_name = name.clone();
+                    prog.dirty = true;
+                    ui.get_autio_programs().set_row_data(idx, StandardListViewItem::from(SharedString::from(name)));
+                    ui.set_output("".into());
                 }
             }
         });
@@ -342,6 +542,7 @@ fn main() {
             if let Some(prog) = programs.get_mut(idx) {
                 if let Some(ui) = ui_handle.upgrade() {
                     prog.config.command_name = ui.get_edit_command_name().to_string();
+                    prog.dirty = true;
                 }
             }
         });
@@ -358,6 +559,7 @@ fn main() {
             if let Some(prog) = programs.get_mut(idx) {
                 if let Some(ui) = ui_handle.upgrade() {
                     prog.config.start_params = ui.get_edit_start_params().split_whitespace().map(|s| s.to_string()).collect();
+                    prog.dirty = true;
                 }
             }
         });
@@ -365,20 +567,20 @@ fn main() {
 
     {
         let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
         let ui_handle = ui.as_weak();
 
         // Callback: Programm starten
         ui.on_start_app(move || {
             let idx = ui_handle.upgrade().map(|ui| ui.get_program_selected()).unwrap_or(0) as usize;
-            let mut programs = audio_programs.lock().unwrap();
-            if let Some(prog) = programs.get_mut(idx) {
-                let result = prog.start();
-                if let Some(ui) = ui_handle.upgrade() {
-                    if let Err(e) = result {
-                        ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
-                    }
-                }
-            }
+            let audio_programs = audio_programs.clone();
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run("Programm starten", move |_| {
+                let output = start_program(&audio_programs, &audit_log, idx).err().map(|e| e.report());
+                report_job(ui_handle, audit_log, output);
+            });
         });
     }
 
@@ -396,6 +598,7 @@ fn main() {
                     let selected_index = ui.get_Jack_connection_selected();
                     if let Some(port) = prog.config.jack_ports.get_mut(selected_index as usize) {
                         port.filter = filter.clone();
+                        prog.dirty = true;
 
                         let filters = get_filters(programs);
                         let unique_filters: Vec<slint::SharedString> = {
@@ -432,7 +635,7 @@ fn main() {
                 let result = prog.save_config();
                 if let Some(ui) = ui_handle.upgrade() {
                     if let Err(e) = result {
-                        ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
+                        ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
                     }
                 }
             }
@@ -449,6 +652,35 @@ fn main() {
         });
     }
 
+    {
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Vorherige Version der Konfiguration wiederherstellen
+        ui.on_restore_config(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let idx = ui.get_program_selected();
+            let result = match audio_programs.lock().unwrap().get_mut(idx as usize) {
+                Some(prog) => prog.restore_previous_config().map(|_| prog.config.program_name.clone()),
+                None => return,
+            };
+            match result {
+                Ok(name) => {
+                    ui.get_autio_programs().set_row_data(idx as usize, StandardListViewItem::from(SharedString::from(name.clone())));
+                    ui.invoke_program_selectiion_changed(idx);
+                    let filters = get_filters(audio_programs.lock().unwrap());
+                    ui.set_use_cases(
+                        ModelRc::new(VecModel::from(
+                            filters.iter().map(|s| SharedString::from(s.clone())).collect::<Vec<SharedString>>()
+                        ))
+                    );
+                    ui.set_output(format!("Vorherige Version von {} wiederhergestellt", name).into());
+                }
+                Err(e) => ui.set_output(log_error(&e).into()),
+            }
+        });
+    }
+
     {
         let audio_programs = audio_programs.clone();
         let ui_handle = ui.as_weak();
@@ -466,11 +698,15 @@ fn main() {
                     source_name,
                     target_search_name: target_name.clone(),
                     target_name,
+                    optional: false,
+                    switching: Switching::default(),
+                    overlap_ms: 0,
+                    loudness: false,
                 });
                 let result = prog.save_config();
                 if let Some(ui) = ui_handle.upgrade() {
                     if let Err(e) = result {
-                        ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
+                        ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
                     }
                 }
                 // Aktualisiere die Verbindungen im UI
@@ -515,7 +751,7 @@ fn main() {
                         port.source_name = source_name;
                         let result = prog.save_config();
                         if let Err(e) = result {
-                            ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
+                            ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
                         }
 
                         // Aktualisiere die Verbindungen im UI
@@ -544,7 +780,7 @@ fn main() {
                         port.target_name = target_name;
                         let result = prog.save_config();
                         if let Err(e) = result {
-                            ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
+                            ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
                         }
 
                         // Aktualisiere die Verbindungen im UI
@@ -558,6 +794,70 @@ fn main() {
         });
     }
 
+    {
+        // Callback: Verbindung als optional markiert
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_jack_optional_changed(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let idx = ui.get_program_selected() as usize;
+            let mut programs = audio_programs.lock().unwrap();
+            if let Some(prog) = programs.get_mut(idx)
+                && let Some(port) = prog.config.jack_ports.get_mut(ui.get_Jack_connection_selected() as usize)
+            {
+                port.optional = ui.get_jack_optional();
+                if let Err(e) = prog.save_config() {
+                    ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
+                }
+            }
+        });
+    }
+
+    {
+        // Callback: Lautheitsmessung für das Ziel der Verbindung an- oder abgeschaltet
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_jack_loudness_changed(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let idx = ui.get_program_selected() as usize;
+            let mut programs = audio_programs.lock().unwrap();
+            if let Some(prog) = programs.get_mut(idx)
+                && let Some(port) = prog.config.jack_ports.get_mut(ui.get_Jack_connection_selected() as usize)
+            {
+                port.loudness = ui.get_jack_loudness();
+                if let Err(e) = prog.save_config() {
+                    ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
+                }
+            }
+        });
+    }
+
+    {
+        // Callback: Reihenfolge beim Umschalten oder Überlappung geändert
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_jack_switching_changed(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let overlap = ui.get_jack_overlap().trim().to_string();
+            let overlap_ms = if overlap.is_empty() { Some(0) } else { overlap.parse::<u64>().ok() };
+            let Some(overlap_ms) = overlap_ms.filter(|ms| *ms <= config_schema::MAX_OVERLAP_MS) else {
+                ui.set_output(format!("Überlappung muss eine Zahl von 0 bis {} ms sein", config_schema::MAX_OVERLAP_MS).into());
+                return;
+            };
+            let idx = ui.get_program_selected() as usize;
+            let mut programs = audio_programs.lock().unwrap();
+            if let Some(prog) = programs.get_mut(idx)
+                && let Some(port) = prog.config.jack_ports.get_mut(ui.get_Jack_connection_selected() as usize)
+            {
+                port.switching = if ui.get_jack_switching() == 1 { Switching::BreakBeforeMake } else { Switching::MakeBeforeBreak };
+                port.overlap_ms = overlap_ms;
+                if let Err(e) = prog.save_config() {
+                    ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
+                }
+            }
+        });
+    }
+
     {
         // Callback: Jack-Suche geändert
         let audio_programs = audio_programs.clone();
@@ -573,7 +873,7 @@ fn main() {
                         port.target_search_name = target_search_name;
                         let result = prog.save_config();
                         if let Err(e) = result {
-                            ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
+                            ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
                         }
 
                         // Aktualisiere die Verbindungen im UI
@@ -602,7 +902,7 @@ fn main() {
                         prog.config.jack_ports.remove(selected_index);
                         let result = prog.save_config();
                         if let Err(e) = result {
-                            ui.set_output(format!("Fehler beim Starten des Programms {}: {:?}", prog.config.program_name, e).into());
+                            ui.set_output(e.context(format!("Fehler beim Speichern von {}", prog.config.program_name)).report().into());
                         }
 
                         // Aktualisiere die Verbindungen im UI
@@ -626,250 +926,2770 @@ fn main() {
     }
 
     {
+        let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
         let ui_handle = ui.as_weak();
         ui.on_jack_connect(move || {
-            if let Some(ui) = ui_handle.upgrade() {
-                let idx = ui.get_program_selected();
-                let mut programs = audio_programs.lock().unwrap();
-                let jack_index = ui.get_jack_selected();
-                if let Err(errors) = connect_jack_ports(&mut programs, jack_index, idx) {
-                    let error_message = errors.join("\n");
-                    ui.set_output(error_message.into());
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let idx = ui.get_program_selected();
+            let jack_index = ui.get_jack_selected();
+            let audio_programs = audio_programs.clone();
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run("Verbindung herstellen", move |_| {
+                let mut record = audit::Record::begin(Trigger::Manual);
+                let mut errors = Vec::new();
+                if let Err(e) = connect_jack_ports(&mut audio_programs.lock().unwrap(), jack_index, idx, &mut record) {
+                    errors.push(e);
                 }
-            }
+                if let Err(e) = audit_log.lock().unwrap().commit(record) {
+                    errors.push(e);
+                }
+                let output = Error::from_list(errors).err().map(|e| log_error(&e));
+                report_job(ui_handle, audit_log, output);
+            });
         });
     }
 
+    set_snapshots(&ui);
+
     {
         let ui_handle = ui.as_weak();
-        ui.on_jack_target_reinit(move || {
-            // JACK-Targets neu abfragen
-            let ports = read_jack_ports();
-            let jack_targets: Vec<String> = {
-                // Verwende die zuvor geparsten Ports (aus `ports`), um die JACK-Quellen zu bestimmen
-                ports
-                    .iter()
-                    .filter(|port| port.properties.iter().any(|prop| prop == "input"))
-                    .map(|port| port.name.clone())
-                    .collect()
-            };
-            // In StandardListViewItem umwandeln
-            let jack_targets_items: Vec<slint::SharedString> = jack_targets
-                .into_iter()
-                .map(|s| slint::SharedString::from(s))
-                .collect();
-            // Im UI setzen
-            if let Some(ui) = ui_handle.upgrade() {
-                ui.set_jack_targets(slint::ModelRc::new(slint::VecModel::from(jack_targets_items)));
+
+        // Callback: aktuellen Verbindungszustand speichern
+        ui.on_snapshot_save(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let name = ui.get_snapshot_name().trim().to_string();
+            match Snapshot::capture(&name).and_then(|snapshot| snapshot.save()) {
+                Ok(()) => ui.set_output(format!("Verbindungszustand '{}' gespeichert", name).into()),
+                Err(e) => ui.set_output(log_error(&e).into()),
             }
+            set_snapshots(&ui);
         });
     }
 
-    ui.set_output(start_errors.join("\n").into());
-    ui.run().unwrap();
-}
-
+    {
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
 
-fn reset_jack_view(programs: MutexGuard<'_, Vec<ManagedAudioProgram>>, ui_handle: slint::Weak<MainWindow>) {
-    let idx = ui_handle.upgrade().map(|ui| ui.get_program_selected()).unwrap_or(0) as usize;
-    if let Some(prog) = programs.get(idx) {
-        if let Some(ui) = ui_handle.upgrade() {
-            let selected_index = ui.get_Jack_connection_selected() as usize;
-            if selected_index < prog.config.jack_ports.len() {
-                let port = &prog.config.jack_ports[selected_index];
-                ui.set_jack_filter(port.filter.clone().into());
-                ui.set_jack_source(port.source_name.clone().into());
-                ui.set_jack_target(port.target_name.clone().into());
-                ui.set_jack_search(port.target_search_name.clone().into());
-            }
-        }
+        // Callback: gespeicherten Verbindungszustand wiederherstellen
+        ui.on_snapshot_restore(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Some(name) = selected_snapshot(&ui) else { return };
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run(format!("Zustand '{}' wiederherstellen", name), move |_| {
+                let mut record = audit::Record::begin(Trigger::Snapshot(name.clone()));
+                let mut messages = Vec::new();
+                match Snapshot::load(&name).and_then(|snapshot| snapshot.restore(&mut record)) {
+                    Ok(skipped) => {
+                        messages.push(format!("Verbindungszustand '{}' wiederhergestellt", name));
+                        messages.extend(skipped.into_iter().map(|c| format!("Nicht zuzuordnen: {}", c)));
+                    }
+                    Err(e) => messages.push(log_error(&e)),
+                }
+                if let Err(e) = audit_log.lock().unwrap().commit(record) {
+                    messages.push(log_error(&e));
+                }
+                report_job(ui_handle, audit_log, Some(messages.join("\n")));
+            });
+        });
     }
-}
 
+    {
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
 
-fn get_filters(programs: MutexGuard<'_, Vec<ManagedAudioProgram>>) -> Vec<String> {
-    let mut filters = {
-        let programs = programs;
-        let mut all_filters = Vec::new();
-        for prog in programs.iter() {
-            for port in prog.config.jack_ports.iter() {
-                for s in port.filter.split_whitespace() {
-                    let s = s.to_string();
-                    if !s.is_empty() {
-                        all_filters.push(s);
-                    }
+        // Callback: Verbindungszustand als Use-Case übernehmen
+        ui.on_snapshot_to_use_case(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Some(name) = selected_snapshot(&ui) else { return };
+            let use_case = ui.get_snapshot_use_case().trim().to_string();
+            let mut programs = audio_programs.lock().unwrap();
+            let mut messages = Vec::new();
+            match Snapshot::load(&name).and_then(|snapshot| snapshot.to_use_case(&use_case, &mut programs)) {
+                Ok(skipped) => {
+                    messages.push(format!("Verbindungszustand '{}' als Use-Case '{}' übernommen", name, use_case));
+                    messages.extend(skipped.into_iter().map(|c| format!("Ziel gehört zu keinem Programm: {}", c)));
                 }
+                Err(e) => messages.push(log_error(&e)),
             }
-        }
-        let mut seen = HashSet::new();
-        all_filters
-            .into_iter()
-            .filter(|s| seen.insert(s.clone()))
-            .collect::<Vec<String>>()
-    };
-    if filters.is_empty() {
-        // Wenn keine Filter vorhanden sind, füge einen Standardfilter hinzu
-        filters.push("start".to_string());
+            set_program_views(&ui, programs);
+            ui.invoke_program_selectiion_changed(ui.get_program_selected());
+            ui.set_output(messages.join("\n").into());
+        });
     }
-    filters
-}
 
+    {
+        let ui_handle = ui.as_weak();
 
-pub fn disconnect_unwanted_jack_ports(
-    apps: MutexGuard<'_, Vec<ManagedAudioProgram>>,
-    use_case: &str,
-) -> Result<(), Vec<String>> {
-    let ports = read_jack_ports();
-    let connections = read_jack_connections();
+        // Callback: gespeicherten Verbindungszustand löschen
+        ui.on_snapshot_delete(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Some(name) = selected_snapshot(&ui) else { return };
+            if let Err(e) = Snapshot::delete(&name) {
+                ui.set_output(log_error(&e).into());
+            }
+            set_snapshots(&ui);
+        });
+    }
 
-    let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
+    {
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
 
-    let wanted_connections: HashSet<(String, String)> = apps.iter()
-        .flat_map(|app| {
-            app.config.jack_ports
-                .iter()
-                .filter(|port| port.filter.split_whitespace().any(|f| f == use_case) || port.filter.is_empty())
-                .map(|port| (port.source_name.clone(), get_jack_name(&ports, &apps_jack_node_names, app.jack_node_name.clone(), port)))
-        })
-        .collect();
+        // Callback: letzte Verbindungsänderung rückgängig machen
+        ui.on_audit_undo(move || {
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run("Rückgängig machen", move |_| {
+                let output = match audit_log.lock().unwrap().undo_last() {
+                    Ok(()) => "Letzte Verbindungsänderung rückgängig gemacht".to_string(),
+                    Err(e) => log_error(&e),
+                };
+                report_job(ui_handle, audit_log, Some(output));
+            });
+        });
+    }
 
-    let mut errors = Vec::new();
+    {
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
 
-    for connection in connections.iter() {
-        let source_port = ports.iter().find(|p| p.name == connection.0);
-        let target_port = ports.iter().find(|p| p.name == connection.1);
-        if let (Some(source), Some(target)) = (source_port, target_port) {
-            // Überprüfen, ob die Verbindung unerwünscht ist
-            if !wanted_connections.contains(&(connection.0.clone(), connection.1.clone())) {
-                // Verbindung trennen
-                let output = Command::new("jack_disconnect")
-                    .arg(&source.name)
-                    .arg(&target.name)
-                    .output()
-                    .expect("Failed to disconnect JACK ports");
-                if output.status.success() {
-                    println!("Disconnected {} from {}", source.name, target.name);
-                } else {
-                    let err = format!(
-                        "Error disconnecting {} from {}: {}",
-                        source.name,
-                        target.name,
-                        String::from_utf8_lossy(&output.stderr)
-                    );
-                    eprintln!("{}", err);
-                    errors.push(err);
-                }
-            }
-        }
+        // Callback: Verbindungen wie vor dem letzten Use-Case wiederherstellen
+        ui.on_audit_rollback(move || {
+            let audit_log = audit_log.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run("Zustand vor letztem Use-Case wiederherstellen", move |_| {
+                let output = match audit_log.lock().unwrap().rollback_last_scenario() {
+                    Ok(name) => format!("Verbindungen wie vor '{}' wiederhergestellt", name),
+                    Err(e) => log_error(&e),
+                };
+                report_job(ui_handle, audit_log, Some(output));
+            });
+        });
     }
 
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(errors)
+    // Pegelmessung für die ausgewählten Ports
+    let meter_config = Arc::new(Mutex::new(MeterConfig::load().unwrap_or_else(|e| {
+        start_errors.push(log_error(&e));
+        MeterConfig::default()
+    })));
+    ui.set_meter_true_peak(meter_config.lock().unwrap().true_peak);
+    let meter: Arc<Mutex<Option<Meter>>> = Arc::new(Mutex::new(None));
+    start_errors.extend(restart_meter(&meter, &meter_config.lock().unwrap()));
+    let meter_timer = slint::Timer::default();
+    {
+        let meter = meter.clone();
+        let ui_handle = ui.as_weak();
+        meter_timer.start(slint::TimerMode::Repeated, Duration::from_millis(100), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            if ui.get_meters_visible()
+                && let Some(meter) = meter.lock().unwrap().as_ref()
+            {
+                set_meter_levels(&ui, &meter.levels());
+            }
+        });
     }
-}
 
-       
-pub fn connect_jack_ports(
-    apps: &mut Vec<ManagedAudioProgram>,
-    app_index: i32,
-    jack_index: i32,
-) -> Result<(), Vec<String>> {
-    let mut errors = Vec::new();
-    let ports = read_jack_ports();
-    if app_index >= 0 && app_index < apps.len() as i32 {
-        let app_index_usize = app_index as usize;
-        let app_jack_node_name;
-        {
-            let app = apps.get(app_index_usize).unwrap();
-            app_jack_node_name = app.jack_node_name.clone();
-        }
-        // Avoid holding a mutable borrow while iterating immutably
-        // First, get the port and clone the relevant data
-        let port = {
-            let app = apps.get(app_index_usize).unwrap();
-            app.config.jack_ports.get(jack_index as usize).cloned()
-        };
-        if let Some(port) = port {
-            let source_port = ports.iter().find(|p| p.name == port.source_name);
-            let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
-            let search_target = get_jack_name(&ports, &apps_jack_node_names, app_jack_node_name, &port);
-            // Now get mutable app only after all immutable borrows are done
-            let app = apps.get_mut(app_index_usize).unwrap();
-            let target_port = ports.iter().find(|p| p.name == search_target);
-            if let (Some(source), Some(target)) = (source_port, target_port) {
-                let output = Command::new("jack_connect")
-                    .arg(&source.name)
-                    .arg(&target.name)
-                    .output()
-                    .expect("Failed to connect JACK ports");
-                if output.status.success() {
-                    app.jack_node_name = target_port
-                        .as_ref()
-                        .map(|p| p.name.split(':').next().unwrap_or(&p.name).to_string())
-                        .unwrap_or_default();
-                    let result = app.save_jack_target();
-                    if let Err(e) = result {
-                        errors.push(format!("Fehler beim Speichern des JACK-Ziels: {:?}", e));
-                    }
-                } else {
-                    errors.push(format!(
-                        "Fehler beim Verbinden {} zu {}: {}",
-                        source.name,
-                        target.name,
-                        String::from_utf8_lossy(&output.stderr)
-                    ));
-                }
+    {
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Messung eines Ports an- oder abschalten
+        ui.on_meter_toggle(move |port| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let mut config = meter_config.lock().unwrap();
+            let mut messages = Vec::new();
+            if config.toggle(&port) {
+                messages.push(format!("Pegel von {} wird gemessen", port));
             } else {
-                errors.push(format!(
-                    "Source oder Target Port nicht gefunden: {} -> {}",
-                    port.source_name, port.target_name
-                ));
+                messages.push(format!("Pegel von {} wird nicht mehr gemessen", port));
             }
-        } else {
-            errors.push("Ungültiger Port-Index".to_string());
-        }
-    } else {
-        errors.push("Ungültiger App-Index".to_string());
-    }
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(errors)
+            if let Err(e) = config.save() {
+                messages.push(log_error(&e));
+            }
+            messages.extend(restart_meter(&meter, &config));
+            set_route_meters(&ui, &config);
+            ui.set_output(messages.join("\n").into());
+        });
     }
-}
 
+    {
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: True-Peak-Schätzung an- oder abschalten
+        ui.on_meter_true_peak_changed(move |enabled| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let mut config = meter_config.lock().unwrap();
+            config.true_peak = enabled;
+            let mut messages = Vec::new();
+            if let Err(e) = config.save() {
+                messages.push(log_error(&e));
+            }
+            messages.extend(restart_meter(&meter, &config));
+            if !messages.is_empty() {
+                ui.set_output(messages.join("\n").into());
+            }
+        });
+    }
 
-fn get_jack_name(ports: &Vec<managed_audio_program::JackPortInfo>, apps_jack_node_names: &Vec<String>, app_jack_node_name: String, port: &JackPort) -> String {
-    let search_target = if !app_jack_node_name.is_empty() {
-        app_jack_node_name.clone() + ":" + if port.target_name.contains(':') {
-            &port.target_name.split(':').last().unwrap_or(&port.target_name)
-        } else {
-            &port.target_name
-        }
-    } else if port.target_name.contains('*') {
-        let matching_targets = ports.iter().filter(|p| {
-            let pattern = port.target_name.clone();
-            let re = regex::Regex::new(&format!("^{}$", pattern.replace("*", ".*"))).unwrap();
-            re.is_match(&p.name)
-        }).collect::<Vec<_>>();
-        if !matching_targets.is_empty() {
-            let mut first_matching_target = matching_targets.first().unwrap();
-            for matching_target in &matching_targets {
-                // Use the collected jack_node_names instead of borrowing apps again
-                if apps_jack_node_names.iter().any(|name| name == &matching_target.name) {
-                    continue;
-                }
-                first_matching_target = matching_target;
-                break; // Nur das erste passende Ziel verbinden
+    // Stille-Erkennung auf den gemessenen Pegeln
+    let silence_watch = Arc::new(Mutex::new(SilenceWatch::default()));
+    set_silence_detectors(&ui, &meter_config.lock().unwrap(), &silence_watch.lock().unwrap(), Instant::now());
+    let silence_timer = slint::Timer::default();
+    {
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let silence_watch = silence_watch.clone();
+        let audit_log = audit_log.clone();
+        let ui_handle = ui.as_weak();
+        silence_timer.start(slint::TimerMode::Repeated, Duration::from_millis(250), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            // Ohne laufende Messung ist nichts bekannt, auch keine Stille
+            let Some(levels) = meter.lock().unwrap().as_ref().map(|m| m.levels()) else { return };
+            let config = meter_config.lock().unwrap();
+            let mut watch = silence_watch.lock().unwrap();
+            let now = Instant::now();
+            for event in watch.update(&config.silence, &levels, now) {
+                handle_silence_event(&ui, &config.silence, &mut watch, &audit_log.lock().unwrap(), event);
             }
-            first_matching_target.name.clone()
-        } else {
-            port.target_name.clone()
-        }
-    } else {
-        port.target_name.clone()
-    };
-    search_target
+            if ui.get_signal_visible() {
+                set_silence_detectors(&ui, &config, &watch, now);
+            }
+        });
+    }
+
+    {
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let silence_watch = silence_watch.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Stille-Erkennung hinzufügen
+        ui.on_silence_add(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let port = ui.get_silence_port().trim().to_string();
+            let threshold = ui.get_silence_threshold().trim().replace(',', ".").parse::<f32>();
+            let duration = ui.get_silence_duration().trim().replace(',', ".").parse::<f32>();
+            let (Ok(threshold_db), Ok(duration_s)) = (threshold, duration) else {
+                ui.set_output("Schwelle (dBFS) und Dauer (s) müssen Zahlen sein".into());
+                return;
+            };
+            // "NaN" und "inf" lassen sich als f32 lesen; mit NaN würde nie Stille erkannt
+            if !threshold_db.is_finite() || !duration_s.is_finite() || duration_s < 0.0 {
+                ui.set_output("Die Schwelle muss eine endliche Zahl sein, die Dauer eine Zahl ab 0".into());
+                return;
+            }
+            if port.is_empty() {
+                ui.set_output("Kein Port angegeben".into());
+                return;
+            }
+            let detector = SilenceDetector {
+                port,
+                threshold_db,
+                duration_s,
+                alert: ui.get_silence_alert_enabled(),
+                hook: ui.get_silence_hook().trim().to_string(),
+                fallback: ui.get_silence_fallback().trim().to_string(),
+                switch_back: ui.get_silence_switch_back(),
+            };
+            let mut config = meter_config.lock().unwrap();
+            let mut messages = vec![format!("Stille-Erkennung: {}", detector)];
+            config.silence.push(detector);
+            if let Err(e) = config.save() {
+                messages.push(log_error(&e));
+            }
+            messages.extend(restart_meter(&meter, &config));
+            set_silence_detectors(&ui, &config, &silence_watch.lock().unwrap(), Instant::now());
+            ui.set_output(messages.join("\n").into());
+        });
+    }
+
+    {
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let silence_watch = silence_watch.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Stille-Erkennung entfernen
+        ui.on_silence_remove(move |index| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Ok(index) = usize::try_from(index) else { return };
+            let mut config = meter_config.lock().unwrap();
+            if index >= config.silence.len() {
+                return;
+            }
+            let detector = config.silence.remove(index);
+            let mut watch = silence_watch.lock().unwrap();
+            watch.remove(index);
+            if !watch.any_triggered() {
+                ui.set_silence_alert("".into());
+            }
+            let mut messages = vec![format!("Stille-Erkennung für {} entfernt", detector.port)];
+            if let Err(e) = config.save() {
+                messages.push(log_error(&e));
+            }
+            messages.extend(restart_meter(&meter, &config));
+            set_silence_detectors(&ui, &config, &watch, Instant::now());
+            ui.set_output(messages.join("\n").into());
+        });
+    }
+
+    // Lautheit der ausgehenden Streams, protokolliert je Sendung (aktiver Use-Case)
+    let loudness_monitor: Arc<Mutex<Option<LoudnessMonitor>>> = Arc::new(Mutex::new(None));
+    {
+        let config = meter_config.lock().unwrap();
+        ui.set_loudness_target(format!("{}", config.loudness.target_lufs).into());
+        ui.set_loudness_tolerance(format!("{}", config.loudness.tolerance_lu).into());
+    }
+    ui.set_loudness_log_dir(paths::log_dir().join(loudness::LOG_DIR).display().to_string().into());
+    let loudness_timer = slint::Timer::default();
+    {
+        let loudness_monitor = loudness_monitor.clone();
+        let meter_config = meter_config.clone();
+        let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        let alerts = Arc::new(Mutex::new(LoudnessAlerts::default()));
+        let pending = Arc::new(AtomicBool::new(false));
+        let mut tick: u64 = 0;
+        loudness_timer.start(slint::TimerMode::Repeated, Duration::from_secs(1), move || {
+            // Messung und Protokoll laufen im Worker; steht die letzte noch aus, keine weitere anhängen
+            if ui_handle.upgrade().is_none() || pending.swap(true, Ordering::Relaxed) {
+                return;
+            }
+            let show = audit_log.lock().unwrap().active_scenario().unwrap_or("ohne Use-Case").to_string();
+            let loudness_monitor = loudness_monitor.clone();
+            let meter_config = meter_config.clone();
+            let audio_programs = audio_programs.clone();
+            let alerts = alerts.clone();
+            let pending = pending.clone();
+            let ui_handle = ui_handle.clone();
+            let current = tick;
+            tick += 1;
+            worker.run_quiet("Lautheit messen", move |_| {
+                let update = measure_loudness(&loudness_monitor, &audio_programs, &meter_config, show, current, &mut alerts.lock().unwrap());
+                pending.store(false, Ordering::Relaxed);
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        show_loudness(&ui, update);
+                    }
+                });
+            });
+        });
+    }
+
+    {
+        let loudness_monitor = loudness_monitor.clone();
+        let meter_config = meter_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Zielbereich der Lautheit geändert
+        ui.on_loudness_target_changed(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let target = ui.get_loudness_target().trim().replace(',', ".").parse::<f64>();
+            let tolerance = ui.get_loudness_tolerance().trim().replace(',', ".").parse::<f64>();
+            let (Ok(target_lufs), Ok(tolerance_lu)) = (target, tolerance) else {
+                ui.set_output("Ziel (LUFS) und Toleranz (LU) müssen Zahlen sein".into());
+                return;
+            };
+            // "NaN" und "inf" lassen sich als f64 lesen; damit wäre jede Messung außerhalb des Ziels
+            if !target_lufs.is_finite() || !tolerance_lu.is_finite() || tolerance_lu <= 0.0 {
+                ui.set_output("Das Ziel muss eine endliche Zahl sein, die Toleranz größer als 0".into());
+                return;
+            }
+            let mut config = meter_config.lock().unwrap();
+            config.loudness = loudness::LoudnessConfig { target_lufs, tolerance_lu };
+            if let Some(monitor) = loudness_monitor.lock().unwrap().as_mut() {
+                monitor.set_config(&config.loudness);
+            }
+            match config.save() {
+                Ok(()) => ui.set_output(format!("Lautheitsziel {} ± {} LUFS", target_lufs, tolerance_lu).into()),
+                Err(e) => ui.set_output(log_error(&e).into()),
+            }
+        });
+    }
+
+    // Testton auf die Ziele des ausgewählten Programms, die Pegelmessung zeigt, ob er ankommt
+    let generator: Arc<Mutex<Option<Generator>>> = Arc::new(Mutex::new(None));
+    let tone_timer = slint::Timer::default();
+    {
+        let generator = generator.clone();
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let ui_handle = ui.as_weak();
+        tone_timer.start(slint::TimerMode::Repeated, Duration::from_millis(250), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let mut generator = generator.lock().unwrap();
+            let Some(running) = generator.as_ref() else { return };
+            if running.expired() {
+                *generator = None;
+                ui.set_output(stop_tone(&ui, &meter, &meter_config.lock().unwrap()).join("\n").into());
+                return;
+            }
+            let levels = meter.lock().unwrap().as_ref().map(|m| m.levels()).unwrap_or_default();
+            let mut lines = vec![format!("{} noch {} s", running.tone(), running.remaining().as_secs() + 1)];
+            for target in running.targets() {
+                let level = levels.iter().find(|(port, _)| port == target).and_then(|(_, level)| *level);
+                match level {
+                    Some(level) if level.peak > meter::MIN_DB => lines.push(format!("{}: {}", target, level)),
+                    _ => lines.push(format!("{}: kein Signal", target)),
+                }
+            }
+            ui.set_tone_status(lines.join("\n").into());
+        });
+    }
+
+    {
+        let generator = generator.clone();
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Testton starten, ein Ausgang je Ziel in der Reihenfolge der Verbindungen
+        ui.on_tone_start(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let frequency = ui.get_tone_frequency().trim().replace(',', ".").parse::<f32>();
+            let level = ui.get_tone_level().trim().replace(',', ".").parse::<f32>();
+            let duration = ui.get_tone_duration().trim().parse::<u64>();
+            let (Ok(frequency_hz), Ok(level_db), Ok(duration)) = (frequency, level, duration) else {
+                ui.set_output("Frequenz (Hz), Pegel (dBFS) und Dauer (s) müssen Zahlen sein".into());
+                return;
+            };
+            // "NaN" und "inf" lassen sich als f32 lesen; die Obergrenze der Frequenz prüft der Generator
+            if !frequency_hz.is_finite() || frequency_hz <= 0.0 || !level_db.is_finite() || duration == 0 {
+                ui.set_output("Frequenz und Dauer müssen größer als 0 sein, der Pegel eine endliche Zahl".into());
+                return;
+            }
+            let tone = Tone {
+                waveform: match ui.get_tone_waveform() {
+                    1 => Waveform::PinkNoise,
+                    2 => Waveform::Identification,
+                    _ => Waveform::Sine,
+                },
+                frequency_hz,
+                level_db,
+            };
+            let mut generator = generator.lock().unwrap();
+            // Der alte Client muss abgemeldet sein, bevor der neue denselben Namen bekommt
+            *generator = None;
+            let targets = {
+                let programs = audio_programs.lock().unwrap();
+                let Some(prog) = programs.get(ui.get_program_selected() as usize) else { return };
+                tone_targets(prog, &programs)
+            };
+            let targets = match targets {
+                Ok(targets) if targets.is_empty() => {
+                    ui.set_output("Keines der Ziele ist gerade vorhanden".into());
+                    return;
+                }
+                Ok(targets) => targets,
+                Err(e) => {
+                    ui.set_output(log_error(&e).into());
+                    return;
+                }
+            };
+            match Generator::start(tone, &targets, Duration::from_secs(duration)) {
+                Ok(started) => *generator = Some(started),
+                Err(e) => {
+                    ui.set_output(log_error(&e).into());
+                    return;
+                }
+            }
+            // Die Ziele nur für die Dauer des Testtons messen, ohne die Auswahl zu speichern
+            let mut config = meter_config.lock().unwrap().clone();
+            for target in targets.iter() {
+                if !config.measured_ports().contains(target) {
+                    config.ports.push(target.clone());
+                }
+            }
+            let mut messages = vec![format!("Testton: {} auf {}", tone, targets.join(", "))];
+            messages.extend(restart_meter(&meter, &config));
+            ui.set_tone_active(true);
+            ui.set_output(messages.join("\n").into());
+        });
+    }
+
+    {
+        let generator = generator.clone();
+        let meter = meter.clone();
+        let meter_config = meter_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Testton vorzeitig beenden
+        ui.on_tone_stop(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            if generator.lock().unwrap().take().is_some() {
+                ui.set_output(stop_tone(&ui, &meter, &meter_config.lock().unwrap()).join("\n").into());
+            }
+        });
+    }
+
+    {
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Umlauflatenz zwischen zwei Ports messen
+        ui.on_latency_measure(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let send = ui.get_latency_send().trim().to_string();
+            let receive = ui.get_latency_receive().trim().to_string();
+            if send.is_empty() || receive.is_empty() {
+                ui.set_output("Sende- und Rückport angeben".into());
+                return;
+            }
+            let ui_handle = ui_handle.clone();
+            worker.run("Latenz messen", move |progress| {
+                let result = latency::measure(&send, &receive, &|pending| {
+                    progress.report(format!("noch {} Testsignale", pending));
+                    progress.check()
+                });
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    match result {
+                        Ok(report) => ui.set_latency_result(report.to_string().into()),
+                        Err(e) => ui.set_output(log_error(&e).into()),
+                    }
+                });
+            });
+        });
+    }
+
+    {
+        let worker = worker.clone();
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: von JACK gemeldete Latenzen der konfigurierten Verbindungen abfragen
+        ui.on_latency_routes_refresh(move || {
+            let audio_programs = audio_programs.clone();
+            let ui_handle = ui_handle.clone();
+            worker.run("Portlatenzen abfragen", move |_| {
+                let routes = latency::route_latencies(&audio_programs.lock().unwrap());
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    match routes {
+                        Ok(routes) => {
+                            let items: Vec<StandardListViewItem> = routes
+                                .iter()
+                                .map(|route| StandardListViewItem::from(SharedString::from(route.to_string())))
+                                .collect();
+                            ui.set_latency_routes(ModelRc::new(VecModel::from(items)));
+                        }
+                        Err(e) => ui.set_output(log_error(&e).into()),
+                    }
+                });
+            });
+        });
+    }
+
+    // Mehrspuraufnahme der ausgewählten Ports, von Hand oder beim Start bestimmter Use-Cases
+    let recorder_config = Arc::new(Mutex::new(RecorderConfig::load().unwrap_or_else(|e| {
+        start_errors.push(log_error(&e));
+        RecorderConfig::default()
+    })));
+    set_recorder_config(&ui, &recorder_config.lock().unwrap());
+    let recorder: Arc<Mutex<Option<Recorder>>> = Arc::new(Mutex::new(None));
+    start_errors.extend(restart_recorder(&recorder, &recorder_config.lock().unwrap()));
+    let recorder_timer = slint::Timer::default();
+    {
+        let recorder = recorder.clone();
+        let recorder_config = recorder_config.clone();
+        let audit_log = audit_log.clone();
+        let ui_handle = ui.as_weak();
+        let mut tick: u64 = 0;
+        // Beim Programmstart läuft ggf. schon ein Use-Case, der soll keine Aufnahme auslösen
+        let mut last_scenario = audit_log.lock().unwrap().active_scenario().map(str::to_string);
+        let mut automatic = false;
+        recorder_timer.start(slint::TimerMode::Repeated, Duration::from_secs(1), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let recorder = recorder.lock().unwrap();
+            let Some(recorder) = recorder.as_ref() else {
+                ui.set_recorder_status("Keine Ports ausgewählt".into());
+                ui.set_recorder_recording(false);
+                return;
+            };
+            if tick.is_multiple_of(2) {
+                for e in recorder.sync() {
+                    tracing::debug!("{}", e.report());
+                }
+            }
+            tick += 1;
+
+            let scenario = audit_log.lock().unwrap().active_scenario().map(str::to_string);
+            if scenario != last_scenario {
+                let config = recorder_config.lock().unwrap();
+                match scenario.as_deref() {
+                    Some(name) if config.scenarios.iter().any(|s| s == name) => {
+                        tracing::info!(use_case = name, "Aufnahme für Use-Case gestartet");
+                        recorder.record(name);
+                        automatic = true;
+                    }
+                    // Eine automatisch gestartete Aufnahme endet mit dem Wechsel zu einem anderen Use-Case
+                    _ if automatic => {
+                        recorder.stop();
+                        automatic = false;
+                    }
+                    _ => {}
+                }
+                last_scenario = scenario;
+            }
+
+            let status = recorder.status();
+            ui.set_recorder_recording(status.recording);
+            ui.set_recorder_error(status.error.clone().unwrap_or_default().into());
+            if ui.get_recorder_visible() {
+                let dir = status.dir.as_ref().map(|dir| format!(" – {}", dir.display())).unwrap_or_default();
+                ui.set_recorder_status(format!("{}{}", status, dir).into());
+            }
+        });
+    }
+
+    {
+        let recorder = recorder.clone();
+        let audit_log = audit_log.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Aufnahme von Hand starten
+        ui.on_recorder_start(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let recorder = recorder.lock().unwrap();
+            let Some(recorder) = recorder.as_ref() else {
+                ui.set_output("Keine Ports für die Aufnahme ausgewählt".into());
+                return;
+            };
+            let mut show = ui.get_recorder_show().trim().to_string();
+            if show.is_empty() {
+                show = audit_log.lock().unwrap().active_scenario().unwrap_or("Aufnahme").to_string();
+            }
+            tracing::info!(show = %show, "Aufnahme von Hand gestartet");
+            recorder.record(&show);
+            ui.set_output(format!("Aufnahme '{}' gestartet", show).into());
+        });
+    }
+
+    {
+        let recorder = recorder.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Aufnahme beenden
+        ui.on_recorder_stop(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            if let Some(recorder) = recorder.lock().unwrap().as_ref() {
+                recorder.stop();
+                ui.set_output("Aufnahme beendet".into());
+            }
+        });
+    }
+
+    {
+        let recorder = recorder.clone();
+        let recorder_config = recorder_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Port zur Aufnahme hinzufügen
+        ui.on_recorder_port_add(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let port = ui.get_recorder_port().trim().to_string();
+            if port.is_empty() {
+                ui.set_output("Kein Port angegeben".into());
+                return;
+            }
+            let mut config = recorder_config.lock().unwrap();
+            if config.ports.contains(&port) {
+                ui.set_output(format!("{} wird bereits aufgenommen", port).into());
+                return;
+            }
+            let mut changed = config.clone();
+            changed.ports.push(port.clone());
+            let messages = apply_recorder_config(&recorder, &mut config, changed, format!("{} wird aufgenommen", port));
+            set_recorder_config(&ui, &config);
+            ui.set_output(messages.join("\n").into());
+        });
+    }
+
+    {
+        let recorder = recorder.clone();
+        let recorder_config = recorder_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Port aus der Aufnahme entfernen
+        ui.on_recorder_port_remove(move |index| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Ok(index) = usize::try_from(index) else { return };
+            let mut config = recorder_config.lock().unwrap();
+            if index >= config.ports.len() {
+                return;
+            }
+            let mut changed = config.clone();
+            let port = changed.ports.remove(index);
+            let messages = apply_recorder_config(&recorder, &mut config, changed, format!("{} wird nicht mehr aufgenommen", port));
+            set_recorder_config(&ui, &config);
+            ui.set_output(messages.join("\n").into());
+        });
+    }
+
+    {
+        let recorder = recorder.clone();
+        let recorder_config = recorder_config.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Einstellungen der Aufnahme speichern
+        ui.on_recorder_save(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let number = |text: SharedString| text.trim().parse::<u64>();
+            let (Ok(pre_roll_s), Ok(split_minutes), Ok(split_mb), Ok(min_free_mb)) = (
+                number(ui.get_recorder_pre_roll()),
+                number(ui.get_recorder_split_minutes()),
+                number(ui.get_recorder_split_mb()),
+                number(ui.get_recorder_min_free()),
+            ) else {
+                ui.set_output("Pre-Roll, Teilen und freier Speicher müssen ganze Zahlen sein".into());
+                return;
+            };
+            let mut config = recorder_config.lock().unwrap();
+            let changed = RecorderConfig {
+                ports: config.ports.clone(),
+                layout: if ui.get_recorder_layout() == 1 { FileLayout::Interleaved } else { FileLayout::PerPort },
+                format: if ui.get_recorder_format() == 1 { AudioFormat::Flac } else { AudioFormat::Wav },
+                pre_roll_s,
+                split_minutes,
+                split_mb,
+                min_free_mb,
+                dir: ui.get_recorder_dir().trim().to_string(),
+                scenarios: ui
+                    .get_recorder_scenarios()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            };
+            let messages = apply_recorder_config(&recorder, &mut config, changed, "Aufnahme-Einstellungen gespeichert".to_string());
+            set_recorder_config(&ui, &config);
+            ui.set_output(messages.join("\n").into());
+        });
+    }
+
+    // Zustand der Programme und Verbindungen im Hintergrund abfragen
+    let xrun_history = Arc::new(Mutex::new(XrunHistory::new()));
+    let _status_monitor = {
+        let meter_config = meter_config.clone();
+        let xrun_history = xrun_history.clone();
+        let ui_handle = ui.as_weak();
+        StatusMonitor::start(audio_programs.clone(), move |statuses| {
+            let meter_config = meter_config.clone();
+            let xrun_history = xrun_history.clone();
+            let ui_handle = ui_handle.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_handle.upgrade() {
+                    xrun_history.lock().unwrap().observe(&statuses, chrono::Local::now());
+                    set_program_status(&ui, &statuses);
+                    set_route_meters(&ui, &meter_config.lock().unwrap());
+                }
+            });
+        })
+    };
+
+    // Zustand des JACK-Servers: Kennzahlen, Xruns und ihr Bezug zu Start und Ende der Programme
+    let health_timer = slint::Timer::default();
+    {
+        let xrun_history = xrun_history.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        let mut monitor: Option<HealthMonitor> = None;
+        // Anmeldung und Speichern laufen im Worker, ein neuer Monitor kommt hierüber zurück
+        let started: Arc<Mutex<Option<HealthMonitor>>> = Arc::new(Mutex::new(None));
+        let pending = Arc::new(AtomicBool::new(false));
+        let mut lost = false;
+        let mut tick: u64 = 0;
+        let mut shown_alert = String::new();
+        health_timer.start(slint::TimerMode::Repeated, Duration::from_secs(1), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            if monitor.as_ref().is_some_and(|m| m.is_stopped()) {
+                monitor = None;
+                lost = true;
+            }
+            if monitor.is_none()
+                && let Some(started) = started.lock().unwrap().take()
+            {
+                tracing::info!("JACK-Server erreichbar: {}", started.status());
+                monitor = Some(started);
+                lost = false;
+            }
+            // Ohne Server nur alle paar Sekunden ein neuer Versuch
+            let connect = monitor.is_none() && tick.is_multiple_of(5);
+            tick += 1;
+            if !pending.swap(true, Ordering::Relaxed) {
+                let started = started.clone();
+                let pending = pending.clone();
+                let xrun_history = xrun_history.clone();
+                worker.run_quiet("JACK-Server beobachten", move |_| {
+                    if connect {
+                        match HealthMonitor::start() {
+                            Ok(monitor) => *started.lock().unwrap() = Some(monitor),
+                            Err(e) => tracing::debug!("{}", e.report()),
+                        }
+                    }
+                    if let Err(e) = xrun_history.lock().unwrap().save() {
+                        tracing::warn!("Xrun-Verlauf: {}", e.report());
+                    }
+                    pending.store(false, Ordering::Relaxed);
+                });
+            }
+
+            let mut history = xrun_history.lock().unwrap();
+            let alert = match monitor.as_mut() {
+                None => {
+                    ui.set_health_state(2);
+                    ui.set_health_status("JACK-Server nicht erreichbar".into());
+                    if lost { "JACK-Server wurde beendet".to_string() } else { String::new() }
+                }
+                Some(monitor) => {
+                    let xruns = monitor.take_xruns();
+                    if !xruns.is_empty() {
+                        tracing::warn!(count = xruns.len(), "Xrun");
+                        history.record(&xruns);
+                    }
+                    let status = monitor.status();
+                    let mut problems = Vec::new();
+                    // Feste Texte, damit eine ausgeblendete Meldung nicht mit jeder Zahl wiederkommt
+                    if history.recent(chrono::Local::now()) >= health::BURST_COUNT {
+                        problems.push("gehäufte Xruns".to_string());
+                    }
+                    if status.dsp_load >= health::HIGH_LOAD {
+                        problems.push(format!("DSP-Last über {:.0} %", health::HIGH_LOAD));
+                    }
+                    ui.set_health_state(if problems.is_empty() { 0 } else { 1 });
+                    ui.set_health_status(status.to_string().into());
+                    if problems.is_empty() { String::new() } else { format!("JACK: {}", problems.join(", ")) }
+                }
+            };
+            // Nur bei Änderungen setzen, damit eine ausgeblendete Meldung ausgeblendet bleibt
+            if alert != shown_alert {
+                if !alert.is_empty() {
+                    tracing::warn!("{}", alert);
+                }
+                ui.set_health_alert(alert.clone().into());
+                shown_alert = alert;
+            }
+            if ui.get_health_visible() {
+                set_health_history(&ui, &history);
+            }
+        });
+    }
+
+    // Patchbay und Matrix: live aktualisieren, solange der Tab sichtbar ist und kein Kabel gezogen wird
+    let graph_views = GraphViews::default();
+    let graph_timer = slint::Timer::default();
+    {
+        let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let graph_views = graph_views.clone();
+        let meter = meter.clone();
+        let meter_pending = Arc::new(AtomicBool::new(false));
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        graph_timer.start(slint::TimerMode::Repeated, Duration::from_secs(2), move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            // Messeingänge folgen den Verbindungen der gemessenen Eingänge, Fehler wurden beim Start gemeldet
+            if !meter_pending.swap(true, Ordering::Relaxed) {
+                let meter = meter.clone();
+                let meter_pending = meter_pending.clone();
+                worker.run_quiet("Messeingänge abgleichen", move |_| {
+                    if let Some(meter) = meter.lock().unwrap().as_ref() {
+                        for e in meter.sync() {
+                            tracing::debug!("{}", e.report());
+                        }
+                    }
+                    meter_pending.store(false, Ordering::Relaxed);
+                });
+            }
+            // Steht die letzte Abfrage noch aus, etwa hinter einem langen Auftrag, keine weitere anhängen
+            if ui.get_patchbay_visible() && ui.get_patch_drag_from() < 0 && !graph_views.patch_pending.load(Ordering::Relaxed) {
+                refresh_patchbay(&ui, &worker, &audio_programs, &audit_log.lock().unwrap(), &graph_views);
+            }
+            if ui.get_matrix_visible() && !graph_views.matrix_pending.load(Ordering::Relaxed) {
+                refresh_matrix(&ui, &worker, &audio_programs, &graph_views);
+            }
+        });
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let graph_views = graph_views.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_matrix_refresh(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                refresh_matrix(&ui, &worker, &audio_programs, &graph_views);
+            }
+        });
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let graph_views = graph_views.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Zelle der Matrix angeklickt
+        ui.on_matrix_toggle(move |row, column| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let (Ok(row), Ok(column)) = (usize::try_from(row), usize::try_from(column)) else { return };
+            let mut programs = audio_programs.lock().unwrap();
+            let use_case = selected_matrix_use_case(&ui);
+            match toggle_matrix_rule(&mut programs, &graph_views.matrix.lock().unwrap(), row, column, &use_case) {
+                Ok(message) => ui.set_output(message.into()),
+                Err(e) => ui.set_output(log_error(&e).into()),
+            }
+            set_program_views(&ui, programs);
+            ui.invoke_program_selectiion_changed(ui.get_program_selected());
+            refresh_matrix(&ui, &worker, &audio_programs, &graph_views);
+        });
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let graph_views = graph_views.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_patch_refresh(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                refresh_patchbay(&ui, &worker, &audio_programs, &audit_log.lock().unwrap(), &graph_views);
+            }
+        });
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let audit_log = audit_log.clone();
+        let graph_views = graph_views.clone();
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Kabel in der Patchbay losgelassen
+        ui.on_patch_drop(move |from, x, y| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let Some((source, target)) = patchbay::drop_target(&graph_views.patch.lock().unwrap().pins, from as usize, x, y) else { return };
+            let use_case = audit_log.lock().unwrap().active_scenario().unwrap_or_default().to_string();
+            let save_rules = ui.get_patch_save_rules();
+            let audio_programs = audio_programs.clone();
+            let audit_log = audit_log.clone();
+            let graph_views = graph_views.clone();
+            let ui_handle = ui_handle.clone();
+            graph_views.patch_pending.store(true, Ordering::Relaxed);
+            worker.run(format!("{} -> {} umschalten", source, target), move |_| {
+                let mut record = audit::Record::begin(Trigger::Manual);
+                let mut messages = Vec::new();
+                match toggle_patch_connection(&mut audio_programs.lock().unwrap(), &source, &target, &use_case, save_rules, &mut record) {
+                    Ok(message) => messages.push(message),
+                    Err(e) => messages.push(log_error(&e)),
+                }
+                if let Err(e) = audit_log.lock().unwrap().commit(record) {
+                    messages.push(log_error(&e));
+                }
+                let graph = read_patch_graph(&audio_programs, &use_case);
+                graph_views.patch_pending.store(false, Ordering::Relaxed);
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    set_audit_entries(&ui, &audit_log.lock().unwrap());
+                    show_patchbay(&ui, graph, &mut graph_views.patch.lock().unwrap());
+                    if save_rules {
+                        ui.invoke_program_selectiion_changed(ui.get_program_selected());
+                    }
+                    ui.set_output(messages.join("\n").into());
+                });
+            });
+        });
+    }
+
+    {
+        let worker = worker.clone();
+        let ui_handle = ui.as_weak();
+        ui.on_jack_target_reinit(move || {
+            let ui_handle = ui_handle.clone();
+            worker.run("JACK-Ports abfragen", move |_| {
+                // JACK-Targets neu abfragen, dabei auch prüfen, ob JACK inzwischen verfügbar ist
+                let ports = read_jack_ports().map_err(|e| jack_unavailable_message(&e));
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    let ports = match ports {
+                        Ok(ports) => {
+                            ui.set_jack_unavailable("".into());
+                            ports
+                        }
+                        Err(message) => {
+                            ui.set_jack_unavailable(message.into());
+                            return;
+                        }
+                    };
+                    let jack_sources: Vec<SharedString> = ports
+                        .iter()
+                        .filter(|port| port.properties.iter().any(|prop| prop == "output"))
+                        .map(|port| SharedString::from(port.name.clone()))
+                        .collect();
+                    let jack_targets: Vec<SharedString> = ports
+                        .iter()
+                        .filter(|port| port.properties.iter().any(|prop| prop == "input"))
+                        .map(|port| SharedString::from(port.name.clone()))
+                        .collect();
+                    ui.set_jack_sources(ModelRc::new(VecModel::from(jack_sources)));
+                    ui.set_jack_targets(ModelRc::new(VecModel::from(jack_targets)));
+                });
+            });
+        });
+    }
+
+    {
+        let midi_config = midi_config.clone();
+        let midi_learn = midi_learn.clone();
+        let midi_controller = midi_controller.clone();
+        let audit_log = audit_log.clone();
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: MIDI-Ein-/Ausgang geändert
+        ui.on_midi_ports_changed(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let mut errors = Vec::new();
+                {
+                    let mut config = midi_config.lock().unwrap();
+                    config.input_port = ui.get_midi_input_port().trim().to_string();
+                    config.output_port = ui.get_midi_output_port().trim().to_string();
+                    if let Err(e) = config.save() {
+                        errors.push(log_error(&e));
+                    }
+                }
+                // Alte Verbindungen schließen, bevor neu verbunden wird
+                midi_controller.lock().unwrap().take();
+                errors.extend(connect_midi(&midi_config, &midi_learn, &midi_controller, &audio_programs, &audit_log, ui_handle.clone()));
+                if errors.is_empty() {
+                    errors.push("MIDI-Ports übernommen".to_string());
+                }
+                ui.set_output(errors.join("\n").into());
+            }
+        });
+    }
+
+    {
+        let midi_learn = midi_learn.clone();
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Lernmodus ein-/ausschalten
+        ui.on_midi_learn(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let mut learn = midi_learn.lock().unwrap();
+                if learn.take().is_some() {
+                    ui.set_midi_learning(false);
+                    return;
+                }
+                let target = ui.get_midi_learn_target().trim().to_string();
+                if target.is_empty() {
+                    ui.set_output("Bitte zuerst Use-Case oder Programmname angeben.".into());
+                    return;
+                }
+                let kind = ui.get_midi_learn_kind();
+                if kind == 0 {
+                    *learn = Some(MidiAction::UseCase(target));
+                } else {
+                    // Programme werden über die ID zugeordnet, damit die Zuordnung ein Umbenennen übersteht
+                    let programs = audio_programs.lock().unwrap();
+                    let Some(prog) = programs.iter().find(|p| p.config.program_name == target) else {
+                        ui.set_output(format!("Programm nicht gefunden: {}", target).into());
+                        return;
+                    };
+                    let id = prog.config.id.clone();
+                    *learn = Some(if kind == 1 { MidiAction::StartProgram(id) } else { MidiAction::StopProgram(id) });
+                }
+                ui.set_midi_learning(true);
+            }
+        });
+    }
+
+    {
+        let midi_config = midi_config.clone();
+        let audio_programs = audio_programs.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: MIDI-Zuordnung entfernen
+        ui.on_midi_mapping_remove(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let mut config = midi_config.lock().unwrap();
+                let selected_index = ui.get_midi_mapping_selected();
+                if selected_index >= 0 && (selected_index as usize) < config.mappings.len() {
+                    config.mappings.remove(selected_index as usize);
+                    if let Err(e) = config.save() {
+                        ui.set_output(log_error(&e).into());
+                    }
+                    set_midi_mappings(&ui, &config, &audio_programs.lock().unwrap());
+                }
+            }
+        });
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let midi_config = midi_config.clone();
+        let midi_learn = midi_learn.clone();
+        let midi_controller = midi_controller.clone();
+        let audit_log = audit_log.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Projekt wechseln
+        ui.on_project_switch(move |name| {
+            if let Some(ui) = ui_handle.upgrade() {
+                let mut messages = Vec::new();
+                match Project::switch_to(&name) {
+                    Ok(()) => {
+                        messages.push(format!("Projekt '{}' geladen", name));
+                        messages.extend(reload_setup(&ui, &audio_programs, &midi_config));
+                        midi_controller.lock().unwrap().take();
+                        messages.extend(connect_midi(&midi_config, &midi_learn, &midi_controller, &audio_programs, &audit_log, ui_handle.clone()));
+                    }
+                    Err(e) => messages.push(log_error(&e)),
+                }
+                set_projects(&ui);
+                ui.set_output(messages.join("\n").into());
+            }
+        });
+    }
+
+    {
+        let ui_handle = ui.as_weak();
+
+        // Callback: Projekt exportieren
+        ui.on_project_export(move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let path = PathBuf::from(ui.get_project_file().to_string());
+                let result = Project::from_current(&Project::active()).and_then(|p| p.export(&path));
+                match result {
+                    Ok(()) => ui.set_output(format!("Projekt exportiert nach {:?}", path).into()),
+                    Err(e) => ui.set_output(log_error(&e).into()),
+                }
+            }
+        });
+    }
+
+    {
+        let audio_programs = audio_programs.clone();
+        let midi_config = midi_config.clone();
+        let midi_learn = midi_learn.clone();
+        let midi_controller = midi_controller.clone();
+        let audit_log = audit_log.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Projekt importieren
+        ui.on_project_import(move |replace| {
+            if let Some(ui) = ui_handle.upgrade() {
+                let path = PathBuf::from(ui.get_project_file().to_string());
+                let mode = if replace { ImportMode::Replace } else { ImportMode::Merge };
+                let mut messages = Vec::new();
+                match Project::read(&path).and_then(|p| p.import(mode)) {
+                    Ok(conflicts) => {
+                        messages.push(format!("Projekt importiert aus {:?}", path));
+                        messages.extend(conflicts.into_iter().map(|c| format!("Konflikt: {}", c)));
+                        messages.extend(reload_setup(&ui, &audio_programs, &midi_config));
+                        midi_controller.lock().unwrap().take();
+                        messages.extend(connect_midi(&midi_config, &midi_learn, &midi_controller, &audio_programs, &audit_log, ui_handle.clone()));
+                    }
+                    Err(e) => messages.push(log_error(&e)),
+                }
+                ui.set_output(messages.join("\n").into());
+            }
+        });
+    }
+
+    // Extern geänderte Konfigurationen übernehmen
+    let pending_conflicts: PendingConflicts = Arc::new(Mutex::new(Vec::new()));
+    let _config_watcher = {
+        let audio_programs = audio_programs.clone();
+        let midi_config = midi_config.clone();
+        let midi_learn = midi_learn.clone();
+        let midi_controller = midi_controller.clone();
+        let audit_log = audit_log.clone();
+        let pending_conflicts = pending_conflicts.clone();
+        let ui_handle = ui.as_weak();
+        let watcher = ConfigWatcher::start(&ManagedAudioProgram::config_dir(), move || {
+            let audio_programs = audio_programs.clone();
+            let midi_config = midi_config.clone();
+            let midi_learn = midi_learn.clone();
+            let midi_controller = midi_controller.clone();
+            let audit_log = audit_log.clone();
+            let pending_conflicts = pending_conflicts.clone();
+            let ui_handle = ui_handle.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_handle.upgrade() {
+                    apply_disk_changes(&ui, &audio_programs, &midi_config, &midi_learn, &midi_controller, &audit_log, &pending_conflicts);
+                }
+            });
+        });
+        match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                start_errors.push(log_error(&e));
+                None
+            }
+        }
+    };
+
+    {
+        let audio_programs = audio_programs.clone();
+        let pending_conflicts = pending_conflicts.clone();
+        let ui_handle = ui.as_weak();
+
+        // Callback: Konflikt zwischen Datei und ungespeicherten Änderungen auflösen
+        ui.on_resolve_conflict(move |take_file| {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            let next = {
+                let mut conflicts = pending_conflicts.lock().unwrap();
+                if conflicts.is_empty() { None } else { Some(conflicts.remove(0)) }
+            };
+            if let Some((id, disk_config)) = next {
+                let mut programs = audio_programs.lock().unwrap();
+                if let Some(prog) = programs.iter_mut().find(|p| p.config.id == id) {
+                    if take_file {
+                        prog.saved_config = Some(disk_config.clone());
+                        prog.config = disk_config;
+                        prog.dirty = false;
+                    } else if let Err(e) = prog.save_config() {
+                        ui.set_output(e.report().into());
+                    }
+                }
+                set_program_views(&ui, programs);
+                ui.invoke_program_selectiion_changed(ui.get_program_selected());
+            }
+            show_next_conflict(&ui, &audio_programs, &pending_conflicts);
+        });
+    }
+
+    ui.set_output(start_errors.join("\n").into());
+    if let Err(e) = ui.run() {
+        eprintln!("Fehler in der Oberfläche: {}", e);
+        std::process::exit(1);
+    }
+}
+
+
+/// Schreibt den Fehler ins Log und liefert die Meldung für die Oberfläche.
+fn log_error(error: &Error) -> String {
+    let report = error.report();
+    tracing::error!("{}", report);
+    report
+}
+
+
+fn jack_unavailable_message(error: &Error) -> String {
+    format!(
+        "JACK ist nicht verfügbar, Verbindungen können nicht hergestellt werden. \
+         Läuft der JACK-Server und sind die JACK-Werkzeuge (jack_lsp, jack_connect) installiert?\n{}",
+        error.report()
+    )
+}
+
+
+/// Programme mit ungespeicherten Änderungen, deren Datei extern geändert wurde: ID und Fassung auf der Platte.
+type PendingConflicts = Arc<Mutex<Vec<(String, AudioProgramConfig)>>>;
+
+
+/// Gleicht Programme und MIDI-Zuordnungen mit dem Konfigurationsverzeichnis ab und aktualisiert die Ansichten.
+fn apply_disk_changes(
+    ui: &MainWindow,
+    audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>,
+    midi_config: &Arc<Mutex<MidiConfig>>,
+    midi_learn: &MidiLearnState,
+    midi_controller: &Arc<Mutex<Option<MidiController>>>,
+    audit_log: &Arc<Mutex<AuditLog>>,
+    pending_conflicts: &PendingConflicts,
+) {
+    let mut messages = Vec::new();
+    let result = config_watcher::sync_programs(&mut audio_programs.lock().unwrap());
+    messages.extend(result.errors.iter().map(log_error));
+    messages.extend(result.changed.iter().map(|name| format!("Konfiguration von {} wurde extern geändert und neu geladen", name)));
+    messages.extend(result.added.iter().map(|name| format!("Programm {} wurde extern hinzugefügt", name)));
+    messages.extend(result.removed.iter().map(|name| format!("Programm {} wurde extern entfernt", name)));
+
+    if !result.is_empty() {
+        let programs = audio_programs.lock().unwrap();
+        let selected = ui.get_program_selected();
+        if selected as usize >= programs.len() {
+            ui.set_program_selected(0);
+        }
+        set_program_views(ui, programs);
+        ui.invoke_program_selectiion_changed(ui.get_program_selected());
+    }
+
+    match MidiConfig::load() {
+        Ok(config) => {
+            let changed = config != *midi_config.lock().unwrap();
+            if changed {
+                *midi_config.lock().unwrap() = config;
+                messages.push("MIDI-Konfiguration wurde extern geändert und neu geladen".to_string());
+                messages.extend(check_setup(audio_programs, midi_config));
+                {
+                    let config = midi_config.lock().unwrap();
+                    ui.set_midi_input_port(config.input_port.clone().into());
+                    ui.set_midi_output_port(config.output_port.clone().into());
+                    set_midi_mappings(ui, &config, &audio_programs.lock().unwrap());
+                }
+                midi_controller.lock().unwrap().take();
+                messages.extend(connect_midi(midi_config, midi_learn, midi_controller, audio_programs, audit_log, ui.as_weak()));
+            }
+        }
+        Err(e) => messages.push(log_error(&e)),
+    }
+
+    if !result.conflicts.is_empty() {
+        let mut conflicts = pending_conflicts.lock().unwrap();
+        for (id, config) in result.conflicts {
+            conflicts.retain(|(pending, _)| pending != &id);
+            conflicts.push((id, config));
+        }
+    }
+    show_next_conflict(ui, audio_programs, pending_conflicts);
+
+    if !messages.is_empty() {
+        ui.set_output(messages.join("\n").into());
+    }
+}
+
+
+fn show_next_conflict(ui: &MainWindow, audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>, pending_conflicts: &PendingConflicts) {
+    let conflicts = pending_conflicts.lock().unwrap();
+    match conflicts.first() {
+        Some((id, disk_config)) => {
+            let name = audio_programs
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|p| &p.config.id == id)
+                .map(|p| p.config.program_name.clone())
+                .unwrap_or_else(|| disk_config.program_name.clone());
+            ui.set_conflict_message(format!(
+                "Die Konfiguration von {} wurde extern geändert, hier liegen aber ungespeicherte Änderungen vor. Welche Fassung soll gelten?",
+                name
+            ).into());
+            ui.set_conflict_visible(true);
+        }
+        None => ui.set_conflict_visible(false),
+    }
+}
+
+
+fn set_projects(ui: &MainWindow) {
+    let items: Vec<SharedString> = Project::list().into_iter().map(SharedString::from).collect();
+    ui.set_projects(ModelRc::new(VecModel::from(items)));
+    ui.set_active_project(Project::active().into());
+}
+
+
+/// Lädt Programme und MIDI-Zuordnungen nach einem Projektwechsel oder Import neu
+/// und aktualisiert die Ansichten.
+fn reload_setup(
+    ui: &MainWindow,
+    audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>,
+    midi_config: &Arc<Mutex<MidiConfig>>,
+) -> Vec<String> {
+    let (programs, load_errors) = ManagedAudioProgram::load_all();
+    let mut errors: Vec<String> = load_errors.iter().map(log_error).collect();
+    *audio_programs.lock().unwrap() = programs;
+    match MidiConfig::load() {
+        Ok(config) => *midi_config.lock().unwrap() = config,
+        Err(e) => errors.push(log_error(&e)),
+    }
+    errors.extend(check_setup(audio_programs, midi_config));
+
+    set_program_views(ui, audio_programs.lock().unwrap());
+    ui.set_program_selected(0);
+    ui.invoke_program_selectiion_changed(0);
+
+    let config = midi_config.lock().unwrap();
+    ui.set_midi_input_port(config.input_port.clone().into());
+    ui.set_midi_output_port(config.output_port.clone().into());
+    set_midi_mappings(ui, &config, &audio_programs.lock().unwrap());
+
+    errors
+}
+
+
+fn set_snapshots(ui: &MainWindow) {
+    let items: Vec<StandardListViewItem> = Snapshot::list()
+        .into_iter()
+        .map(|name| StandardListViewItem::from(SharedString::from(name)))
+        .collect();
+    ui.set_snapshots(ModelRc::new(VecModel::from(items)));
+}
+
+
+fn selected_snapshot(ui: &MainWindow) -> Option<String> {
+    let index = usize::try_from(ui.get_snapshot_selected()).ok()?;
+    ui.get_snapshots().row_data(index).map(|item| item.text.to_string())
+}
+
+
+/// Meldet das Ergebnis eines Auftrags aus dem Worker an die Oberfläche und frischt den Verlauf auf.
+fn report_job(ui_handle: slint::Weak<MainWindow>, audit_log: Arc<Mutex<AuditLog>>, output: Option<String>) {
+    let _ = slint::invoke_from_event_loop(move || {
+        let Some(ui) = ui_handle.upgrade() else { return };
+        set_audit_entries(&ui, &audit_log.lock().unwrap());
+        if let Some(output) = output {
+            ui.set_output(output.into());
+        }
+    });
+}
+
+
+/// Zeigt Zustand, Last und Verbindungen der Programme auf dem Programm-Tab an.
+fn set_program_status(ui: &MainWindow, statuses: &[ProgramStatus]) {
+    let items: Vec<ProgramStatusItem> = statuses
+        .iter()
+        .map(|status| ProgramStatusItem {
+            name: status.program.clone().into(),
+            state: match status.state {
+                ProgramState::Stopped => 0,
+                ProgramState::Starting => 1,
+                ProgramState::Running => 2,
+                ProgramState::Ready => 3,
+                ProgramState::Crashed => 4,
+            },
+            state_text: status.state.to_string().into(),
+            details: status.details().into(),
+            routes: ModelRc::new(VecModel::from(
+                status
+                    .routes
+                    .iter()
+                    .map(|route| RouteStatusItem {
+                        text: route.to_string().into(),
+                        state: match route.state {
+                            RouteState::Connected => 0,
+                            RouteState::Disconnected => 1,
+                            RouteState::Unresolvable => 2,
+                            RouteState::JackUnavailable => 3,
+                        },
+                        source: route.source.clone().into(),
+                        target: route.target.clone().into(),
+                        source_meter: -1,
+                        target_meter: -1,
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+        })
+        .collect();
+    ui.set_program_status(ModelRc::new(VecModel::from(items)));
+}
+
+
+/// Trägt bei den Verbindungen ein, welche Anzeige in `meter_levels` zu Quelle und Ziel gehört.
+fn set_route_meters(ui: &MainWindow, config: &MeterConfig) {
+    let meter_index = |port: &SharedString| {
+        config.ports.iter().position(|p| p.as_str() == port.as_str()).map_or(-1, |index| index as i32)
+    };
+    for status in ui.get_program_status().iter() {
+        for (row, mut route) in status.routes.iter().enumerate() {
+            route.source_meter = meter_index(&route.source);
+            route.target_meter = meter_index(&route.target);
+            status.routes.set_row_data(row, route);
+        }
+    }
+}
+
+
+/// Pegelanzeige: 0 entspricht `METER_DISPLAY_MIN_DB`, 1 entspricht 0 dBFS.
+fn set_meter_levels(ui: &MainWindow, levels: &[(String, Option<Level>)]) {
+    let scale = |db: f32| ((db - METER_DISPLAY_MIN_DB) / -METER_DISPLAY_MIN_DB).clamp(0.0, 1.0);
+    let items: Vec<MeterLevel> = levels
+        .iter()
+        .map(|(_, level)| match level {
+            Some(level) => MeterLevel {
+                active: true,
+                peak: scale(level.true_peak.unwrap_or(level.peak)),
+                rms: scale(level.rms),
+                clip: level.clipping(),
+                text: format!("{:.1} dB Peak, {:.1} dB RMS", level.true_peak.unwrap_or(level.peak), level.rms).into(),
+            },
+            None => MeterLevel::default(),
+        })
+        .collect();
+    // Vorhandenes Modell weiterverwenden, damit die Anzeigen nicht neu aufgebaut werden
+    let model = ui.get_meter_levels();
+    if model.row_count() == items.len() {
+        for (row, item) in items.into_iter().enumerate() {
+            model.set_row_data(row, item);
+        }
+    } else {
+        ui.set_meter_levels(ModelRc::new(VecModel::from(items)));
+    }
+}
+
+
+/// Zeigt die Stille-Erkennungen mit ihrem aktuellen Zustand an.
+fn set_silence_detectors(ui: &MainWindow, config: &MeterConfig, watch: &SilenceWatch, now: Instant) {
+    let items: Vec<SilenceItem> = config
+        .silence
+        .iter()
+        .enumerate()
+        .map(|(index, detector)| {
+            let state = watch.state(index, now);
+            SilenceItem {
+                text: detector.to_string().into(),
+                state: match state {
+                    SilenceState::Signal => 0,
+                    SilenceState::Silent(_) => 1,
+                    SilenceState::Triggered(_) => 2,
+                },
+                state_text: state.to_string().into(),
+            }
+        })
+        .collect();
+    ui.set_silence_detectors(ModelRc::new(VecModel::from(items)));
+}
+
+
+/// Zeigt den Verlauf der Xruns und die Programmereignisse an, denen sie folgten.
+fn set_health_history(ui: &MainWindow, history: &XrunHistory) {
+    let lines: Vec<StandardListViewItem> = history
+        .lines()
+        .into_iter()
+        .map(|line| StandardListViewItem::from(SharedString::from(line)))
+        .collect();
+    ui.set_health_history(ModelRc::new(VecModel::from(lines)));
+    let suspects: Vec<StandardListViewItem> = history
+        .suspects()
+        .into_iter()
+        .map(|(event, count)| StandardListViewItem::from(SharedString::from(format!("{}: {} Xruns", event, count))))
+        .collect();
+    ui.set_health_suspects(ModelRc::new(VecModel::from(suspects)));
+}
+
+
+/// Zeigt die Lautheit der Streams an, Werte auf eine Nachkommastelle.
+fn set_loudness_streams(ui: &MainWindow, readings: &[Reading]) {
+    let value = |v: Option<f64>| match v {
+        Some(v) if v.is_finite() => format!("{:.1}", v),
+        Some(_) => "-inf".to_string(),
+        None => "–".to_string(),
+    };
+    let items: Vec<LoudnessItem> = readings
+        .iter()
+        .map(|reading| LoudnessItem {
+            stream: reading.stream.clone().into(),
+            momentary: value(reading.momentary).into(),
+            short_term: value(reading.short_term).into(),
+            integrated: value(reading.integrated).into(),
+            range: value(reading.range).into(),
+            state: match reading.in_target {
+                None => 0,
+                Some(true) => 1,
+                Some(false) => 2,
+            },
+        })
+        .collect();
+    ui.set_loudness_streams(ModelRc::new(VecModel::from(items)));
+}
+
+
+/// Was die Lautheitsanzeige von einer Messung zur nächsten behält.
+#[derive(Default)]
+struct LoudnessAlerts {
+    /// Streams außerhalb des Zielbereichs, damit jeder Wechsel nur einmal geloggt wird
+    alerted: HashSet<String>,
+    /// Zuletzt angezeigte Warnung
+    shown: String,
+}
+
+/// Ergebnis einer Lautheitsmessung für die Oberfläche.
+struct LoudnessUpdate {
+    readings: Vec<Reading>,
+    show: String,
+    /// Neue Warnung, nur wenn sie sich geändert hat
+    alert: Option<String>,
+    messages: Vec<String>,
+}
+
+/// Gleicht die Lautheitsmessung mit Streams und Sendung ab und schreibt das Protokoll.
+/// Läuft im Worker, weil dabei JACK-Clients angemeldet und Dateien geschrieben werden.
+/// Ohne gemessene Streams kommt `None` zurück.
+fn measure_loudness(
+    loudness_monitor: &Mutex<Option<LoudnessMonitor>>,
+    audio_programs: &Mutex<Vec<ManagedAudioProgram>>,
+    meter_config: &Mutex<MeterConfig>,
+    show: String,
+    tick: u64,
+    alerts: &mut LoudnessAlerts,
+) -> Option<LoudnessUpdate> {
+    let mut messages = Vec::new();
+    let mut monitor = loudness_monitor.lock().unwrap();
+    // Streams und ihre Ziele ändern sich mit der Konfiguration und laufenden Programmen
+    if tick.is_multiple_of(2) {
+        let programs = audio_programs.lock().unwrap();
+        let layout = loudness::layout(&programs);
+        if monitor.as_ref().map(|m| m.layout()) != Some(layout.as_slice()) {
+            let previous = monitor.take();
+            if !layout.is_empty() {
+                let config = meter_config.lock().unwrap().loudness.clone();
+                // Unveränderte Streams messen weiter, die Sendung bleibt im selben Protokoll
+                let started = match previous {
+                    Some(previous) => previous.restart(&programs, &config),
+                    None => LoudnessMonitor::start(&programs, &config),
+                };
+                match started {
+                    Ok(started) => *monitor = Some(started),
+                    // Ohne JACK nur einmal melden, nicht bei jedem Versuch
+                    Err(e) if tick == 0 => messages.push(log_error(&e)),
+                    Err(e) => tracing::debug!("{}", e.report()),
+                }
+            }
+        }
+        if let Some(monitor) = monitor.as_ref() {
+            for e in monitor.sync(&programs) {
+                tracing::debug!("{}", e.report());
+            }
+        }
+    }
+
+    let monitor = monitor.as_mut()?;
+    monitor.poll();
+    if monitor.show() != Some(show.as_str())
+        && let Err(e) = monitor.start_show(&show)
+    {
+        messages.push(log_error(&e));
+    }
+    if let Err(e) = monitor.log() {
+        tracing::warn!("{}", e.report());
+    }
+    let readings = monitor.readings();
+    let config = meter_config.lock().unwrap().loudness.clone();
+    let mut outside = Vec::new();
+    for reading in readings.iter() {
+        if reading.in_target == Some(false) {
+            let integrated = reading.integrated.unwrap_or_default();
+            if alerts.alerted.insert(reading.stream.clone()) {
+                tracing::warn!(stream = %reading.stream, integrated, "Lautheit außerhalb des Zielbereichs");
+            }
+            outside.push(format!("{} {:.1} LUFS", reading.stream, integrated));
+        } else if alerts.alerted.remove(&reading.stream) {
+            tracing::info!(stream = %reading.stream, "Lautheit wieder im Zielbereich");
+        }
+    }
+    let alert = if outside.is_empty() {
+        String::new()
+    } else {
+        format!("Lautheit außerhalb {:.1} ± {:.1} LUFS: {}", config.target_lufs, config.tolerance_lu, outside.join(", "))
+    };
+    // Nur bei Änderungen setzen, damit eine ausgeblendete Meldung ausgeblendet bleibt
+    let alert = (alert != alerts.shown).then(|| {
+        alerts.shown = alert.clone();
+        alert
+    });
+    Some(LoudnessUpdate { readings, show, alert, messages })
+}
+
+
+/// Zeigt das Ergebnis einer Lautheitsmessung an.
+fn show_loudness(ui: &MainWindow, update: Option<LoudnessUpdate>) {
+    let Some(update) = update else {
+        ui.set_loudness_streams(ModelRc::default());
+        return;
+    };
+    if !update.messages.is_empty() {
+        ui.set_output(update.messages.join("\n").into());
+    }
+    if let Some(alert) = update.alert {
+        ui.set_loudness_alert(alert.into());
+    }
+    ui.set_loudness_show(update.show.into());
+    if ui.get_signal_visible() {
+        set_loudness_streams(ui, &update.readings);
+    }
+}
+
+
+/// Führt die Aktionen eines Detektors bei Beginn und Ende einer Stille aus.
+fn handle_silence_event(
+    ui: &MainWindow,
+    detectors: &[SilenceDetector],
+    watch: &mut SilenceWatch,
+    audit_log: &AuditLog,
+    event: SilenceEvent,
+) {
+    let (index, started) = match event {
+        SilenceEvent::Started(index) => (index, true),
+        SilenceEvent::Ended(index) => (index, false),
+    };
+    let Some(detector) = detectors.get(index) else { return };
+    if started {
+        tracing::warn!(port = %detector.port, seconds = detector.duration_s, "Stille erkannt");
+        if detector.alert {
+            ui.set_silence_alert(format!("Stille auf {} seit {:.0} s", detector.port, detector.duration_s).into());
+        }
+    } else {
+        tracing::info!(port = %detector.port, "Signal wieder vorhanden");
+        if !watch.any_triggered() {
+            ui.set_silence_alert("".into());
+        }
+    }
+    if !detector.hook.is_empty()
+        && let Err(e) = silence::run_hook(detector, if started { "start" } else { "end" })
+    {
+        ui.set_output(log_error(&e).into());
+    }
+    if detector.fallback.is_empty() {
+        return;
+    }
+    if started {
+        watch.set_resume(index, audit_log.active_scenario().map(str::to_string));
+        tracing::info!(use_case = %detector.fallback, "Umschalten auf Ausweich-Use-Case");
+        ui.invoke_start_use_case(detector.fallback.clone().into());
+    } else if detector.switch_back
+        && let Some(previous) = watch.take_resume(index)
+        && previous != detector.fallback
+    {
+        tracing::info!(use_case = %previous, "Zurückschalten nach Stille");
+        ui.invoke_start_use_case(previous.into());
+    }
+}
+
+
+/// Ziele aller Verbindungen des Programms, soweit sie gerade vorhanden sind, jedes nur einmal.
+fn tone_targets(prog: &ManagedAudioProgram, programs: &[ManagedAudioProgram]) -> error::Result<Vec<String>> {
+    let ports = read_jack_ports()?;
+    let names: Vec<String> = programs.iter().map(|p| p.jack_node_name.clone()).collect();
+    let mut targets = Vec::new();
+    for port in prog.config.jack_ports.iter() {
+        if let Ok(target) = get_jack_name(&ports, &names, prog.jack_node_name.clone(), port)
+            && ports.iter().any(|p| p.name == target)
+            && !targets.contains(&target)
+        {
+            targets.push(target);
+        }
+    }
+    Ok(targets)
+}
+
+
+/// Setzt die Anzeige nach dem Testton zurück und misst wieder nur die gespeicherte Auswahl.
+fn stop_tone(ui: &MainWindow, meter: &Mutex<Option<Meter>>, config: &MeterConfig) -> Vec<String> {
+    ui.set_tone_active(false);
+    ui.set_tone_status("".into());
+    let mut messages = vec!["Testton beendet".to_string()];
+    messages.extend(restart_meter(meter, config));
+    messages
+}
+
+
+/// Übernimmt geänderte Einstellungen der Aufnahme und richtet die Eingänge neu ein.
+/// Während einer Aufnahme bleibt alles unverändert. Liefert Meldungen für die Oberfläche.
+fn apply_recorder_config(
+    recorder: &Mutex<Option<Recorder>>,
+    config: &mut RecorderConfig,
+    changed: RecorderConfig,
+    message: String,
+) -> Vec<String> {
+    if recorder.lock().unwrap().as_ref().is_some_and(|r| r.status().recording) {
+        return vec!["Während einer Aufnahme lassen sich die Einstellungen nicht ändern".to_string()];
+    }
+    *config = changed;
+    let mut messages = vec![message];
+    if let Err(e) = config.save() {
+        messages.push(log_error(&e));
+    }
+    messages.extend(restart_recorder(recorder, config));
+    messages
+}
+
+
+/// Meldet den Aufnahme-Client neu an, damit Eingänge und Einstellungen der Auswahl entsprechen.
+fn restart_recorder(recorder: &Mutex<Option<Recorder>>, config: &RecorderConfig) -> Vec<String> {
+    let mut recorder = recorder.lock().unwrap();
+    // Der alte Client muss abgemeldet sein, bevor der neue denselben Namen bekommt
+    *recorder = None;
+    if config.ports.is_empty() {
+        return Vec::new();
+    }
+    match Recorder::start(config) {
+        Ok(started) => {
+            let messages = started.sync().iter().map(log_error).collect();
+            *recorder = Some(started);
+            messages
+        }
+        Err(e) => vec![log_error(&e)],
+    }
+}
+
+
+fn set_recorder_config(ui: &MainWindow, config: &RecorderConfig) {
+    let ports: Vec<SharedString> = config.ports.iter().map(SharedString::from).collect();
+    ui.set_recorder_ports(ModelRc::new(VecModel::from(ports)));
+    ui.set_recorder_format(match config.format {
+        AudioFormat::Wav => 0,
+        AudioFormat::Flac => 1,
+    });
+    ui.set_recorder_layout(match config.layout {
+        FileLayout::PerPort => 0,
+        FileLayout::Interleaved => 1,
+    });
+    ui.set_recorder_pre_roll(config.pre_roll_s.to_string().into());
+    ui.set_recorder_split_minutes(config.split_minutes.to_string().into());
+    ui.set_recorder_split_mb(config.split_mb.to_string().into());
+    ui.set_recorder_min_free(config.min_free_mb.to_string().into());
+    ui.set_recorder_dir(config.dir.clone().into());
+    ui.set_recorder_default_dir(RecorderConfig::default().recordings_dir().display().to_string().into());
+    ui.set_recorder_scenarios(config.scenarios.join(", ").into());
+}
+
+
+/// Beendet die laufende Messung und startet sie mit der aktuellen Auswahl neu.
+/// Liefert Meldungen für die Oberfläche.
+fn restart_meter(meter: &Mutex<Option<Meter>>, config: &MeterConfig) -> Vec<String> {
+    let mut meter = meter.lock().unwrap();
+    // Der alte Client muss abgemeldet sein, bevor der neue denselben Namen bekommt
+    *meter = None;
+    if config.measured_ports().is_empty() {
+        return Vec::new();
+    }
+    match Meter::start(config) {
+        Ok(started) => {
+            let messages = started.sync().iter().map(log_error).collect();
+            *meter = Some(started);
+            messages
+        }
+        Err(e) => vec![log_error(&e)],
+    }
+}
+
+
+/// Zeigt den Verlauf der Verbindungsänderungen an, die neuesten oben.
+fn set_audit_entries(ui: &MainWindow, audit_log: &AuditLog) {
+    let mut items = Vec::new();
+    for record in audit_log.records().iter().rev() {
+        let reverted = if audit_log.is_reverted(record.id) { " (rückgängig gemacht)" } else { "" };
+        items.push(StandardListViewItem::from(SharedString::from(format!(
+            "{} #{} {}{}", record.time, record.id, record.trigger, reverted
+        ))));
+        for change in record.changes.iter() {
+            items.push(StandardListViewItem::from(SharedString::from(format!("        {}", change))));
+        }
+    }
+    ui.set_audit_entries(ModelRc::new(VecModel::from(items)));
+}
+
+
+/// Zeigt die Log-Einträge der Sitzung nach Stufe und Programm gefiltert an, die neuesten oben.
+fn set_log_entries(ui: &MainWindow) {
+    let entries = logging::entries();
+
+    // Programme, zu denen es Einträge gibt, auch wenn sie inzwischen gelöscht wurden
+    let mut programs = vec!["Alle Programme".to_string()];
+    for entry in entries.iter() {
+        if let Some(program) = &entry.program
+            && !programs.contains(program)
+        {
+            programs.push(program.clone());
+        }
+    }
+    if ui.get_log_programs().iter().map(|s| s.to_string()).collect::<Vec<_>>() != programs {
+        ui.set_log_programs(ModelRc::new(VecModel::from(
+            programs.iter().map(|s| SharedString::from(s.clone())).collect::<Vec<SharedString>>()
+        )));
+    }
+
+    let max_level = match ui.get_log_level_selected() {
+        0 => tracing::Level::ERROR,
+        1 => tracing::Level::WARN,
+        2 => tracing::Level::INFO,
+        _ => tracing::Level::TRACE,
+    };
+    let program = match ui.get_log_program_selected() {
+        index if index > 0 => programs.get(index as usize).cloned().unwrap_or_default(),
+        _ => String::new(),
+    };
+    let items: Vec<StandardListViewItem> = entries
+        .iter()
+        .rev()
+        .filter(|entry| entry.matches(max_level, &program))
+        .map(|entry| StandardListViewItem::from(SharedString::from(entry.to_string())))
+        .collect();
+    ui.set_log_entries(ModelRc::new(VecModel::from(items)));
+}
+
+
+/// Aktualisiert Programmliste, Use-Case-Buttons und Filterauswahl.
+fn set_program_views(ui: &MainWindow, programs: MutexGuard<'_, Vec<ManagedAudioProgram>>) {
+    let items: Vec<StandardListViewItem> = programs
+        .iter()
+        .map(|p| StandardListViewItem::from(SharedString::from(p.config.program_name.clone())))
+        .collect();
+    ui.set_autio_programs(ModelRc::new(VecModel::from(items)));
+
+    let filters = get_filters(programs);
+    ui.set_use_cases(
+        ModelRc::new(VecModel::from(
+            filters.iter().map(|s| SharedString::from(s.clone())).collect::<Vec<SharedString>>()
+        ))
+    );
+    ui.set_jack_filters(ModelRc::new(
+        VecModel::from(filters.into_iter().map(SharedString::from).collect::<Vec<SharedString>>())
+    ));
+}
+
+
+/// Stellt MIDI-Verweise auf Programmnamen auf IDs um und prüft das Setup als Ganzes.
+fn check_setup(audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>, midi_config: &Arc<Mutex<MidiConfig>>) -> Vec<String> {
+    let mut errors = Vec::new();
+    let programs: Vec<AudioProgramConfig> = audio_programs.lock().unwrap().iter().map(|p| p.config.clone()).collect();
+    let mut midi = midi_config.lock().unwrap();
+    if midi.resolve_program_names(&programs)
+        && let Err(e) = midi.save()
+    {
+        errors.push(log_error(&e));
+    }
+    let issues = config_schema::validate_setup(&programs, &midi, &ManagedAudioProgram::config_dir());
+    for issue in issues.iter() {
+        tracing::warn!("{}", issue);
+    }
+    errors.extend(issues.iter().map(|issue| issue.to_string()));
+    errors
+}
+
+
+fn set_midi_mappings(ui: &MainWindow, config: &MidiConfig, programs: &[ManagedAudioProgram]) {
+    let configs: Vec<AudioProgramConfig> = programs.iter().map(|p| p.config.clone()).collect();
+    let items: Vec<StandardListViewItem> = config
+        .mapping_labels(&configs)
+        .into_iter()
+        .map(|s| StandardListViewItem::from(SharedString::from(s)))
+        .collect();
+    ui.set_midi_mappings(ModelRc::new(VecModel::from(items)));
+}
+
+
+/// Öffnet die MIDI-Verbindungen und leitet eingehende Trigger an die UI weiter.
+/// Ist der Lernmodus aktiv, wird der nächste Trigger stattdessen als Zuordnung gespeichert.
+fn connect_midi(
+    midi_config: &Arc<Mutex<MidiConfig>>,
+    midi_learn: &MidiLearnState,
+    midi_controller: &Arc<Mutex<Option<MidiController>>>,
+    audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>,
+    audit_log: &Arc<Mutex<AuditLog>>,
+    ui_handle: slint::Weak<MainWindow>,
+) -> Vec<String> {
+    let config = midi_config.lock().unwrap().clone();
+    let midi_config = midi_config.clone();
+    let midi_learn = midi_learn.clone();
+    let audio_programs = audio_programs.clone();
+    let audit_log = audit_log.clone();
+    let (controller, errors) = MidiController::connect(&config, move |trigger| {
+        let ui_handle = ui_handle.clone();
+        if let Some(action) = midi_learn.lock().unwrap().take() {
+            let mut config = midi_config.lock().unwrap();
+            config.learn(trigger, action);
+            let result = config.save();
+            let config = config.clone();
+            let audio_programs = audio_programs.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_handle.upgrade() {
+                    ui.set_midi_learning(false);
+                    set_midi_mappings(&ui, &config, &audio_programs.lock().unwrap());
+                    if let Err(e) = result {
+                        ui.set_output(log_error(&e).into());
+                    }
+                }
+            });
+            return;
+        }
+
+        let action = midi_config.lock().unwrap().action_for(&trigger).cloned();
+        let start = matches!(action, Some(MidiAction::StartProgram(_)));
+        match action {
+            Some(MidiAction::UseCase(name)) => {
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_handle.upgrade() else { return };
+                    ui.set_output(format!("Starte {}", name).into());
+                    ui.invoke_start_use_case(name.into());
+                });
+            }
+            Some(MidiAction::StartProgram(id) | MidiAction::StopProgram(id)) => {
+                // Im Thread des MIDI-Eingangs, damit die Oberfläche nicht auf den Prozess wartet
+                let index = audio_programs.lock().unwrap().iter().position(|p| p.config.id == id);
+                let result = match index {
+                    Some(index) if start => start_program(&audio_programs, &audit_log, index),
+                    Some(index) => match audio_programs.lock().unwrap().get_mut(index) {
+                        Some(prog) => prog.stop().context(format!("Fehler bei Programm {}", prog.config.program_name)),
+                        None => Err(Error::ProgramNotFound(id)),
+                    },
+                    None => Err(Error::ProgramNotFound(id)),
+                };
+                let output = result.err().map(|e| e.report());
+                if let Some(output) = output {
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_handle.upgrade() {
+                            ui.set_output(output.into());
+                        }
+                    });
+                }
+            }
+            None => {}
+        }
+    });
+    *midi_controller.lock().unwrap() = Some(controller);
+    errors.iter().map(log_error).collect()
+}
+
+
+fn reset_jack_view(programs: MutexGuard<'_, Vec<ManagedAudioProgram>>, ui_handle: slint::Weak<MainWindow>) {
+    let idx = ui_handle.upgrade().map(|ui| ui.get_program_selected()).unwrap_or(0) as usize;
+    if let Some(prog) = programs.get(idx) {
+        if let Some(ui) = ui_handle.upgrade() {
+            let selected_index = ui.get_Jack_connection_selected() as usize;
+            if selected_index < prog.config.jack_ports.len() {
+                let port = &prog.config.jack_ports[selected_index];
+                ui.set_jack_filter(port.filter.clone().into());
+                ui.set_jack_source(port.source_name.clone().into());
+                ui.set_jack_target(port.target_name.clone().into());
+                ui.set_jack_search(port.target_search_name.clone().into());
+                ui.set_jack_optional(port.optional);
+                ui.set_jack_loudness(port.loudness);
+                ui.set_jack_switching(match port.switching {
+                    Switching::MakeBeforeBreak => 0,
+                    Switching::BreakBeforeMake => 1,
+                });
+                ui.set_jack_overlap(if port.overlap_ms > 0 { port.overlap_ms.to_string().into() } else { "".into() });
+            }
+        }
+    }
+}
+
+
+fn get_filters(programs: MutexGuard<'_, Vec<ManagedAudioProgram>>) -> Vec<String> {
+    let mut filters = {
+        let programs = programs;
+        let mut all_filters = Vec::new();
+        for prog in programs.iter() {
+            for port in prog.config.jack_ports.iter() {
+                for s in port.filter.split_whitespace() {
+                    let s = s.to_string();
+                    if !s.is_empty() {
+                        all_filters.push(s);
+                    }
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        all_filters
+            .into_iter()
+            .filter(|s| seen.insert(s.clone()))
+            .collect::<Vec<String>>()
+    };
+    if filters.is_empty() {
+        // Wenn keine Filter vorhanden sind, füge einen Standardfilter hinzu
+        filters.push("start".to_string());
+    }
+    filters
+}
+
+
+pub fn disconnect_unwanted_jack_ports(
+    apps: &[ManagedAudioProgram],
+    use_case: &str,
+    record: &mut audit::Record,
+) -> error::Result<()> {
+    let ports = read_jack_ports()?;
+    let connections = read_jack_connections()?;
+
+    let mut errors = Vec::new();
+    for (source, target) in unwanted_connections(apps, use_case, &ports, &connections, &mut errors) {
+        if let Err(e) = disconnect_jack_ports(apps, &source, &target, record) {
+            errors.push(e);
+        }
+    }
+
+    Error::from_list(errors)
+}
+
+
+/// Bestehende Verbindungen zwischen bekannten Ports, die im Use-Case nicht vorgesehen sind.
+/// Fehler beim Auflösen der gewünschten Ziele landen in `errors`.
+fn unwanted_connections(
+    apps: &[ManagedAudioProgram],
+    use_case: &str,
+    ports: &[managed_audio_program::JackPortInfo],
+    connections: &[(String, String)],
+    errors: &mut Vec<Error>,
+) -> Vec<(String, String)> {
+    let wanted_connections = wanted_connections(apps, use_case, ports, errors);
+    connections
+        .iter()
+        .filter(|(source, target)| {
+            ports.iter().any(|p| &p.name == source)
+                && ports.iter().any(|p| &p.name == target)
+                && !meter::is_internal_port(target)
+                && !wanted_connections.contains(&(source.clone(), target.clone()))
+        })
+        .cloned()
+        .collect()
+}
+
+
+/// Verbindungen, die der Use-Case samt der immer gültigen Regeln vorsieht, soweit die Ziele laufen.
+fn wanted_connections(
+    apps: &[ManagedAudioProgram],
+    use_case: &str,
+    ports: &[managed_audio_program::JackPortInfo],
+    errors: &mut Vec<Error>,
+) -> HashSet<(String, String)> {
+    let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
+
+    let mut wanted_connections: HashSet<(String, String)> = HashSet::new();
+    for app in apps.iter() {
+        for port in app.config.jack_ports.iter() {
+            if port.applies_to(use_case) {
+                match get_jack_name(ports, &apps_jack_node_names, app.jack_node_name.clone(), port) {
+                    Ok(target) => {
+                        wanted_connections.insert((port.source_name.clone(), target));
+                    }
+                    // Ziel läuft nicht, also gibt es auch keine gewünschte Verbindung dorthin
+                    Err(Error::Resolution { .. }) => {}
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+    }
+    wanted_connections
+}
+
+
+/// Zustand von Patchbay und Matrix. Eingelesen wird im Worker, angezeigt im UI-Thread.
+#[derive(Clone, Default)]
+struct GraphViews {
+    patch: Arc<Mutex<patchbay::Graph>>,
+    matrix: Arc<Mutex<matrix::Matrix>>,
+    /// Eine Abfrage steht im Worker noch aus
+    patch_pending: Arc<AtomicBool>,
+    matrix_pending: Arc<AtomicBool>,
+}
+
+
+/// Liest den JACK-Graphen im Worker neu ein und zeigt ihn in der Patchbay an. Hervorgehoben
+/// werden verwaltete Programme und die Verbindungen des aktiven Use-Cases.
+fn refresh_patchbay(
+    ui: &MainWindow,
+    worker: &Worker,
+    audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>,
+    audit_log: &AuditLog,
+    views: &GraphViews,
+) {
+    let use_case = audit_log.active_scenario().unwrap_or_default().to_string();
+    ui.set_patch_scenario(use_case.clone().into());
+    let audio_programs = audio_programs.clone();
+    let views = views.clone();
+    let ui_handle = ui.as_weak();
+    views.patch_pending.store(true, Ordering::Relaxed);
+    worker.run_quiet("Patchbay aktualisieren", move |_| {
+        let graph = read_patch_graph(&audio_programs, &use_case);
+        views.patch_pending.store(false, Ordering::Relaxed);
+        let _ = slint::invoke_from_event_loop(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            show_patchbay(&ui, graph, &mut views.patch.lock().unwrap());
+        });
+    });
+}
+
+
+/// Liest JACK-Ports und -Verbindungen und ordnet sie für die Patchbay an. Ist JACK nicht
+/// erreichbar, kommt der Hinweis für die Oberfläche zurück.
+fn read_patch_graph(audio_programs: &Mutex<Vec<ManagedAudioProgram>>, use_case: &str) -> Result<patchbay::Graph, String> {
+    let (connections, ports) = read_jack_ports()
+        .and_then(|ports| Ok((read_jack_connections()?, ports)))
+        .map_err(|e| jack_unavailable_message(&e))?;
+    let apps = audio_programs.lock().unwrap();
+    // Nicht auflösbare Regeln zeigt die Patchbay einfach nicht hervorgehoben
+    let scenario = wanted_connections(&apps, use_case, &ports, &mut Vec::new());
+    Ok(patchbay::layout(&ports, &connections, |client| owner_of(&apps, client).is_some(), &scenario))
+}
+
+
+/// Übernimmt einen eingelesenen Graphen in die Patchbay. Während ein Kabel gezogen wird,
+/// bleibt die Anzeige stehen, sonst verschöben sich die Pins unter der Maus.
+fn show_patchbay(ui: &MainWindow, result: Result<patchbay::Graph, String>, graph: &mut patchbay::Graph) {
+    if ui.get_patch_drag_from() >= 0 {
+        return;
+    }
+    *graph = match result {
+        Ok(graph) => {
+            ui.set_jack_unavailable("".into());
+            graph
+        }
+        Err(message) => {
+            ui.set_jack_unavailable(message.into());
+            patchbay::Graph::default()
+        }
+    };
+
+    ui.set_patch_width(graph.width);
+    ui.set_patch_height(graph.height);
+    ui.set_patch_clients(ModelRc::new(VecModel::from(
+        graph
+            .clients
+            .iter()
+            .map(|c| PatchClient {
+                name: c.name.clone().into(),
+                x: c.x,
+                y: c.y,
+                width: c.width,
+                height: c.height,
+                managed: c.managed,
+            })
+            .collect::<Vec<_>>(),
+    )));
+    ui.set_patch_pins(ModelRc::new(VecModel::from(
+        graph
+            .pins
+            .iter()
+            .map(|p| PatchPin { label: p.label.clone().into(), x: p.x, y: p.y, output: p.output, connected: p.connected })
+            .collect::<Vec<_>>(),
+    )));
+    ui.set_patch_lines(ModelRc::new(VecModel::from(
+        graph
+            .lines
+            .iter()
+            .map(|l| PatchLine { x1: l.x1, y1: l.y1, x2: l.x2, y2: l.y2, scenario: l.scenario })
+            .collect::<Vec<_>>(),
+    )));
+}
+
+
+/// Eintrag der Use-Case-Auswahl der Matrix für Regeln ohne Filter.
+const MATRIX_ALWAYS: &str = "Immer (ohne Filter)";
+
+/// Untergrenze der Pegelanzeige in dBFS.
+const METER_DISPLAY_MIN_DB: f32 = -60.0;
+
+
+/// In der Matrix gewählter Use-Case, leer für Regeln ohne Filter.
+fn selected_matrix_use_case(ui: &MainWindow) -> String {
+    match ui.get_matrix_use_case_selected() {
+        index if index > 0 => ui.get_matrix_use_cases().row_data(index as usize).map(|s| s.to_string()).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+
+/// Baut die Verbindungsmatrix für den gewählten Use-Case im Worker neu auf. Ist JACK nicht
+/// erreichbar, erscheinen alle Regeln als nicht auflösbar.
+fn refresh_matrix(ui: &MainWindow, worker: &Worker, audio_programs: &Arc<Mutex<Vec<ManagedAudioProgram>>>, views: &GraphViews) {
+    // Auswahl über den Namen halten, falls sich die Liste der Use-Cases geändert hat
+    let selected = selected_matrix_use_case(ui);
+    let mut use_cases = vec![MATRIX_ALWAYS.to_string()];
+    use_cases.extend(ui.get_use_cases().iter().map(|s| s.to_string()));
+    if ui.get_matrix_use_cases().iter().map(|s| s.to_string()).collect::<Vec<_>>() != use_cases {
+        ui.set_matrix_use_cases(ModelRc::new(VecModel::from(
+            use_cases.iter().map(|s| SharedString::from(s.clone())).collect::<Vec<SharedString>>()
+        )));
+        let index = use_cases.iter().skip(1).position(|u| *u == selected).map_or(0, |i| i + 1);
+        ui.set_matrix_use_case_selected(index as i32);
+    }
+    let use_case = selected_matrix_use_case(ui);
+
+    let audio_programs = audio_programs.clone();
+    let views = views.clone();
+    let ui_handle = ui.as_weak();
+    views.matrix_pending.store(true, Ordering::Relaxed);
+    worker.run_quiet("Matrix aktualisieren", move |_| {
+        let (ports, connections, unavailable) = match read_jack_ports().and_then(|ports| Ok((ports, read_jack_connections()?))) {
+            Ok((ports, connections)) => (ports, connections, String::new()),
+            Err(e) => (Vec::new(), Vec::new(), jack_unavailable_message(&e)),
+        };
+        let matrix = {
+            let apps = audio_programs.lock().unwrap();
+            let rules = matrix_rules(&apps, &use_case, &ports);
+            matrix::Matrix::build(&ports, &connections, rules, |port| owner_of(&apps, port))
+        };
+        views.matrix_pending.store(false, Ordering::Relaxed);
+        let _ = slint::invoke_from_event_loop(move || {
+            let Some(ui) = ui_handle.upgrade() else { return };
+            // Inzwischen wurde ein anderer Use-Case gewählt, dessen Abfrage folgt
+            if selected_matrix_use_case(&ui) != use_case {
+                return;
+            }
+            ui.set_jack_unavailable(unavailable.into());
+            let mut current = views.matrix.lock().unwrap();
+            *current = matrix;
+            show_matrix(&ui, &current);
+        });
+    });
+}
+
+
+fn show_matrix(ui: &MainWindow, matrix: &matrix::Matrix) {
+    let header = |h: &matrix::Header| MatrixHeader { label: h.port.clone().into(), group_start: h.group_start, missing: h.missing };
+    ui.set_matrix_sources(ModelRc::new(VecModel::from(matrix.sources.iter().map(header).collect::<Vec<_>>())));
+    ui.set_matrix_targets(ModelRc::new(VecModel::from(matrix.targets.iter().map(header).collect::<Vec<_>>())));
+    ui.set_matrix_cells(ModelRc::new(VecModel::from(
+        matrix
+            .cells
+            .iter()
+            .map(|c| MatrixCell {
+                row: c.row as i32,
+                column: c.column as i32,
+                state: match c.state {
+                    matrix::CellState::Empty => 0,
+                    matrix::CellState::Live => 1,
+                    matrix::CellState::Pending => 2,
+                    matrix::CellState::Failing => 3,
+                    matrix::CellState::Unmanaged => 4,
+                },
+                always: c.rules.iter().any(|i| matrix.rules[*i].always),
+            })
+            .collect::<Vec<_>>(),
+    )));
+}
+
+
+/// Alle Regeln, die für `use_case` gelten, mit aufgelösten Ports. Ein leerer `use_case`
+/// steht nur für die Regeln ohne Filter.
+fn matrix_rules(
+    apps: &[ManagedAudioProgram],
+    use_case: &str,
+    ports: &[managed_audio_program::JackPortInfo],
+) -> Vec<matrix::Rule> {
+    let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
+    let mut rules = Vec::new();
+    for (program, app) in apps.iter().enumerate() {
+        for (index, port) in app.config.jack_ports.iter().enumerate() {
+            if !port.applies_to(use_case) {
+                continue;
+            }
+            let (target, resolved) = match resolve_connection(ports, &apps_jack_node_names, app.jack_node_name.clone(), port) {
+                Ok((_, target)) => (target, true),
+                Err(_) => (
+                    get_jack_name(ports, &apps_jack_node_names, app.jack_node_name.clone(), port)
+                        .unwrap_or_else(|_| port.target_name.clone()),
+                    false,
+                ),
+            };
+            rules.push(matrix::Rule { program, port: index, source: port.source_name.clone(), target, resolved, always: port.filter.is_empty() });
+        }
+    }
+    rules
+}
+
+
+/// Schaltet die Regel einer Matrixzelle um: Gibt es Regeln, werden sie für `use_case` entfernt,
+/// sonst wird beim Programm des Ziels eine neue angelegt.
+fn toggle_matrix_rule(
+    apps: &mut [ManagedAudioProgram],
+    matrix: &matrix::Matrix,
+    row: usize,
+    column: usize,
+    use_case: &str,
+) -> error::Result<String> {
+    let (Some(source), Some(target)) = (matrix.sources.get(row), matrix.targets.get(column)) else {
+        return Ok(String::new());
+    };
+    let mut changed = vec![false; apps.len()];
+    let message = match matrix.cell(row, column).filter(|c| !c.rules.is_empty()) {
+        Some(cell) => {
+            // Regeln ohne Filter gelten für alle Use-Cases und lassen sich nur dort entfernen
+            let mut removable: Vec<&matrix::Rule> = cell
+                .rules
+                .iter()
+                .map(|i| &matrix.rules[*i])
+                .filter(|r| use_case.is_empty() || !r.always)
+                .collect();
+            if removable.is_empty() {
+                return Ok(format!(
+                    "{} -> {} gilt für alle Use-Cases und kann nur unter \"{}\" entfernt werden",
+                    source.port, target.port, MATRIX_ALWAYS
+                ));
+            }
+            // Von hinten entfernen, damit die Indizes der übrigen Regeln gültig bleiben
+            removable.sort_by_key(|r| std::cmp::Reverse((r.program, r.port)));
+            for rule in removable {
+                changed[rule.program] |= apps[rule.program].remove_rule_use_case(rule.port, use_case);
+            }
+            format!("Regel {} -> {} entfernt", source.port, target.port)
+        }
+        None => {
+            let Some(owner) = target.owner else {
+                return Err(Error::ProgramNotFound(target.port.clone()));
+            };
+            changed[owner] = apps[owner].add_rule(use_case, &source.port, &target.port);
+            format!("Regel {} -> {} bei {} angelegt", source.port, target.port, apps[owner].config.program_name)
+        }
+    };
+
+    let mut errors = Vec::new();
+    for (prog, changed) in apps.iter_mut().zip(changed) {
+        if changed && let Err(e) = prog.save_config() {
+            errors.push(e.context(format!("Fehler beim Speichern der Regeln für {}", prog.config.program_name)));
+        }
+    }
+    Error::from_list(errors)?;
+    Ok(message)
+}
+
+
+/// Verbindet oder trennt zwei Ports aus der Patchbay. Mit `save_rule` wird die Änderung als
+/// Regel für `use_case` bei dem Programm gespeichert, dem das Ziel gehört.
+fn toggle_patch_connection(
+    apps: &mut [ManagedAudioProgram],
+    source: &str,
+    target: &str,
+    use_case: &str,
+    save_rule: bool,
+    record: &mut audit::Record,
+) -> error::Result<String> {
+    let connected = read_jack_connections()?.iter().any(|(s, t)| s == source && t == target);
+    let mut message = if connected {
+        disconnect_jack_ports(apps, source, target, record)?;
+        format!("{} -> {} getrennt", source, target)
+    } else {
+        run_jack_tool("jack_connect", &[source, target])?;
+        let program = owner_of(apps, target).map(|i| apps[i].config.program_name.as_str());
+        tracing::info!(program, source, target, "JACK-Verbindung hergestellt");
+        record.connected(source, target, program);
+        format!("{} -> {} verbunden", source, target)
+    };
+    if !save_rule {
+        return Ok(message);
+    }
+    let Some(index) = owner_of(apps, target) else {
+        return Ok(message + ", das Ziel gehört zu keinem Programm, keine Regel gespeichert");
+    };
+    let prog = &mut apps[index];
+    let changed = if connected {
+        prog.remove_rule(use_case, source, target)
+    } else {
+        prog.add_rule(use_case, source, target)
+    };
+    if changed {
+        prog.save_config()
+            .context(format!("Fehler beim Speichern der Regel für {}", prog.config.program_name))?;
+        message += &format!(
+            ", Regel bei {} {}",
+            prog.config.program_name,
+            if connected { "entfernt" } else { "gespeichert" }
+        );
+    }
+    Ok(message)
+}
+
+
+fn disconnect_jack_ports(apps: &[ManagedAudioProgram], source: &str, target: &str, record: &mut audit::Record) -> error::Result<()> {
+    disconnect_ports(source, target, target_program(apps, target).as_deref(), record)
+}
+
+/// Programm, zu dem der Ziel-Port gehört.
+fn target_program(apps: &[ManagedAudioProgram], target: &str) -> Option<String> {
+    apps.iter()
+        .find(|a| !a.jack_node_name.is_empty() && target.starts_with(&format!("{}:", a.jack_node_name)))
+        .map(|a| a.config.program_name.clone())
+}
+
+fn disconnect_ports(source: &str, target: &str, program: Option<&str>, record: &mut audit::Record) -> error::Result<()> {
+    run_jack_tool("jack_disconnect", &[source, target])?;
+    tracing::info!(program, source, target, "JACK-Verbindung getrennt");
+    record.disconnected(source, target, program);
+    Ok(())
+}
+
+       
+pub fn connect_jack_ports(
+    apps: &mut Vec<ManagedAudioProgram>,
+    app_index: i32,
+    jack_index: i32,
+    record: &mut audit::Record,
+) -> error::Result<()> {
+    let ports = read_jack_ports()?;
+    let Some(app) = usize::try_from(app_index).ok().and_then(|i| apps.get(i)) else {
+        return Err(Error::ProgramNotFound(app_index.to_string()));
+    };
+    let app_index = app_index as usize;
+    let app_jack_node_name = app.jack_node_name.clone();
+    let Some(port) = app.config.jack_ports.get(jack_index as usize).cloned() else {
+        return Err(Error::ConnectionNotFound {
+            program: app.config.program_name.clone(),
+            index: jack_index,
+        });
+    };
+    let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
+    let (source, target) = resolve_connection(&ports, &apps_jack_node_names, app_jack_node_name, &port)?;
+    run_jack_tool("jack_connect", &[&source, &target])?;
+    tracing::info!(program = %app.config.program_name, source = %source, target = %target, "JACK-Verbindung hergestellt");
+    record.connected(&source, &target, Some(&app.config.program_name));
+    let app = &mut apps[app_index];
+    app.jack_node_name = target.split(':').next().unwrap_or(&target).to_string();
+    app.save_jack_target()
+}
+
+
+/// Startet ein einzelnes Programm. Ist ein Use-Case aktiv, werden danach die Verbindungen,
+/// die er für das Programm vorsieht, wiederhergestellt und als `Trigger::Reconnect` aufgezeichnet.
+fn start_program(audio_programs: &Mutex<Vec<ManagedAudioProgram>>, audit_log: &Mutex<AuditLog>, index: usize) -> error::Result<()> {
+    let Some((id, name)) = audio_programs.lock().unwrap().get(index).map(|p| (p.config.id.clone(), p.config.program_name.clone())) else {
+        return Err(Error::ProgramNotFound(index.to_string()));
+    };
+    launch_program(audio_programs, index).context(format!("Fehler beim Starten des Programms {}", name))?;
+    let Some(use_case) = audit_log.lock().unwrap().active_scenario().map(str::to_string) else {
+        return Ok(());
+    };
+    let mut record = audit::Record::begin(Trigger::Reconnect(name.clone()));
+    let mut errors = Vec::new();
+    let mut programs = audio_programs.lock().unwrap();
+    // Während des Starts kann sich die Liste geändert haben
+    let Some(index) = programs.iter().position(|p| p.config.id == id) else {
+        return Err(Error::ProgramNotFound(name));
+    };
+    if let Err(e) = reconnect_program(&mut programs, index, &use_case, &mut record) {
+        errors.push(e.context(format!("{} nicht wieder mit Use-Case '{}' verbunden", name, use_case)));
+    }
+    drop(programs);
+    if let Err(e) = audit_log.lock().unwrap().commit(record) {
+        errors.push(e);
+    }
+    Error::from_list(errors)
+}
+
+/// Startet das Programm `index`. Die Programmliste ist nur vorher und nachher gesperrt, nicht
+/// während der Prozess hochfährt.
+fn launch_program(audio_programs: &Mutex<Vec<ManagedAudioProgram>>, index: usize) -> error::Result<()> {
+    let Some((launch, id)) = audio_programs.lock().unwrap().get(index).map(|p| (p.launch(), p.config.id.clone())) else {
+        return Err(Error::ProgramNotFound(index.to_string()));
+    };
+    let (child, result) = launch.run();
+    if let Some(prog) = audio_programs.lock().unwrap().iter_mut().find(|p| p.config.id == id) {
+        prog.started(child, &result);
+    }
+    result
+}
+
+/// Stellt die fehlenden Verbindungen des Use-Cases her, an denen das Programm beteiligt ist:
+/// seine eigenen Regeln und die anderer Programme mit einem seiner Ports als Quelle.
+/// Getrennt wird nichts; optionale Verbindungen dürfen fehlen.
+fn reconnect_program(apps: &mut [ManagedAudioProgram], index: usize, use_case: &str, record: &mut audit::Record) -> error::Result<()> {
+    let ports = read_jack_ports()?;
+    let connections = read_jack_connections()?;
+    let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
+    let own_prefix = format!("{}:", apps[index].jack_node_name);
+    let mut errors = Vec::new();
+    let mut targets = Vec::new();
+    for (app_index, app) in apps.iter().enumerate() {
+        for port in app.config.jack_ports.iter().filter(|p| p.applies_to(use_case)) {
+            let involved = app_index == index || (own_prefix.len() > 1 && port.source_name.starts_with(&own_prefix));
+            if !involved {
+                continue;
+            }
+            let (source, target) = match resolve_connection(&ports, &apps_jack_node_names, app.jack_node_name.clone(), port) {
+                Ok(connection) => connection,
+                Err(_) if port.optional => continue,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            if !connections.contains(&(source.clone(), target.clone())) {
+                match run_jack_tool("jack_connect", &[&source, &target]) {
+                    Ok(_) => {
+                        tracing::info!(program = %app.config.program_name, source = %source, target = %target, "JACK-Verbindung wiederhergestellt");
+                        record.connected(&source, &target, Some(&app.config.program_name));
+                    }
+                    Err(_) if port.optional => {}
+                    Err(e) => errors.push(e),
+                }
+            }
+            targets.push((app_index, target));
+        }
+    }
+    for (app_index, target) in targets {
+        let app = &mut apps[app_index];
+        app.jack_node_name = target.split(':').next().unwrap_or(&target).to_string();
+        if let Err(e) = app.save_jack_target() {
+            errors.push(e);
+        }
+    }
+    Error::from_list(errors)
+}
+
+
+/// Eine Verbindung, die ein Use-Case herstellen soll, mit bereits aufgelösten Portnamen.
+struct PlannedConnection {
+    app_index: usize,
+    source: String,
+    target: String,
+    optional: bool,
+    switching: Switching,
+    overlap: Duration,
+}
+
+/// Zweiter Teil eines Use-Case-Wechsels: die alten Verbindungen, die nach der Überlappung
+/// getrennt werden. Braucht die Programmliste nicht mehr, damit deren Sperre während der
+/// Wartezeit frei ist.
+#[must_use]
+struct Switchover {
+    use_case: String,
+    overlap: Duration,
+    /// Quelle, Ziel und Programm des Ziels
+    unwanted: Vec<(String, String, Option<String>)>,
+    /// Fehler aus dem ersten Teil, die den Wechsel nicht verhindert haben
+    errors: Vec<Error>,
+}
+
+impl Switchover {
+    fn finish(self, record: &mut audit::Record) -> error::Result<()> {
+        let mut errors = self.errors;
+        if !self.overlap.is_zero() && !self.unwanted.is_empty() {
+            tracing::debug!(use_case = %self.use_case, overlap_ms = self.overlap.as_millis() as u64, "Überlappung vor dem Trennen");
+            std::thread::sleep(self.overlap);
+        }
+        for (source, target, program) in self.unwanted {
+            if let Err(e) = disconnect_ports(&source, &target, program.as_deref(), record) {
+                errors.push(e);
+            }
+        }
+        Error::from_list(errors)
+    }
+}
+
+/// Warum ein Use-Case nicht vollständig angewendet wurde.
+enum UseCaseError {
+    /// Vor der ersten Änderung gescheitert, oder Fehler, die den Wechsel nicht verhindert haben
+    NotApplied(Error),
+    /// Eine erforderliche Verbindung ist fehlgeschlagen, `before` muss wiederhergestellt werden
+    Failed { error: Error, before: Vec<(String, String)> },
+}
+
+impl From<Error> for UseCaseError {
+    fn from(error: Error) -> Self {
+        UseCaseError::NotApplied(error)
+    }
+}
+
+/// Wendet einen Use-Case als Transaktion an: Zuerst werden alle Ziele aufgelöst; fehlt ein
+/// erforderliches, wird nichts verändert. Schlägt danach eine erforderliche Verbindung fehl,
+/// liefert es `UseCaseError::Failed` mit dem Zustand vor dem Use-Case; den stellt der Aufrufer
+/// in einem eigenen Verlaufseintrag wieder her. Optionale Verbindungen dürfen fehlschlagen.
+///
+/// Umgeschaltet wird Make-before-break: erst alle neuen Verbindungen, dann nach der längsten
+/// Überlappung das Trennen der alten. Bei Break-before-make werden die alten Quellen des Ziels
+/// vor der neuen Verbindung getrennt. Das Trennen nach der Überlappung übernimmt
+/// `Switchover::finish`, das ohne die Programmliste auskommt.
+fn apply_use_case(
+    apps: &mut [ManagedAudioProgram],
+    use_case: &str,
+    record: &mut audit::Record,
+    progress: &Progress,
+) -> Result<Switchover, UseCaseError> {
+    let ports = read_jack_ports()?;
+    let connections = read_jack_connections()?;
+    // Programme sind bereits gestartet, der Zustand direkt vor dem Umschalten zählt
+    record.before = Some(connections.clone());
+
+    let apps_jack_node_names: Vec<String> = apps.iter().map(|a| a.jack_node_name.clone()).collect();
+    let mut planned = Vec::new();
+    let mut missing = Vec::new();
+    for (app_index, app) in apps.iter().enumerate() {
+        for port in app.config.jack_ports.iter().filter(|p| p.applies_to(use_case)) {
+            match resolve_connection(&ports, &apps_jack_node_names, app.jack_node_name.clone(), port) {
+                Ok((source, target)) => planned.push(PlannedConnection {
+                    app_index,
+                    source,
+                    target,
+                    optional: port.optional,
+                    switching: port.switching,
+                    overlap: Duration::from_millis(port.overlap_ms),
+                }),
+                Err(e) if port.optional => {
+                    tracing::warn!(program = %app.config.program_name, "Optionale Verbindung übersprungen: {}", e.report());
+                }
+                Err(e) => missing.push(e.context(format!("Programm {}", app.config.program_name))),
+            }
+        }
+    }
+    if !missing.is_empty() {
+        Error::from_list(missing).context(format!("Use-Case '{}' nicht angewendet, es wurde nichts verändert", use_case))?;
+    }
+    progress.check().context(format!("Use-Case '{}' nicht angewendet, es wurde nichts verändert", use_case))?;
+
+    let mut errors = Vec::new();
+    let planned_pairs: HashSet<(String, String)> = planned.iter().map(|p| (p.source.clone(), p.target.clone())).collect();
+    let mut unwanted: Vec<(String, String)> = unwanted_connections(apps, use_case, &ports, &connections, &mut errors)
+        .into_iter()
+        .filter(|connection| !planned_pairs.contains(connection))
+        .collect();
+
+    let mut failed = None;
+    let mut overlap = Duration::ZERO;
+    for plan in planned {
+        // Ein Abbruch zählt wie eine fehlgeschlagene Verbindung
+        if let Err(e) = progress.check() {
+            failed = Some(e);
+            break;
+        }
+        let program = apps[plan.app_index].config.program_name.clone();
+        if !connections.contains(&(plan.source.clone(), plan.target.clone())) {
+            let mut result = Ok(());
+            if plan.switching == Switching::BreakBeforeMake {
+                // Alte Quellen dieses Ziels vor der neuen Verbindung trennen
+                let (old, rest): (Vec<_>, Vec<_>) = unwanted.into_iter().partition(|(_, target)| target == &plan.target);
+                unwanted = rest;
+                for (source, target) in old {
+                    if result.is_ok() {
+                        result = disconnect_jack_ports(apps, &source, &target, record);
+                    }
+                }
+            }
+            if result.is_ok() {
+                progress.report(format!("Verbinde {} -> {}", plan.source, plan.target));
+                result = run_jack_tool("jack_connect", &[&plan.source, &plan.target]).map(|_| ());
+            }
+            match result {
+                Ok(()) => {
+                    tracing::info!(program = %program, source = %plan.source, target = %plan.target, "JACK-Verbindung hergestellt");
+                    record.connected(&plan.source, &plan.target, Some(&program));
+                    if plan.switching == Switching::MakeBeforeBreak {
+                        overlap = overlap.max(plan.overlap);
+                    }
+                }
+                Err(e) if plan.optional => {
+                    tracing::warn!(program = %program, "Optionale Verbindung fehlgeschlagen: {}", e.report());
+                    errors.push(e);
+                    continue;
+                }
+                Err(e) => {
+                    failed = Some(e.context(format!("Programm {}", program)));
+                    break;
+                }
+            }
+        }
+        let app = &mut apps[plan.app_index];
+        app.jack_node_name = plan.target.split(':').next().unwrap_or(&plan.target).to_string();
+        if let Err(e) = app.save_jack_target() {
+            errors.push(e);
+        }
+    }
+
+    if let Some(error) = failed {
+        return Err(UseCaseError::Failed { error, before: connections });
+    }
+
+    let unwanted = unwanted
+        .into_iter()
+        .map(|(source, target)| {
+            let program = target_program(apps, &target);
+            (source, target, program)
+        })
+        .collect();
+    Ok(Switchover { use_case: use_case.to_string(), overlap, unwanted, errors })
 }
@@ -1,20 +1,61 @@
-use std::{fs::{self, File}, io::Write, path::PathBuf, process::{Child, Command}};
+use std::{fs, io::Write, path::{Path, PathBuf}, process::{Child, Command}};
 
 use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+use crate::{atomic_file, config_schema, paths};
+use crate::error::{Error, Result, ResultExt};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct JackPort {
     pub filter: String,
     pub source_name: String,
     pub target_search_name: String,
     pub target_name: String,
+    /// Optionale Verbindungen dürfen beim Anwenden eines Use-Cases fehlschlagen,
+    /// ohne dass der vorherige Zustand wiederhergestellt wird.
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub switching: Switching,
+    /// Bei Make-before-break: so lange laufen alte und neue Quelle gemeinsam, bevor die alte getrennt wird.
+    #[serde(default)]
+    pub overlap_ms: u64,
+    /// Das Ziel ist ein ausgehender Stream, dessen Lautheit gemessen wird.
+    #[serde(default)]
+    pub loudness: bool,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+impl JackPort {
+    /// Ob die Regel zu `use_case` gehört. Der Filter ist eine Liste von Use-Cases, durch
+    /// Leerzeichen getrennt; ohne Filter gilt die Regel immer.
+    pub fn applies_to(&self, use_case: &str) -> bool {
+        self.filter.is_empty() || self.filter.split_whitespace().any(|f| f == use_case)
+    }
+}
+
+/// Reihenfolge beim Umschalten eines Ziels auf eine neue Quelle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Switching {
+    /// Neue Verbindung zuerst herstellen, alte danach trennen, damit es keinen Aussetzer gibt.
+    #[default]
+    MakeBeforeBreak,
+    /// Alte Verbindungen zuerst trennen, damit das Ziel nie zwei Quellen gleichzeitig bekommt.
+    BreakBeforeMake,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct AudioProgramConfig {
+    #[serde(default)]
+    pub version: u32,
+    /// Feste ID, nach der das Konfigurationsverzeichnis benannt ist. Ändert sich beim Umbenennen nicht.
+    #[serde(default)]
+    pub id: String,
     pub program_name: String,
     pub command_name: String,
+    #[serde(default)]
     pub start_params: Vec<String>,
+    #[serde(default)]
     pub jack_ports: Vec<JackPort>,
 }
 
@@ -23,218 +64,447 @@ pub struct ManagedAudioProgram {
     pub process: Option<Child>,
     pub pid_file: PathBuf,
     pub jack_node_name: String,
+    /// In der Oberfläche geändert, aber noch nicht gespeichert.
+    pub dirty: bool,
+    /// Fassung, die zuletzt geschrieben oder gelesen wurde. Daran erkennt der Abgleich mit
+    /// der Platte die eigenen Schreibvorgänge.
+    pub saved_config: Option<AudioProgramConfig>,
 }
 
 impl ManagedAudioProgram {
     pub fn config_dir() -> PathBuf {
-        dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".jackstreamingmanager")
+        paths::config_dir()
     }
     /// Lädt alle vorhandenen Konfigurationen aus dem Konfigurationsverzeichnis.
-    pub fn load_all() -> (Vec<Self>, Vec<String>) {
+    /// Ein fehlerhaftes Programm verhindert nicht das Laden der übrigen; seine Fehler
+    /// stehen als `Error::LoadProgram` in der Liste.
+    pub fn load_all() -> (Vec<Self>, Vec<Error>) {
         let mut programs = Vec::new();
-        let mut errors = Vec::new();
-        let config_dir = Self::config_dir();
-        if let Ok(entries) = fs::read_dir(&config_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_dir() {
-                    let program_name = path.file_name().unwrap().to_string_lossy().to_string();
-                    match Self::new(&program_name) {
-                        Some(mut prog) => {
-                            prog.pid_file = ManagedAudioProgram::pid_path(&program_name);
-                            prog.jack_node_name = fs::read_to_string(path.join("jack_target")).unwrap_or_default();
-                            if let Err(e) = prog.remove_dead_pids() {
-                                errors.extend(e);
-                            }
-                            programs.push(prog);
-                        }
-                        None => {
-                            errors.push(format!("Fehler beim Laden der Konfiguration für '{}'", program_name));
-                        }
+        let (dirs, mut errors) = Self::program_dirs();
+        for dir_name in dirs {
+            match Self::new(&dir_name) {
+                Ok(mut prog) => {
+                    prog.jack_node_name = fs::read_to_string(prog.runtime_dir().join("jack_target")).unwrap_or_default();
+                    // Ein nicht laufendes Programm hat keine PID-Datei
+                    if let Err(e) = prog.remove_dead_pids()
+                        && !matches!(e, Error::PidMissing { .. })
+                    {
+                        errors.push(e);
+                    }
+                    // Inhaltliche Fehler nur melden, damit das Programm in der Oberfläche korrigiert werden kann
+                    let issues = config_schema::validate_program(&prog.config, &Self::config_path(&prog.config.id));
+                    if !issues.is_empty() {
+                        errors.push(Error::ConfigInvalid(issues));
                     }
+                    programs.push(prog);
+                }
+                Err(e) => {
+                    errors.push(Error::LoadProgram { dir: dir_name, source: Box::new(e) });
                 }
             }
-        } else {
-            errors.push(format!("Fehler beim Lesen des Konfigurationsverzeichnisses: {:?}", config_dir));
         }
         (programs, errors)
     }
 
-    /// Lädt die Einstellungen aus der Konfigurationsdatei und gibt eine neue Instanz zurück.
-    pub fn new(program_name: &str) -> Option<Self> {
-        let config_path = Self::config_path(program_name);
-        if let Ok(file) = File::open(&config_path) {
-            if let Ok(config) = serde_json::from_reader::<_, AudioProgramConfig>(file) {
-                let pid_file = Self::pid_path(program_name);
-                return Some(Self {
-                    config,
-                    process: None,
-                    pid_file,
-                    jack_node_name: "".to_string(),
+    /// Liest alle Konfigurationen mit dem jeweiligen JACK-Namen, ohne etwas zu verändern:
+    /// keine Migration auf der Platte, kein Umbenennen, keine PID-Bereinigung.
+    pub fn read_all() -> (Vec<(AudioProgramConfig, String)>, Vec<Error>) {
+        let mut configs = Vec::new();
+        let (dirs, mut errors) = Self::program_dirs();
+        for dir_name in dirs {
+            let config_path = Self::config_path(&dir_name);
+            let config = fs::read_to_string(&config_path)
+                .map_err(|source| Error::ConfigIo { path: config_path.clone(), source })
+                .and_then(|text| config_schema::parse_program_config(&text, &config_path).map_err(Error::ConfigParse))
+                .and_then(|(config, _)| match config_schema::check_program_name(&config.id) {
+                    Some(_) => Err(Error::InvalidName(config.id)),
+                    None => Ok(config),
                 });
+            match config {
+                Ok(config) => {
+                    let jack_node_name =
+                        fs::read_to_string(paths::runtime_dir().join(&config.id).join("jack_target")).unwrap_or_default();
+                    configs.push((config, jack_node_name));
+                }
+                Err(e) => errors.push(Error::LoadProgram { dir: dir_name, source: Box::new(e) }),
+            }
+        }
+        (configs, errors)
+    }
+
+    /// Namen der Programmverzeichnisse im Konfigurationsverzeichnis.
+    fn program_dirs() -> (Vec<String>, Vec<Error>) {
+        let config_dir = Self::config_dir();
+        let entries = match fs::read_dir(&config_dir) {
+            Ok(entries) => entries,
+            Err(source) => return (Vec::new(), vec![Error::ConfigIo { path: config_dir, source }]),
+        };
+        let reserved = [
+            crate::project::PROJECTS_DIR,
+            crate::snapshot::SNAPSHOTS_DIR,
+            atomic_file::BACKUP_DIR,
+            paths::STATE_DIR,
+            paths::LOG_DIR,
+        ];
+        let dirs = entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir() && !reserved.iter().any(|name| entry.file_name() == *name))
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        (dirs, Vec::new())
+    }
+
+    /// Lädt die Einstellungen aus der Konfigurationsdatei und gibt eine neue Instanz zurück.
+    /// Ältere Konfigurationsversionen werden migriert und im aktuellen Format zurückgeschrieben.
+    /// Ein noch nach dem Programmnamen benanntes Verzeichnis wird dabei auf die ID umbenannt.
+    pub fn new(dir_name: &str) -> Result<Self> {
+        let config_path = Self::config_path(dir_name);
+        let text = fs::read_to_string(&config_path)
+            .map_err(|source| Error::ConfigIo { path: config_path.clone(), source })?;
+        let (config, migrated) = config_schema::parse_program_config(&text, &config_path)
+            .map_err(Error::ConfigParse)?;
+        // Die ID wird Teil von Pfaden, deshalb vor jedem Umbenennen prüfen
+        if config_schema::check_program_name(&config.id).is_some() {
+            return Err(Error::InvalidName(config.id));
+        }
+
+        if config.id != dir_name {
+            let from = Self::config_dir().join(dir_name);
+            let to = Self::config_dir().join(&config.id);
+            if to.exists() {
+                return Err(Error::AlreadyExists { path: to }).context(format!("{:?} wurde nicht umbenannt", from));
+            }
+            // rename ist innerhalb eines Dateisystems atomar
+            fs::rename(&from, &to)
+                .map_err(|source| Error::ConfigIo { path: from.clone(), source })
+                .context(format!("Fehler beim Umbenennen nach {:?}", to))?;
+            let runtime_from = paths::runtime_dir().join(dir_name);
+            let runtime_to = paths::runtime_dir().join(&config.id);
+            if runtime_from.exists() && !runtime_to.exists() {
+                fs::rename(&runtime_from, &runtime_to)
+                    .map_err(|source| Error::ConfigIo { path: runtime_from.clone(), source })
+                    .context(format!("Fehler beim Umbenennen nach {:?}", runtime_to))?;
             }
         }
-        None
+
+        let mut prog = Self {
+            pid_file: Self::pid_path(&config.id),
+            saved_config: Some(config.clone()),
+            config,
+            process: None,
+            jack_node_name: "".to_string(),
+            dirty: false,
+        };
+        if migrated {
+            prog.save_config()?;
+        }
+        Ok(prog)
     }
 
-    fn config_path(program_name: &str) -> PathBuf {
-        Self::config_dir().join(format!("{}/config.json", program_name))
+    /// Neue Instanz für eine Konfiguration, die noch nicht gespeichert sein muss.
+    pub fn from_config(config: AudioProgramConfig) -> Self {
+        Self {
+            pid_file: Self::pid_path(&config.id),
+            config,
+            process: None,
+            jack_node_name: "".to_string(),
+            dirty: false,
+            saved_config: None,
+        }
     }
 
-    fn pid_path(program_name: &str) -> PathBuf {
-        Self::config_dir().join(format!("{}/pid", program_name))
+    fn config_path(id: &str) -> PathBuf {
+        Self::config_dir().join(format!("{}/config.json", id))
     }
 
-    pub fn save_config(&self) -> Result<(), Vec<String>> {
+    fn pid_path(id: &str) -> PathBuf {
+        paths::runtime_dir().join(id).join("pid")
+    }
+
+    /// Verzeichnis dieses Programms, benannt nach der ID.
+    pub fn dir(&self) -> PathBuf {
+        Self::config_dir().join(&self.config.id)
+    }
+
+    /// Verzeichnis für PID-Datei und JACK-Namen, getrennt von der Konfiguration.
+    pub fn runtime_dir(&self) -> PathBuf {
+        paths::runtime_dir().join(&self.config.id)
+    }
+
+    /// Speichert die Konfiguration absturzsicher und hebt die bisherige Fassung als Sicherung auf.
+    pub fn save_config(&mut self) -> Result<()> {
         let mut errors = Vec::new();
-        let dir = Self::config_dir().join(&self.config.program_name);
-        if let Err(e) = fs::create_dir_all(&dir) {
-            errors.push(format!("Fehler beim Erstellen des Verzeichnisses: {}", e));
+        let config_path = Self::config_path(&self.config.id);
+        if let Err(source) = atomic_file::backup(&config_path) {
+            errors.push(Error::ConfigIo { path: config_path.clone(), source }.context("Fehler beim Sichern der Konfiguration"));
         }
-        let config_path = dir.join("config.json");
-        match File::create(&config_path) {
-            Ok(file) => {
-                if let Err(e) = serde_json::to_writer_pretty(file, &self.config) {
-                    errors.push(format!("Fehler beim Schreiben der Konfiguration: {}", e));
+        match serde_json::to_vec_pretty(&self.config) {
+            Ok(data) => {
+                if let Err(source) = atomic_file::write_atomic(&config_path, &data) {
+                    errors.push(Error::ConfigIo { path: config_path, source });
                 }
             }
-            Err(e) => errors.push(format!("Fehler beim Erstellen der Konfigurationsdatei: {}", e)),
+            Err(source) => errors.push(Error::Serialize { path: config_path, source }),
         }
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+        if let Err(e) = Error::from_list(errors) {
+            tracing::error!(program = %self.config.program_name, "Speichern fehlgeschlagen: {}", e.report());
+            return Err(e);
         }
+        tracing::info!(program = %self.config.program_name, "Konfiguration gespeichert");
+        self.saved_config = Some(self.config.clone());
+        self.dirty = false;
+        Ok(())
     }
 
-    pub fn save_pid(&self) -> Result<(), Vec<String>> {
-        let mut errors = Vec::new();
-        if let Some(child) = &self.process {
-            let dir = Self::config_dir().join(&self.config.program_name);
-            if let Err(e) = fs::create_dir_all(&dir) {
-                errors.push(format!("Fehler beim Erstellen des Verzeichnisses: {}", e));
-            }
-            let pid_path = dir.join("pid");
-            match File::create(&pid_path) {
-                Ok(mut file) => {
-                    if let Err(e) = write!(file, "{}", child.id()) {
-                        errors.push(format!("Fehler beim Schreiben der PID: {}", e));
-                    }
+    /// Ersetzt die Konfiguration durch die zuletzt gesicherte Fassung und lädt sie neu.
+    /// Ist die Fassung ungültig, bleibt der bisherige Stand erhalten.
+    pub fn restore_previous_config(&mut self) -> Result<()> {
+        let config_path = Self::config_path(&self.config.id);
+        let restore = atomic_file::restore_previous(&config_path)
+            .map_err(|source| Error::ConfigIo { path: config_path.clone(), source })
+            .context("Fehler beim Wiederherstellen")?;
+        // Direkt einlesen statt über `new`, das bei abweichender ID das Verzeichnis umbenennen würde
+        let loaded = fs::read_to_string(&config_path)
+            .map_err(|source| Error::ConfigIo { path: config_path.clone(), source })
+            .and_then(|text| config_schema::parse_program_config(&text, &config_path).map_err(Error::ConfigParse))
+            .and_then(|(config, _)| {
+                if config.id == self.config.id { Ok(config) } else { Err(Error::InvalidName(config.id)) }
+            });
+        let backup = restore.backup.clone();
+        let config = match loaded {
+            Ok(config) => config,
+            Err(e) => {
+                if let Err(source) = restore.revert() {
+                    tracing::error!(program = %self.config.program_name, "Vorheriger Stand nicht zurückgeschrieben: {}", source);
                 }
-                Err(e) => errors.push(format!("Fehler beim Erstellen der PID-Datei: {}", e)),
+                return Err(e.context(format!("Wiederhergestellte Fassung {:?} ist ungültig", backup)));
             }
-        } else {
-            errors.push("Kein laufender Prozess vorhanden.".to_string());
+        };
+        if let Err(source) = restore.commit() {
+            tracing::warn!(backup = ?backup, "Wiederhergestellte Sicherung nicht entfernt: {}", source);
         }
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+        self.saved_config = Some(config.clone());
+        self.config = config;
+        self.dirty = false;
+        tracing::info!(program = %self.config.program_name, backup = ?backup, "Vorherige Konfiguration wiederhergestellt");
+        Ok(())
+    }
+
+    pub fn save_jack_target(&self) -> Result<()> {
+        let target_path = self.runtime_dir().join("jack_target");
+        atomic_file::write_atomic(&target_path, self.jack_node_name.as_bytes())
+            .map_err(|source| Error::ConfigIo { path: target_path, source })
+            .context("Fehler beim Speichern des JACK-Ziels")
+    }
+
+    /// Liest die PID aus der PID-Datei.
+    fn read_pid(&self) -> Result<u32> {
+        read_pid_file(&self.pid_file)
+    }
+
+    /// PID aus der PID-Datei, `None`, wenn das Programm nicht gestartet wurde.
+    pub fn pid(&self) -> Option<u32> {
+        self.read_pid().ok()
+    }
+
+    pub fn remove_dead_pids(&mut self) -> Result<()> {
+        let pid = self.read_pid()?;
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing()
+                .with_cmd(UpdateKind::OnlyIfNotSet)
+                .with_exe(UpdateKind::OnlyIfNotSet),
+        );
+        if sys.process(sysinfo::Pid::from(pid as usize)).is_none() {
+            fs::remove_file(&self.pid_file)
+                .map_err(|source| Error::PidIo { path: self.pid_file.clone(), source })?;
         }
+        Ok(())
     }
 
-    pub fn save_jack_target(&self) -> Result<(), Vec<String>> {
-        let mut errors = Vec::new();
-        let dir = Self::config_dir().join(&self.config.program_name);
-        if let Err(e) = fs::create_dir_all(&dir) {
-            errors.push(format!("Fehler beim Erstellen des Verzeichnisses: {}", e));
+    /// Was zum Starten nötig ist. `Launch::run` braucht das Programm nicht mehr, sodass der
+    /// Aufrufer die Programmliste währenddessen freigeben kann; danach `started` aufrufen.
+    pub fn launch(&self) -> Launch {
+        Launch { config: self.config.clone(), pid_file: self.pid_file.clone() }
+    }
+
+    /// Übernimmt den Prozess aus `Launch::run`.
+    pub fn started(&mut self, child: Option<Child>, result: &Result<()>) {
+        if child.is_some() {
+            self.process = child;
         }
-        let target_path = dir.join("jack_target");
-        match File::create(&target_path) {
-            Ok(mut file) => {
-                if let Err(e) = write!(file, "{}", self.jack_node_name) {
-                    errors.push(format!("Fehler beim Schreiben der jack_node_name: {}", e));
-                }
+        self.log_result("Start", result);
+    }
+
+    /// Beendet das Programm, egal ob es von dieser Instanz oder einer früheren gestartet wurde.
+    pub fn stop(&mut self) -> Result<()> {
+        let result = self.terminate();
+        self.log_result("Beenden", &result);
+        result
+    }
+
+    fn log_result(&self, action: &str, result: &Result<()>) {
+        let program = &self.config.program_name;
+        match result {
+            Ok(()) => {}
+            Err(e @ (Error::AlreadyRunning { .. } | Error::NotRunning { .. })) => {
+                tracing::warn!(program = %program, "{}", e.report());
             }
-            Err(e) => errors.push(format!("Fehler beim Erstellen der Datei: {}", e)),
-        }
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+            Err(e) => tracing::error!(program = %program, "{} fehlgeschlagen: {}", action, e.report()),
         }
     }
 
-    pub fn remove_dead_pids(&mut self) -> Result<(), Vec<String>> {
-        let mut errors = Vec::new();
-        if self.pid_file.exists() {
-            match fs::read_to_string(&self.pid_file) {
-                Ok(pid_str) => {
-                    match pid_str.trim().parse::<u32>() {
-                        Ok(pid) => {
-                            let mut sys = System::new();
-                            sys.refresh_processes_specifics(
-                                ProcessesToUpdate::All,
-                                true,
-                                ProcessRefreshKind::nothing()
-                                    .with_cmd(UpdateKind::OnlyIfNotSet)
-                                    .with_exe(UpdateKind::OnlyIfNotSet),
-                            );
-                            if sys.process(sysinfo::Pid::from(pid as usize)).is_none() {
-                                if let Err(e) = fs::remove_file(&self.pid_file) {
-                                    errors.push(format!("Fehler beim Entfernen der PID-Datei: {}", e));
-                                }
-                            }
-                        }
-                        Err(e) => errors.push(format!("Fehler beim Parsen der PID: {}", e)),
-                    }
+    fn terminate(&mut self) -> Result<()> {
+        if let Some(mut child) = self.process.take() {
+            let pid = child.id();
+            child.kill().map_err(|source| Error::Kill { pid, source: Some(source) })?;
+            let _ = child.wait();
+            tracing::info!(program = %self.config.program_name, pid, "Programm beendet");
+        } else {
+            let pid = match self.read_pid() {
+                Ok(pid) => pid,
+                Err(Error::PidMissing { .. }) => {
+                    return Err(Error::NotRunning { program: self.config.program_name.clone() });
                 }
-                Err(e) => errors.push(format!("Fehler beim Lesen der PID-Datei: {}", e)),
+                Err(e) => return Err(e),
+            };
+            let mut sys = System::new();
+            sys.refresh_processes(ProcessesToUpdate::Some(&[sysinfo::Pid::from(pid as usize)]), true);
+            if let Some(process) = sys.process(sysinfo::Pid::from(pid as usize))
+                && process.name().to_string_lossy().contains(&self.config.command_name)
+                && !process.kill()
+            {
+                return Err(Error::Kill { pid, source: None });
             }
-        } else {
-            errors.push("PID-Datei existiert nicht.".to_string());
+            tracing::info!(program = %self.config.program_name, pid, "Programm beendet");
+        }
+
+        if self.pid_file.exists() {
+            fs::remove_file(&self.pid_file)
+                .map_err(|source| Error::PidIo { path: self.pid_file.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Fügt eine Verbindungsregel für `use_case` hinzu, sofern es sie noch nicht gibt.
+    /// Liefert `true`, wenn die Konfiguration geändert wurde.
+    pub fn add_rule(&mut self, use_case: &str, source: &str, target: &str) -> bool {
+        let target_name = rule_target_name(target);
+        let exists = self
+            .config
+            .jack_ports
+            .iter()
+            .any(|p| p.filter == use_case && p.source_name == source && p.target_name == target_name);
+        if !exists {
+            self.config.jack_ports.push(JackPort {
+                filter: use_case.to_string(),
+                source_name: source.to_string(),
+                target_search_name: String::new(),
+                target_name,
+                optional: false,
+                switching: Switching::default(),
+                overlap_ms: 0,
+                loudness: false,
+            });
+            self.dirty = true;
         }
-        if errors.is_empty() {
-            Ok(())
+        !exists
+    }
+
+    /// Entfernt die Verbindungsregeln für `use_case`, die `source` mit `target` verbinden.
+    /// Liefert `true`, wenn die Konfiguration geändert wurde.
+    pub fn remove_rule(&mut self, use_case: &str, source: &str, target: &str) -> bool {
+        let target_name = rule_target_name(target);
+        let before = self.config.jack_ports.len();
+        self.config.jack_ports.retain(|p| {
+            !(p.filter == use_case && p.source_name == source && (p.target_name == target_name || p.target_name == target))
+        });
+        let changed = self.config.jack_ports.len() != before;
+        if changed {
+            self.dirty = true;
+        }
+        changed
+    }
+
+    /// Nimmt `use_case` aus dem Filter der Regel `index`. Bleibt kein Use-Case übrig oder ist
+    /// `use_case` leer, wird die Regel gelöscht. Liefert `true`, wenn die Konfiguration geändert wurde.
+    pub fn remove_rule_use_case(&mut self, index: usize, use_case: &str) -> bool {
+        let Some(port) = self.config.jack_ports.get_mut(index) else {
+            return false;
+        };
+        let remaining: Vec<&str> = port.filter.split_whitespace().filter(|f| *f != use_case).collect();
+        if use_case.is_empty() || remaining.is_empty() {
+            self.config.jack_ports.remove(index);
         } else {
-            Err(errors)
+            port.filter = remaining.join(" ");
         }
+        self.dirty = true;
+        true
     }
 
-    pub fn start(&mut self) -> Result<(), Vec<String>> {
+    pub fn delete_config(&self) -> Result<()> {
+        let dir = self.dir();
+        if dir.exists()
+            && let Err(source) = fs::remove_dir_all(&dir)
+        {
+            let e = Error::ConfigIo { path: dir, source };
+            tracing::error!(program = %self.config.program_name, "Löschen fehlgeschlagen: {}", e.report());
+            return Err(e);
+        }
+        // Laufzeitdaten sind ohne Konfiguration wertlos
+        let _ = fs::remove_dir_all(self.runtime_dir());
+        tracing::info!(program = %self.config.program_name, "Konfiguration gelöscht");
+        Ok(())
+    }
+}
+
+/// Start eines Programms ohne Zugriff auf die Programmliste, siehe `ManagedAudioProgram::launch`.
+pub struct Launch {
+    config: AudioProgramConfig,
+    pid_file: PathBuf,
+}
+
+impl Launch {
+    /// Startet den Prozess, schreibt die PID-Datei und wartet kurz, damit die JACK-Ports
+    /// angelegt sind. Liefert den Prozess auch dann, wenn danach etwas fehlgeschlagen ist.
+    pub fn run(self) -> (Option<Child>, Result<()>) {
         let mut errors = Vec::new();
 
         // Prüfe, ob das Programm bereits läuft (PID-File vorhanden und Prozess existiert)
-        if self.pid_file.exists() {
-            match fs::read_to_string(&self.pid_file) {
-                Ok(pid_str) => {
-                    match pid_str.trim().parse::<u32>() {
-                        Ok(pid) => {
-                            let mut sys = System::new();
-                            sys.refresh_processes_specifics(
-                                ProcessesToUpdate::All,
-                                true,
-                                ProcessRefreshKind::nothing()
-                                    .with_cmd(UpdateKind::OnlyIfNotSet)
-                                    .with_exe(UpdateKind::OnlyIfNotSet),
-                            );
-                            let test = sys.process(sysinfo::Pid::from(pid as usize));
-                            if test.is_some() && test.unwrap().name().to_string_lossy().contains(&self.config.command_name) {
-                                errors.push(format!("Prozess mit PID {} läuft bereits.", pid));
-                                return Err(errors);
-                            }
-                        }
-                        Err(e) => errors.push(format!("Fehler beim Parsen der PID: {}", e)),
-                    }
+        match read_pid_file(&self.pid_file) {
+            Ok(pid) => {
+                let mut sys = System::new();
+                sys.refresh_processes_specifics(
+                    ProcessesToUpdate::All,
+                    true,
+                    ProcessRefreshKind::nothing()
+                        .with_cmd(UpdateKind::OnlyIfNotSet)
+                        .with_exe(UpdateKind::OnlyIfNotSet),
+                );
+                if let Some(process) = sys.process(sysinfo::Pid::from(pid as usize))
+                    && process.name().to_string_lossy().contains(&self.config.command_name)
+                {
+                    return (None, Err(Error::AlreadyRunning { program: self.config.program_name.clone(), pid }));
                 }
-                Err(e) => errors.push(format!("Fehler beim Lesen der PID-Datei: {}", e)),
             }
+            Err(Error::PidMissing { .. }) => {}
+            Err(e) => errors.push(e),
         }
 
         let mut cmd = Command::new(&self.config.command_name);
         cmd.args(&self.config.start_params);
-        let child = match cmd.spawn() {
+        let mut child = match cmd.spawn() {
             Ok(child) => child,
-            Err(e) => {
-                errors.push(format!("Fehler beim Starten des Audio-Programms: {}", e));
-                return Err(errors);
+            Err(source) => {
+                errors.push(Error::Spawn { command: self.config.command_name.clone(), source });
+                return (None, Error::from_list(errors));
             }
         };
-        self.process = Some(child);
-        if let Err(pid_errors) = self.save_pid() {
-            errors.extend(pid_errors);
+        tracing::info!(program = %self.config.program_name, pid = child.id(), command = %self.config.command_name, "Programm gestartet");
+        if let Err(source) = atomic_file::write_atomic(&self.pid_file, child.id().to_string().as_bytes()) {
+            errors.push(Error::PidIo { path: self.pid_file.clone(), source });
         }
 
         // Pause für 300 ms nach dem Starten des Prozesses
@@ -242,33 +512,109 @@ impl ManagedAudioProgram {
 
         // Wenn das gestartete Programm "baresip" ist, sende "D" an stdin
         if self.config.command_name == "baresip" {
-            if let Some(child) = &mut self.process {
-                if let Some(stdin) = child.stdin.as_mut() {
-                    if let Err(e) = write!(stdin, "D") {
-                        errors.push(format!("Fehler beim Schreiben an baresip stdin: {}", e));
-                    }
-                    if let Err(e) = stdin.flush() {
-                        errors.push(format!("Fehler beim Flushen von baresip stdin: {}", e));
-                    }
-                } else {
-                    errors.push("baresip stdin ist nicht verfügbar.".to_string());
+            let stdin_error = |source| Error::Spawn { command: "baresip".to_string(), source }
+                .context("Fehler beim Schreiben an baresip stdin");
+            if let Some(stdin) = child.stdin.as_mut() {
+                if let Err(e) = write!(stdin, "D") {
+                    errors.push(stdin_error(e));
+                }
+                if let Err(e) = stdin.flush() {
+                    errors.push(stdin_error(e));
                 }
+            } else {
+                errors.push(stdin_error(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "stdin ist nicht verfügbar")));
             }
         }
 
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
-        }
+        (Some(child), Error::from_list(errors))
     }
+}
 
-    pub fn delete_config(&self) {
-        let dir = Self::config_dir().join(&self.config.program_name);
-        if dir.exists() {
-            fs::remove_dir_all(&dir).expect("Failed to delete program config directory");
-        }
+fn read_pid_file(path: &Path) -> Result<u32> {
+    if !path.exists() {
+        return Err(Error::PidMissing { path: path.to_path_buf() });
+    }
+    let pid_str = fs::read_to_string(path).map_err(|source| Error::PidIo { path: path.to_path_buf(), source })?;
+    pid_str
+        .trim()
+        .parse::<u32>()
+        .map_err(|source| Error::PidInvalid { path: path.to_path_buf(), source })
+}
+
+/// Clientname ohne angehängte Nummer, z.B. `baresip` für `baresip-1234`.
+pub fn client_base(client: &str) -> &str {
+    let trimmed = client.trim_end_matches(|c: char| c.is_ascii_digit());
+    if trimmed.len() == client.len() || trimmed.is_empty() {
+        return client;
     }
+    trimmed.trim_end_matches(['-', '_', '.', ' '])
+}
+
+/// Index des Programms, dem der JACK-Client des Ports gehört: über den ermittelten
+/// JACK-Namen oder, solange der unbekannt ist, über das Kommando.
+pub fn owner_of(programs: &[ManagedAudioProgram], port: &str) -> Option<usize> {
+    let client = port.split_once(':').map_or(port, |(client, _)| client);
+    programs.iter().position(|p| {
+        (!p.jack_node_name.is_empty() && p.jack_node_name == client)
+            || (!p.config.command_name.is_empty() && client_base(client) == p.config.command_name)
+    })
+}
+
+/// Zielname für eine Regel. Die Nummer eines Clients ändert sich beim nächsten Start,
+/// daher wird sie durch ein Suchmuster ersetzt.
+fn rule_target_name(target: &str) -> String {
+    match target.split_once(':') {
+        Some((client, short)) if client_base(client) != client => format!("{}*:{}", client_base(client), short),
+        _ => target.to_string(),
+    }
+}
+
+/// Sucht Quell- und Zielport einer konfigurierten Verbindung unter den vorhandenen Ports.
+pub fn resolve_connection(
+    ports: &[JackPortInfo],
+    apps_jack_node_names: &[String],
+    app_jack_node_name: String,
+    port: &JackPort,
+) -> Result<(String, String)> {
+    let Some(source) = ports.iter().find(|p| p.name == port.source_name) else {
+        return Err(Error::PortNotFound { port: port.source_name.clone() });
+    };
+    let search_target = get_jack_name(ports, apps_jack_node_names, app_jack_node_name, port)?;
+    let Some(target) = ports.iter().find(|p| p.name == search_target) else {
+        return Err(if port.target_name.contains('*') {
+            Error::Resolution { pattern: port.target_name.clone() }
+        } else {
+            Error::PortNotFound { port: search_target }
+        });
+    };
+    Ok((source.name.clone(), target.name.clone()))
+}
+
+
+pub fn get_jack_name(ports: &[JackPortInfo], apps_jack_node_names: &[String], app_jack_node_name: String, port: &JackPort) -> Result<String> {
+    let search_target = if !app_jack_node_name.is_empty() {
+        app_jack_node_name.clone() + ":" + if port.target_name.contains(':') {
+            port.target_name.split(':').next_back().unwrap_or(&port.target_name)
+        } else {
+            &port.target_name
+        }
+    } else if port.target_name.contains('*') {
+        let re = config_schema::pattern_regex(&port.target_name)
+            .map_err(|source| Error::Pattern { pattern: port.target_name.clone(), source })?;
+        let matching_targets = ports.iter().filter(|p| re.is_match(&p.name)).collect::<Vec<_>>();
+        // Nur das erste passende Ziel verbinden, das noch keinem anderen Programm gehört
+        let target = matching_targets
+            .iter()
+            .find(|target| !apps_jack_node_names.iter().any(|name| name == &target.name))
+            .or(matching_targets.first());
+        match target {
+            Some(target) => target.name.clone(),
+            None => return Err(Error::Resolution { pattern: port.target_name.clone() }),
+        }
+    } else {
+        port.target_name.clone()
+    };
+    Ok(search_target)
 }
 
 #[derive(Debug, Clone)]
@@ -277,12 +623,25 @@ pub struct JackPortInfo {
     pub properties: Vec<String>,
 }
 
-pub fn read_jack_ports() -> Vec<JackPortInfo> {
-    let output = Command::new("jack_lsp")
-        .arg("-p")
+/// Führt ein JACK-Werkzeug aus und liefert dessen Ausgabe. Fehlt das Werkzeug oder
+/// läuft kein JACK-Server, wird ein Fehler statt einer leeren Ausgabe geliefert.
+pub fn run_jack_tool(command: &str, args: &[&str]) -> Result<String> {
+    tracing::debug!(command, args = ?args, "JACK-Werkzeug aufrufen");
+    let output = Command::new(command)
+        .args(args)
         .output()
-        .expect("Failed to execute jack_lsp -p");
-    let stdout = String::from_utf8_lossy(&output.stdout);
+        .map_err(|source| Error::Jack { command: command.to_string(), source })?;
+    if !output.status.success() {
+        return Err(Error::JackCommand {
+            command: format!("{} {}", command, args.join(" ")),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn read_jack_ports() -> Result<Vec<JackPortInfo>> {
+    let stdout = run_jack_tool("jack_lsp", &["-p"])?;
 
     let mut ports = Vec::new();
     let mut current_port: Option<JackPortInfo> = None;
@@ -311,15 +670,11 @@ pub fn read_jack_ports() -> Vec<JackPortInfo> {
     if let Some(port) = current_port {
         ports.push(port);
     }
-    ports
+    Ok(ports)
 }
 
-pub fn read_jack_connections() -> Vec<(String, String)> {
-    let output = Command::new("jack_lsp")
-        .args(["-c", "-p"])
-        .output()
-        .expect("Failed to execute jack_lsp -c -p");
-    let stdout = String::from_utf8_lossy(&output.stdout);
+pub fn read_jack_connections() -> Result<Vec<(String, String)>> {
+    let stdout = run_jack_tool("jack_lsp", &["-c", "-p"])?;
 
     use std::collections::HashMap;
     let mut port_properties: HashMap<String, Vec<String>> = HashMap::new();
@@ -357,5 +712,128 @@ pub fn read_jack_connections() -> Vec<(String, String)> {
             }
         }
     }
-    connections
-}
\ No newline at end of file
+    Ok(connections)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    /// Legt ein gespeichertes Programm mit eigener ID im Testverzeichnis an.
+    fn program(id: &str) -> ManagedAudioProgram {
+        paths::init_for_tests();
+        let _ = fs::remove_dir_all(ManagedAudioProgram::config_dir().join(id));
+        ManagedAudioProgram::from_config(AudioProgramConfig {
+            version: config_schema::CURRENT_VERSION,
+            id: id.to_string(),
+            program_name: id.to_string(),
+            command_name: "jack_rec".to_string(),
+            start_params: Vec::new(),
+            jack_ports: Vec::new(),
+        })
+    }
+
+    /// Speichert mit geändertem Kommando. Die Sicherungen tragen einen Zeitstempel in
+    /// Millisekunden, deshalb kurz warten, damit keine die vorherige überschreibt.
+    fn save(prog: &mut ManagedAudioProgram, command: &str) {
+        thread::sleep(Duration::from_millis(5));
+        prog.config.command_name = command.to_string();
+        prog.save_config().unwrap();
+    }
+
+    fn backups(prog: &ManagedAudioProgram) -> Vec<PathBuf> {
+        atomic_file::list_backups(&ManagedAudioProgram::config_path(&prog.config.id))
+    }
+
+    fn on_disk(prog: &ManagedAudioProgram) -> String {
+        let path = ManagedAudioProgram::config_path(&prog.config.id);
+        config_schema::parse_program_config(&fs::read_to_string(&path).unwrap(), &path).unwrap().0.command_name
+    }
+
+    #[test]
+    fn rule_filter_lists_use_cases() {
+        let rule = |filter: &str| JackPort {
+            filter: filter.to_string(),
+            source_name: "mic:out".to_string(),
+            target_search_name: String::new(),
+            target_name: "in".to_string(),
+            optional: false,
+            switching: Switching::default(),
+            overlap_ms: 0,
+            loudness: false,
+        };
+        assert!(rule("live").applies_to("live"));
+        assert!(!rule("live").applies_to("pause"));
+        assert!(!rule("liveshow").applies_to("live"));
+        assert!(rule("live  pause").applies_to("pause"));
+        assert!(!rule("live pause").applies_to(""));
+        // Ohne Filter immer, auch ohne gewählten Use-Case
+        assert!(rule("").applies_to("live"));
+        assert!(rule("").applies_to(""));
+    }
+
+    #[test]
+    fn backups_are_rotated() {
+        let mut prog = program("test-rotation");
+        save(&mut prog, "v0");
+        assert!(backups(&prog).is_empty());
+        for version in 1..=atomic_file::BACKUP_COUNT + 2 {
+            save(&mut prog, &format!("v{}", version));
+        }
+        let backups = backups(&prog);
+        assert_eq!(backups.len(), atomic_file::BACKUP_COUNT);
+        // Die neueste Sicherung ist die vorletzte Fassung, die ältesten sind gelöscht
+        let newest = fs::read_to_string(&backups[0]).unwrap();
+        assert!(newest.contains(&format!("\"v{}\"", atomic_file::BACKUP_COUNT + 1)));
+        assert!(!backups.iter().any(|b| fs::read_to_string(b).unwrap().contains("\"v0\"")));
+    }
+
+    #[test]
+    fn unchanged_content_is_backed_up_once() {
+        let mut prog = program("test-unchanged");
+        save(&mut prog, "v1");
+        save(&mut prog, "v2");
+        save(&mut prog, "v2");
+        save(&mut prog, "v2");
+        assert_eq!(backups(&prog).len(), 2);
+    }
+
+    #[test]
+    fn restore_commits_valid_backup() {
+        let mut prog = program("test-restore");
+        save(&mut prog, "v1");
+        save(&mut prog, "v2");
+        save(&mut prog, "v3");
+        prog.restore_previous_config().unwrap();
+        assert_eq!(prog.config.command_name, "v2");
+        assert_eq!(on_disk(&prog), "v2");
+        assert!(!prog.dirty);
+        // Ein zweiter Aufruf geht weiter zurück statt zwischen zwei Fassungen zu wechseln
+        thread::sleep(Duration::from_millis(5));
+        prog.restore_previous_config().unwrap();
+        assert_eq!(prog.config.command_name, "v1");
+        assert_eq!(on_disk(&prog), "v1");
+    }
+
+    #[test]
+    fn restore_reverts_invalid_backup() {
+        let mut prog = program("test-revert");
+        save(&mut prog, "v1");
+        save(&mut prog, "v2");
+        let config_path = ManagedAudioProgram::config_path(&prog.config.id);
+        // Eine Sicherung mit fremder ID ist die neueste
+        let foreign = fs::read_to_string(&config_path).unwrap().replace("\"test-revert\"", "\"fremd\"");
+        let invalid = config_path.parent().unwrap().join(atomic_file::BACKUP_DIR).join("config.json.99999999-000000.000");
+        fs::write(&invalid, foreign).unwrap();
+        let before = backups(&prog).len();
+
+        assert!(prog.restore_previous_config().is_err());
+        assert_eq!(prog.config.command_name, "v2");
+        assert_eq!(on_disk(&prog), "v2");
+        // Keine zusätzliche Sicherung, die ungültige bleibt zur Untersuchung erhalten
+        assert_eq!(backups(&prog).len(), before);
+        assert!(invalid.exists());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::managed_audio_program::JackPortInfo;
+
+/// Zustand einer Zelle der Verbindungsmatrix.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CellState {
+    #[default]
+    Empty,
+    /// Regel vorhanden und verbunden
+    Live,
+    /// Regel vorhanden, aber (noch) nicht verbunden
+    Pending,
+    /// Regel vorhanden, Quelle oder Ziel lässt sich nicht auflösen
+    Failing,
+    /// Verbunden, ohne dass eine Regel es vorsieht
+    Unmanaged,
+}
+
+/// Eine Verbindungsregel des gewählten Use-Cases, bereits aufgelöst.
+pub struct Rule {
+    /// Index des Programms und des `JackPort`-Eintrags in dessen Konfiguration
+    pub program: usize,
+    pub port: usize,
+    pub source: String,
+    /// Aufgelöster Zielport oder, wenn nicht auflösbar, das konfigurierte Ziel
+    pub target: String,
+    pub resolved: bool,
+    /// Regel ohne Filter, gilt für alle Use-Cases
+    pub always: bool,
+}
+
+/// Zeilen- bzw. Spaltenkopf: ein Port.
+pub struct Header {
+    pub port: String,
+    /// Erster Port eines Clients, davor wird eine Trennlinie gezeichnet
+    pub group_start: bool,
+    /// Port existiert nicht, kommt nur in einer Regel vor
+    pub missing: bool,
+    /// Programm, das Regeln für diesen Zielport aufnimmt
+    pub owner: Option<usize>,
+}
+
+pub struct Cell {
+    pub row: usize,
+    pub column: usize,
+    pub state: CellState,
+    /// Indizes in `Matrix::rules`
+    pub rules: Vec<usize>,
+}
+
+#[derive(Default)]
+pub struct Matrix {
+    pub sources: Vec<Header>,
+    pub targets: Vec<Header>,
+    /// Nur Zellen, die nicht leer sind
+    pub cells: Vec<Cell>,
+    pub rules: Vec<Rule>,
+}
+
+impl Matrix {
+    /// Zeilen sind alle Ausgänge, Spalten die Eingänge der verwalteten Programme, jeweils nach Client
+    /// gruppiert. Ports, die nur in Regeln vorkommen, werden am Ende ihrer Gruppe angehängt.
+    pub fn build(
+        ports: &[JackPortInfo],
+        connections: &[(String, String)],
+        rules: Vec<Rule>,
+        owner_of: impl Fn(&str) -> Option<usize>,
+    ) -> Self {
+        let has = |port: &JackPortInfo, direction: &str| port.properties.iter().any(|p| p == direction);
+        let mut sources: Vec<(String, bool, Option<usize>)> = ports
+            .iter()
+            .filter(|p| has(p, "output"))
+            .map(|p| (p.name.clone(), false, None))
+            .collect();
+        let mut targets: Vec<(String, bool, Option<usize>)> = ports
+            .iter()
+            .filter(|p| has(p, "input"))
+            .filter_map(|p| owner_of(&p.name).map(|owner| (p.name.clone(), false, Some(owner))))
+            .collect();
+        for rule in rules.iter() {
+            if !sources.iter().any(|(name, _, _)| name == &rule.source) {
+                sources.push((rule.source.clone(), true, None));
+            }
+            if !targets.iter().any(|(name, _, _)| name == &rule.target) {
+                let missing = !ports.iter().any(|p| p.name == rule.target);
+                targets.push((rule.target.clone(), missing, Some(rule.program)));
+            }
+        }
+        let sources = group(sources);
+        let targets = group(targets);
+
+        let mut cells: Vec<Cell> = Vec::new();
+        let position = |headers: &[Header], name: &str| headers.iter().position(|h| h.port == name);
+        for (index, rule) in rules.iter().enumerate() {
+            if let (Some(row), Some(column)) = (position(&sources, &rule.source), position(&targets, &rule.target)) {
+                let connected = connections.iter().any(|(s, t)| s == &rule.source && t == &rule.target);
+                let cell = cell_mut(&mut cells, row, column);
+                cell.rules.push(index);
+                let state = match (rule.resolved, connected) {
+                    (false, _) => CellState::Failing,
+                    (true, true) => CellState::Live,
+                    (true, false) => CellState::Pending,
+                };
+                // Eine fehlschlagende Regel überdeckt eine funktionierende für dieselbe Zelle
+                if cell.state != CellState::Failing {
+                    cell.state = state;
+                }
+            }
+        }
+        for (source, target) in connections {
+            if let (Some(row), Some(column)) = (position(&sources, source), position(&targets, target)) {
+                let cell = cell_mut(&mut cells, row, column);
+                if cell.state == CellState::Empty {
+                    cell.state = CellState::Unmanaged;
+                }
+            }
+        }
+
+        Self { sources, targets, cells, rules }
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> Option<&Cell> {
+        self.cells.iter().find(|c| c.row == row && c.column == column)
+    }
+}
+
+fn cell_mut(cells: &mut Vec<Cell>, row: usize, column: usize) -> &mut Cell {
+    let index = match cells.iter().position(|c| c.row == row && c.column == column) {
+        Some(index) => index,
+        None => {
+            cells.push(Cell { row, column, state: CellState::Empty, rules: Vec::new() });
+            cells.len() - 1
+        }
+    };
+    &mut cells[index]
+}
+
+/// Sortiert die Ports stabil nach Client und markiert jeweils den ersten Port eines Clients.
+fn group(mut ports: Vec<(String, bool, Option<usize>)>) -> Vec<Header> {
+    let client = |port: &str| port.split_once(':').map_or(port, |(client, _)| client).to_string();
+    let mut order: Vec<String> = Vec::new();
+    for (port, _, _) in ports.iter() {
+        if !order.contains(&client(port)) {
+            order.push(client(port));
+        }
+    }
+    ports.sort_by_key(|(port, _, _)| order.iter().position(|c| *c == client(port)));
+    let mut previous = None;
+    ports
+        .into_iter()
+        .map(|(port, missing, owner)| {
+            let current = client(&port);
+            let group_start = previous.as_ref() != Some(&current);
+            previous = Some(current);
+            Header { port, group_start, missing, owner }
+        })
+        .collect()
+}
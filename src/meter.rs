@@ -0,0 +1,338 @@
+use std::{
+    f32::consts::PI,
+    fs,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+};
+
+use jack::{AudioIn, Client, ClientOptions, Control, Port, PortFlags, ProcessHandler, ProcessScope};
+
+use crate::error::{Error, Result};
+use crate::loudness::LoudnessConfig;
+use crate::managed_audio_program::ManagedAudioProgram;
+use crate::silence::SilenceDetector;
+use crate::{atomic_file, config_schema};
+
+/// Gemeinsamer Anfang der Namen aller eigenen JACK-Clients (Messung, Lautheit, Aufnahme, Testton).
+pub const CLIENT_PREFIX: &str = "jackstreamingmanager-";
+
+/// Name des JACK-Clients mit den Messeingängen. Ist er vergeben, hängt JACK eine Nummer an.
+const METER_CLIENT: &str = "jackstreamingmanager-meter";
+
+/// Über diesen Zeitraum werden Spitzenwert und RMS gebildet (Millisekunden).
+const WINDOW_MS: usize = 300;
+
+/// Überabtastung für die True-Peak-Schätzung und Stützstellen je Zwischenwert.
+const OVERSAMPLING: usize = 4;
+const TAPS: usize = 8;
+
+/// Untergrenze für Pegel in dBFS, darunter gilt das Signal als still.
+pub const MIN_DB: f32 = -90.0;
+
+/// Ab diesem Spitzenwert (dBFS) gilt das Signal als übersteuert.
+const CLIP_DB: f32 = -0.1;
+
+/// Ports, deren Pegel gemessen wird. Jeder Port kostet einen Eingang im Mess-Client,
+/// daher wird nur gemessen, was hier ausgewählt ist.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MeterConfig {
+    pub ports: Vec<String>,
+    /// Zusätzlich den True-Peak per Überabtastung schätzen, kostet mehr DSP-Last.
+    #[serde(default)]
+    pub true_peak: bool,
+    /// Ihre Ports werden zusätzlich gemessen
+    #[serde(default)]
+    pub silence: Vec<SilenceDetector>,
+    /// Zielbereich der Lautheitsmessung
+    #[serde(default)]
+    pub loudness: LoudnessConfig,
+}
+
+impl MeterConfig {
+    fn config_path() -> PathBuf {
+        ManagedAudioProgram::config_dir().join("meters.json")
+    }
+
+    /// Lädt die Auswahl. Fehlt die Datei, wird nichts gemessen.
+    pub fn load() -> Result<Self> {
+        let path = Self::config_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path).map_err(|source| Error::ConfigIo { path: path.clone(), source })?;
+        config_schema::parse_json(&text, &path).map_err(Error::ConfigParse)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::config_path();
+        let data = serde_json::to_vec_pretty(self).map_err(|source| Error::Serialize { path: path.clone(), source })?;
+        atomic_file::write_atomic(&path, &data).map_err(|source| Error::ConfigIo { path, source })
+    }
+
+    /// Ausgewählte Ports, danach die der Stille-Erkennung, jeder Port nur einmal.
+    pub fn measured_ports(&self) -> Vec<String> {
+        let mut ports = self.ports.clone();
+        for detector in self.silence.iter() {
+            if !ports.contains(&detector.port) {
+                ports.push(detector.port.clone());
+            }
+        }
+        ports
+    }
+
+    /// Schaltet die Messung für `port` um. Gibt zurück, ob der Port jetzt gemessen wird.
+    pub fn toggle(&mut self, port: &str) -> bool {
+        if let Some(index) = self.ports.iter().position(|p| p == port) {
+            self.ports.remove(index);
+            false
+        } else {
+            self.ports.push(port.to_string());
+            true
+        }
+    }
+}
+
+/// Ob der Port zu einem eigenen Client gehört. Solche Verbindungen sind keine Routen und
+/// werden beim Aufräumen und in Snapshots übergangen.
+pub fn is_internal_port(port: &str) -> bool {
+    port.split_once(':').is_some_and(|(client, _)| client.starts_with(CLIENT_PREFIX))
+}
+
+/// Gemessene Pegel in dBFS, jeweils über das letzte Messfenster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+    pub true_peak: Option<f32>,
+}
+
+impl Level {
+    pub fn clipping(&self) -> bool {
+        self.true_peak.unwrap_or(self.peak) >= CLIP_DB
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let db = |value: f32| if value <= MIN_DB { "-inf".to_string() } else { format!("{:.1}", value) };
+        write!(f, "Peak {} dBFS, RMS {} dBFS", db(self.peak), db(self.rms))?;
+        if let Some(true_peak) = self.true_peak {
+            write!(f, ", True-Peak {} dBTP", db(true_peak))?;
+        }
+        Ok(())
+    }
+}
+
+fn to_db(linear: f32) -> f32 {
+    if linear > 0.0 { (20.0 * linear.log10()).max(MIN_DB) } else { MIN_DB }
+}
+
+/// Messwerte eines Ports, vom Audio-Thread geschrieben und ohne Sperre gelesen.
+struct Channel {
+    port: String,
+    /// Der gemessene Port existiert und ist angeschlossen
+    present: AtomicBool,
+    /// Mindestens ein Messfenster ist abgeschlossen
+    measured: AtomicBool,
+    /// Lineare Werte als `f32::to_bits`
+    peak: AtomicU32,
+    rms: AtomicU32,
+    true_peak: AtomicU32,
+}
+
+/// Zwischenstand eines Eingangs im Audio-Thread.
+struct Input {
+    port: Port<AudioIn>,
+    peak: f32,
+    sum: f64,
+    true_peak: f32,
+    /// Letzte Samples, das neueste am Ende
+    history: [f32; TAPS],
+}
+
+struct Process {
+    inputs: Vec<Input>,
+    channels: Arc<Vec<Channel>>,
+    /// Koeffizienten der Zwischenwerte 1 bis `OVERSAMPLING - 1`, ohne True-Peak leer
+    phases: Vec<[f32; TAPS]>,
+    frames: usize,
+    window_frames: usize,
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        for input in self.inputs.iter_mut() {
+            for &sample in input.port.as_slice(ps) {
+                input.peak = input.peak.max(sample.abs());
+                input.sum += f64::from(sample * sample);
+                if !self.phases.is_empty() {
+                    input.history.copy_within(1.., 0);
+                    input.history[TAPS - 1] = sample;
+                    for coefficients in self.phases.iter() {
+                        let value: f32 = coefficients.iter().zip(input.history.iter()).map(|(c, s)| c * s).sum();
+                        input.true_peak = input.true_peak.max(value.abs());
+                    }
+                }
+            }
+        }
+        self.frames += ps.n_frames() as usize;
+        if self.frames >= self.window_frames {
+            for (input, channel) in self.inputs.iter_mut().zip(self.channels.iter()) {
+                let rms = (input.sum / self.frames as f64).sqrt() as f32;
+                channel.peak.store(input.peak.to_bits(), Ordering::Relaxed);
+                channel.rms.store(rms.to_bits(), Ordering::Relaxed);
+                channel.true_peak.store(input.true_peak.max(input.peak).to_bits(), Ordering::Relaxed);
+                channel.measured.store(true, Ordering::Relaxed);
+                input.peak = 0.0;
+                input.sum = 0.0;
+                input.true_peak = 0.0;
+            }
+            self.frames = 0;
+        }
+        Control::Continue
+    }
+}
+
+/// Interpolationsfilter (Hann-gefenstertes Sinc) für die Zwischenwerte zwischen den beiden
+/// mittleren Samples der Historie.
+fn interpolation_phases() -> Vec<[f32; TAPS]> {
+    let center = (TAPS / 2 - 1) as f32;
+    (1..OVERSAMPLING)
+        .map(|phase| {
+            let offset = phase as f32 / OVERSAMPLING as f32;
+            let mut coefficients = [0.0; TAPS];
+            for (k, coefficient) in coefficients.iter_mut().enumerate() {
+                let x = k as f32 - center - offset;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let window = 0.5 * (1.0 + (PI * x / (TAPS / 2) as f32).cos());
+                *coefficient = sinc * window;
+            }
+            coefficients
+        })
+        .collect()
+}
+
+/// Eigener JACK-Client, der die ausgewählten Ports auf Messeingänge legt und deren Pegel
+/// im Audio-Thread misst. Ausgänge werden direkt angeschlossen, bei Eingängen werden deren
+/// Quellen gespiegelt, sodass die Summe gemessen wird, die das Programm bekommt.
+/// Die Messung endet, wenn die Instanz verworfen wird.
+pub struct Meter {
+    client: jack::AsyncClient<(), Process>,
+    channels: Arc<Vec<Channel>>,
+    true_peak: bool,
+}
+
+impl Meter {
+    pub fn start(config: &MeterConfig) -> Result<Self> {
+        let jack_error = |action: &str| {
+            let action = action.to_string();
+            move |source| Error::JackClient { action, source }
+        };
+        let (client, _) = Client::new(METER_CLIENT, ClientOptions::NO_START_SERVER)
+            .map_err(jack_error("Mess-Client anmelden"))?;
+        let ports = config.measured_ports();
+        let mut inputs = Vec::new();
+        for index in 0..ports.len() {
+            let port = client
+                .register_port(&input_name(index), AudioIn)
+                .map_err(jack_error("Messeingang anlegen"))?;
+            inputs.push(Input { port, peak: 0.0, sum: 0.0, true_peak: 0.0, history: [0.0; TAPS] });
+        }
+        let channels: Arc<Vec<Channel>> = Arc::new(
+            ports
+                .iter()
+                .map(|port| Channel {
+                    port: port.clone(),
+                    present: AtomicBool::new(false),
+                    measured: AtomicBool::new(false),
+                    peak: AtomicU32::new(0),
+                    rms: AtomicU32::new(0),
+                    true_peak: AtomicU32::new(0),
+                })
+                .collect(),
+        );
+        let process = Process {
+            inputs,
+            channels: channels.clone(),
+            phases: if config.true_peak { interpolation_phases() } else { Vec::new() },
+            frames: 0,
+            window_frames: client.sample_rate() * WINDOW_MS / 1000,
+        };
+        let client = client.activate_async((), process).map_err(jack_error("Mess-Client aktivieren"))?;
+        tracing::info!(ports = ports.len(), true_peak = config.true_peak, "Pegelmessung gestartet");
+        Ok(Self { client, channels, true_peak: config.true_peak })
+    }
+
+    /// Gleicht die Verbindungen der Messeingänge mit dem Graphen ab: Ports, die neu auftauchen,
+    /// werden angeschlossen, und bei Eingängen folgen die Messeingänge deren Quellen.
+    pub fn sync(&self) -> Vec<Error> {
+        let client = self.client.as_client();
+        let mut errors = Vec::new();
+        for (index, channel) in self.channels.iter().enumerate() {
+            let own = format!("{}:{}", client.name(), input_name(index));
+            let present = follow(client, &own, &channel.port, &mut errors);
+            channel.present.store(present, Ordering::Relaxed);
+        }
+        errors
+    }
+
+    /// Pegel aller gemessenen Ports in der Reihenfolge der Auswahl. `None`, solange der Port
+    /// fehlt oder noch kein Messfenster abgeschlossen ist.
+    pub fn levels(&self) -> Vec<(String, Option<Level>)> {
+        self.channels
+            .iter()
+            .map(|channel| {
+                let ready = channel.present.load(Ordering::Relaxed) && channel.measured.load(Ordering::Relaxed);
+                let level = ready.then(|| {
+                    let load = |value: &AtomicU32| to_db(f32::from_bits(value.load(Ordering::Relaxed)));
+                    Level {
+                        peak: load(&channel.peak),
+                        rms: load(&channel.rms),
+                        true_peak: self.true_peak.then(|| load(&channel.true_peak)),
+                    }
+                });
+                (channel.port.clone(), level)
+            })
+            .collect()
+    }
+}
+
+/// Verbindet den eigenen Eingang `own` so, dass er dasselbe Signal wie `port` bekommt: einen
+/// Ausgang direkt, bei einem Eingang dessen Quellen. Liefert, ob `port` existiert.
+pub fn follow(client: &Client, own: &str, port: &str, errors: &mut Vec<Error>) -> bool {
+    let outputs = client.ports(None, None, PortFlags::IS_OUTPUT);
+    // Quellen, die mit `port` verbunden sind, auch der Testton, damit er am Ziel sichtbar wird
+    let sources = |port: &str| -> Vec<String> {
+        let Some(port) = client.port_by_name(port) else { return Vec::new() };
+        outputs
+            .iter()
+            .filter(|source| port.is_connected_to(source).unwrap_or(false))
+            .cloned()
+            .collect()
+    };
+    let current = sources(own);
+    let followed = client.port_by_name(port);
+    let wanted = match &followed {
+        Some(followed) if followed.flags().contains(PortFlags::IS_OUTPUT) => vec![port.to_string()],
+        Some(_) => sources(port),
+        None => Vec::new(),
+    };
+    for source in wanted.iter().filter(|p| !current.contains(p)) {
+        if let Err(source_error) = client.connect_ports_by_name(source, own) {
+            errors.push(Error::JackClient { action: format!("{} messen", port), source: source_error });
+        }
+    }
+    for source in current.iter().filter(|p| !wanted.contains(p)) {
+        if let Err(source_error) = client.disconnect_ports_by_name(source, own) {
+            errors.push(Error::JackClient { action: format!("{} nicht mehr messen", port), source: source_error });
+        }
+    }
+    followed.is_some()
+}
+
+fn input_name(index: usize) -> String {
+    format!("in_{}", index + 1)
+}
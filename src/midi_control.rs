@@ -0,0 +1,291 @@
+use std::{fs, path::PathBuf, sync::{Arc, Mutex}};
+
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+
+use crate::{atomic_file, config_schema};
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{AudioProgramConfig, ManagedAudioProgram};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MidiMessageKind {
+    NoteOn,
+    ControlChange,
+    ProgramChange,
+}
+
+/// Eine eingehende MIDI-Nachricht, auf die eine Zuordnung reagiert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MidiTrigger {
+    pub kind: MidiMessageKind,
+    pub channel: u8,
+    pub number: u8,
+}
+
+/// Programme werden über ihre ID angesprochen, Use-Cases über ihren Namen.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MidiAction {
+    UseCase(String),
+    StartProgram(String),
+    StopProgram(String),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MidiMapping {
+    pub trigger: MidiTrigger,
+    pub action: MidiAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MidiConfig {
+    /// Teil des Namens des MIDI-Eingangs (ALSA-Sequencer), z.B. "nanoKONTROL". JACK-MIDI wird
+    /// nicht direkt unterstützt, solche Geräte müssen über a2jmidid als ALSA-Ports erscheinen.
+    pub input_port: String,
+    /// Teil des Namens des MIDI-Ausgangs für die LED-Rückmeldung. Leer = keine Rückmeldung.
+    pub output_port: String,
+    pub mappings: Vec<MidiMapping>,
+}
+
+impl MidiTrigger {
+    /// Wertet eine rohe MIDI-Nachricht aus. Note-Off, Note-On mit Velocity 0 und
+    /// CC mit Wert 0 (Taste losgelassen) lösen nichts aus.
+    pub fn from_message(message: &[u8]) -> Option<Self> {
+        let status = *message.first()?;
+        let channel = status & 0x0F;
+        let number = *message.get(1)?;
+        let kind = match status & 0xF0 {
+            0x90 if message.get(2).copied().unwrap_or(0) > 0 => MidiMessageKind::NoteOn,
+            0xB0 if message.get(2).copied().unwrap_or(0) > 0 => MidiMessageKind::ControlChange,
+            0xC0 => MidiMessageKind::ProgramChange,
+            _ => return None,
+        };
+        Some(Self { kind, channel, number })
+    }
+
+    /// Nachricht, mit der die LED des Controllers für diesen Trigger geschaltet wird.
+    fn led_message(&self, on: bool) -> Option<[u8; 3]> {
+        let value = if on { 0x7F } else { 0x00 };
+        match self.kind {
+            MidiMessageKind::NoteOn => Some([0x90 | self.channel, self.number, value]),
+            MidiMessageKind::ControlChange => Some([0xB0 | self.channel, self.number, value]),
+            MidiMessageKind::ProgramChange => None,
+        }
+    }
+}
+
+impl std::fmt::Display for MidiTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            MidiMessageKind::NoteOn => "Note",
+            MidiMessageKind::ControlChange => "CC",
+            MidiMessageKind::ProgramChange => "PC",
+        };
+        write!(f, "{} {} (Kanal {})", kind, self.number, self.channel + 1)
+    }
+}
+
+impl std::fmt::Display for MidiAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiAction::UseCase(name) => write!(f, "Use-Case {}", name),
+            MidiAction::StartProgram(name) => write!(f, "Starte {}", name),
+            MidiAction::StopProgram(name) => write!(f, "Stoppe {}", name),
+        }
+    }
+}
+
+impl MidiConfig {
+    fn config_path() -> PathBuf {
+        ManagedAudioProgram::config_dir().join("midi.json")
+    }
+
+    /// Lädt die MIDI-Zuordnungen. Fehlt die Datei, wird eine leere Konfiguration geliefert.
+    pub fn load() -> Result<Self> {
+        let path = Self::config_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path).map_err(|source| Error::ConfigIo { path: path.clone(), source })?;
+        config_schema::parse_json(&text, &path).map_err(Error::ConfigParse)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        let path = Self::config_path();
+        if let Err(source) = atomic_file::backup(&path) {
+            errors.push(Error::ConfigIo { path: path.clone(), source }.context("Fehler beim Sichern der MIDI-Konfiguration"));
+        }
+        match serde_json::to_vec_pretty(self) {
+            Ok(data) => {
+                if let Err(source) = atomic_file::write_atomic(&path, &data) {
+                    errors.push(Error::ConfigIo { path, source });
+                }
+            }
+            Err(source) => errors.push(Error::Serialize { path, source }),
+        }
+        Error::from_list(errors)
+    }
+
+    pub fn action_for(&self, trigger: &MidiTrigger) -> Option<&MidiAction> {
+        self.mappings
+            .iter()
+            .find(|m| &m.trigger == trigger)
+            .map(|m| &m.action)
+    }
+
+    /// Legt eine Zuordnung an oder ersetzt die bestehende für denselben Trigger.
+    pub fn learn(&mut self, trigger: MidiTrigger, action: MidiAction) {
+        self.mappings.retain(|m| m.trigger != trigger);
+        self.mappings.push(MidiMapping { trigger, action });
+    }
+
+    /// Beschriftungen für die Oberfläche; Programm-IDs werden über `programs` in Namen aufgelöst.
+    pub fn mapping_labels(&self, programs: &[AudioProgramConfig]) -> Vec<String> {
+        let name = |id: &String| {
+            programs
+                .iter()
+                .find(|p| &p.id == id)
+                .map(|p| p.program_name.clone())
+                .unwrap_or_else(|| id.clone())
+        };
+        self.mappings
+            .iter()
+            .map(|m| {
+                let action = match &m.action {
+                    MidiAction::StartProgram(id) => MidiAction::StartProgram(name(id)),
+                    MidiAction::StopProgram(id) => MidiAction::StopProgram(name(id)),
+                    action => action.clone(),
+                };
+                format!("{} -> {}", m.trigger, action)
+            })
+            .collect()
+    }
+
+    /// Ersetzt Programmverweise, die noch den Programmnamen statt der ID enthalten.
+    /// Liefert `true`, wenn etwas geändert wurde und gespeichert werden sollte.
+    pub fn resolve_program_names(&mut self, programs: &[AudioProgramConfig]) -> bool {
+        let mut changed = false;
+        for mapping in self.mappings.iter_mut() {
+            if let MidiAction::StartProgram(reference) | MidiAction::StopProgram(reference) = &mut mapping.action
+                && !programs.iter().any(|p| &p.id == reference)
+                && let Some(program) = programs.iter().find(|p| &p.program_name == reference)
+            {
+                *reference = program.id.clone();
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Hält die MIDI-Verbindungen offen, solange die Instanz lebt.
+pub struct MidiController {
+    _input: Option<MidiInputConnection<()>>,
+    output: Option<MidiOutputConnection>,
+}
+
+impl MidiController {
+    /// Öffnet Ein- und Ausgang laut Konfiguration. `on_trigger` wird im MIDI-Thread
+    /// für jede auswertbare Nachricht aufgerufen.
+    pub fn connect<F>(config: &MidiConfig, mut on_trigger: F) -> (Self, Vec<Error>)
+    where
+        F: FnMut(MidiTrigger) + Send + 'static,
+    {
+        let mut errors = Vec::new();
+
+        let input = if config.input_port.is_empty() {
+            None
+        } else {
+            match MidiInput::new("jackstreamingmanager") {
+                Ok(mut midi_in) => {
+                    midi_in.ignore(Ignore::All);
+                    let port = midi_in.ports().into_iter().find(|p| {
+                        midi_in.port_name(p).map(|n| n.contains(&config.input_port)).unwrap_or(false)
+                    });
+                    match port {
+                        Some(port) => match midi_in.connect(
+                            &port,
+                            "control-in",
+                            move |_, message, _| {
+                                if let Some(trigger) = MidiTrigger::from_message(message) {
+                                    tracing::debug!(%trigger, "MIDI-Trigger empfangen");
+                                    on_trigger(trigger);
+                                }
+                            },
+                            (),
+                        ) {
+                            Ok(conn) => {
+                                tracing::info!(port = %config.input_port, "MIDI-Eingang verbunden");
+                                Some(conn)
+                            }
+                            Err(e) => {
+                                errors.push(Error::Midi(format!("Fehler beim Verbinden mit MIDI-Eingang {}: {}", config.input_port, e)));
+                                None
+                            }
+                        },
+                        None => {
+                            errors.push(Error::Midi(format!("MIDI-Eingang nicht gefunden: {}", config.input_port)));
+                            None
+                        }
+                    }
+                }
+                Err(e) => {
+                    errors.push(Error::Midi(format!("Fehler beim Öffnen des MIDI-Systems: {}", e)));
+                    None
+                }
+            }
+        };
+
+        let output = if config.output_port.is_empty() {
+            None
+        } else {
+            match MidiOutput::new("jackstreamingmanager") {
+                Ok(midi_out) => {
+                    let port = midi_out.ports().into_iter().find(|p| {
+                        midi_out.port_name(p).map(|n| n.contains(&config.output_port)).unwrap_or(false)
+                    });
+                    match port {
+                        Some(port) => match midi_out.connect(&port, "control-out") {
+                            Ok(conn) => {
+                                tracing::info!(port = %config.output_port, "MIDI-Ausgang verbunden");
+                                Some(conn)
+                            }
+                            Err(e) => {
+                                errors.push(Error::Midi(format!("Fehler beim Verbinden mit MIDI-Ausgang {}: {}", config.output_port, e)));
+                                None
+                            }
+                        },
+                        None => {
+                            errors.push(Error::Midi(format!("MIDI-Ausgang nicht gefunden: {}", config.output_port)));
+                            None
+                        }
+                    }
+                }
+                Err(e) => {
+                    errors.push(Error::Midi(format!("Fehler beim Öffnen des MIDI-Systems: {}", e)));
+                    None
+                }
+            }
+        };
+
+        (Self { _input: input, output }, errors)
+    }
+
+    /// Schaltet die LED des aktiven Use-Cases ein und die aller anderen aus.
+    pub fn show_active_use_case(&mut self, config: &MidiConfig, use_case: &str) -> Result<()> {
+        let mut errors = Vec::new();
+        if let Some(output) = self.output.as_mut() {
+            for mapping in &config.mappings {
+                if let MidiAction::UseCase(name) = &mapping.action
+                    && let Some(message) = mapping.trigger.led_message(name == use_case)
+                    && let Err(e) = output.send(&message)
+                {
+                    errors.push(Error::Midi(format!("Fehler beim Senden der LED-Rückmeldung: {}", e)));
+                }
+            }
+        }
+        Error::from_list(errors)
+    }
+}
+
+/// Zustand des Lernmodus: die Aktion, die dem nächsten eingehenden Trigger zugeordnet wird.
+pub type MidiLearnState = Arc<Mutex<Option<MidiAction>>>;
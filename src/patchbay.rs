@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use crate::managed_audio_program::JackPortInfo;
+
+const MARGIN: f32 = 20.0;
+const CLIENT_WIDTH: f32 = 220.0;
+const COLUMN_GAP: f32 = 140.0;
+const HEADER_HEIGHT: f32 = 26.0;
+const ROW_HEIGHT: f32 = 20.0;
+const CLIENT_GAP: f32 = 18.0;
+/// Abstand zu einem Pin, in dem ein losgelassenes Kabel noch als darauf gezogen gilt.
+const PIN_CATCH: f32 = 12.0;
+
+/// Ein JACK-Client als Kasten.
+pub struct Client {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Gehört zu einem verwalteten Programm
+    pub managed: bool,
+}
+
+/// Ein Port als Anschluss am Rand seines Clients: Eingänge links, Ausgänge rechts.
+pub struct Pin {
+    pub port: String,
+    pub label: String,
+    pub x: f32,
+    pub y: f32,
+    pub output: bool,
+    pub connected: bool,
+}
+
+/// Eine bestehende Verbindung zwischen zwei Pins.
+pub struct Line {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    /// Vom aktiven Use-Case vorgesehen
+    pub scenario: bool,
+}
+
+/// Der JACK-Graph, angeordnet für die Patchbay.
+#[derive(Default)]
+pub struct Graph {
+    pub clients: Vec<Client>,
+    pub pins: Vec<Pin>,
+    pub lines: Vec<Line>,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Ordnet die Clients in drei Spalten an: reine Quellen links, Clients mit Ein- und Ausgängen
+/// in der Mitte, reine Senken rechts. Die Reihenfolge innerhalb einer Spalte folgt `jack_lsp`.
+pub fn layout(
+    ports: &[JackPortInfo],
+    connections: &[(String, String)],
+    managed: impl Fn(&str) -> bool,
+    scenario: &HashSet<(String, String)>,
+) -> Graph {
+    // Clients in der Reihenfolge ihres ersten Ports, Ports ohne Richtung werden nicht gezeigt
+    let mut clients: Vec<(String, Vec<&JackPortInfo>, Vec<&JackPortInfo>)> = Vec::new();
+    for port in ports {
+        let Some((client, _)) = port.name.split_once(':') else { continue };
+        let output = port.properties.iter().any(|p| p == "output");
+        let input = port.properties.iter().any(|p| p == "input");
+        if !output && !input {
+            continue;
+        }
+        let index = match clients.iter().position(|(name, _, _)| name == client) {
+            Some(index) => index,
+            None => {
+                clients.push((client.to_string(), Vec::new(), Vec::new()));
+                clients.len() - 1
+            }
+        };
+        if output {
+            clients[index].2.push(port);
+        } else {
+            clients[index].1.push(port);
+        }
+    }
+
+    let connected: HashSet<&str> = connections
+        .iter()
+        .flat_map(|(source, target)| [source.as_str(), target.as_str()])
+        .collect();
+    let mut graph = Graph::default();
+    let mut column_y = [MARGIN; 3];
+    for (name, inputs, outputs) in clients {
+        let column = match (inputs.is_empty(), outputs.is_empty()) {
+            (true, _) => 0,
+            (false, false) => 1,
+            (false, true) => 2,
+        };
+        let x = MARGIN + column as f32 * (CLIENT_WIDTH + COLUMN_GAP);
+        let y = column_y[column];
+        let rows = inputs.len() + outputs.len();
+        let height = HEADER_HEIGHT + rows as f32 * ROW_HEIGHT + ROW_HEIGHT / 2.0;
+        column_y[column] += height + CLIENT_GAP;
+
+        // Eingänge zuerst, damit Kabel von links nicht die Ausgänge kreuzen
+        for (row, (port, output)) in inputs
+            .iter()
+            .map(|p| (p, false))
+            .chain(outputs.iter().map(|p| (p, true)))
+            .enumerate()
+        {
+            graph.pins.push(Pin {
+                port: port.name.clone(),
+                label: port.name.split_once(':').map_or(port.name.as_str(), |(_, short)| short).to_string(),
+                x: if output { x + CLIENT_WIDTH } else { x },
+                y: y + HEADER_HEIGHT + (row as f32 + 0.5) * ROW_HEIGHT,
+                output,
+                connected: connected.contains(port.name.as_str()),
+            });
+        }
+        graph.clients.push(Client { managed: managed(&name), name, x, y, width: CLIENT_WIDTH, height });
+    }
+
+    for (source, target) in connections {
+        let from = graph.pins.iter().find(|p| p.output && &p.port == source);
+        let to = graph.pins.iter().find(|p| !p.output && &p.port == target);
+        if let (Some(from), Some(to)) = (from, to) {
+            graph.lines.push(Line {
+                x1: from.x,
+                y1: from.y,
+                x2: to.x,
+                y2: to.y,
+                scenario: scenario.contains(&(source.clone(), target.clone())),
+            });
+        }
+    }
+
+    graph.width = 2.0 * MARGIN + 3.0 * CLIENT_WIDTH + 2.0 * COLUMN_GAP;
+    graph.height = column_y.iter().cloned().fold(MARGIN, f32::max) + MARGIN;
+    graph
+}
+
+/// Die Verbindung, die ein von `from` nach (`x`, `y`) gezogenes Kabel meint: Quelle und Ziel,
+/// wenn dort ein Pin der Gegenrichtung liegt.
+pub fn drop_target(pins: &[Pin], from: usize, x: f32, y: f32) -> Option<(String, String)> {
+    let from = pins.get(from)?;
+    let to = pins
+        .iter()
+        .filter(|p| p.output != from.output)
+        .map(|p| (p, (p.x - x).hypot(p.y - y)))
+        .filter(|(_, distance)| *distance <= PIN_CATCH)
+        .min_by(|a, b| a.1.total_cmp(&b.1))?
+        .0;
+    if from.output {
+        Some((from.port.clone(), to.port.clone()))
+    } else {
+        Some((to.port.clone(), from.port.clone()))
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fs, io, path::{Path, PathBuf}, sync::OnceLock};
+
+use crate::error::{Error, Result};
+
+const APP_NAME: &str = "jackstreamingmanager";
+
+/// Umgebungsvariable, mit der das Verzeichnis wie mit `--config-dir` festgelegt wird.
+pub const CONFIG_DIR_ENV: &str = "JACKSTREAMINGMANAGER_CONFIG_DIR";
+
+/// Unterverzeichnisse für Laufzeitdaten und Logs, wenn das Verzeichnis vorgegeben wurde.
+/// So bleiben parallel laufende Testinstanzen vollständig getrennt.
+pub const STATE_DIR: &str = "state";
+pub const LOG_DIR: &str = "logs";
+
+/// Früherer Speicherort im Home-Verzeichnis, wird beim ersten Start migriert.
+const LEGACY_DIR: &str = ".jackstreamingmanager";
+
+/// Dateien im Programmverzeichnis, die Laufzeitzustand und keine Konfiguration sind.
+const RUNTIME_FILES: &[&str] = &["pid", "jack_target"];
+
+struct Paths {
+    config: PathBuf,
+    runtime: PathBuf,
+    log: PathBuf,
+}
+
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+impl Paths {
+    fn in_dir(dir: PathBuf) -> Self {
+        Self {
+            runtime: dir.join(STATE_DIR),
+            log: dir.join(LOG_DIR),
+            config: dir,
+        }
+    }
+
+    /// Konfiguration in `$XDG_CONFIG_HOME`, Laufzeitzustand in `$XDG_RUNTIME_DIR`
+    /// (ersatzweise `$XDG_STATE_HOME`), Logs in `$XDG_STATE_HOME`.
+    fn xdg() -> Result<Self> {
+        let missing = || {
+            Error::Paths(format!(
+                "Kein Home-Verzeichnis gefunden. Bitte das Verzeichnis mit --config-dir oder {} angeben.",
+                CONFIG_DIR_ENV
+            ))
+        };
+        let config = dirs::config_dir().ok_or_else(missing)?.join(APP_NAME);
+        let state = dirs::state_dir().ok_or_else(missing)?.join(APP_NAME);
+        let runtime = dirs::runtime_dir().map(|d| d.join(APP_NAME)).unwrap_or_else(|| state.join(STATE_DIR));
+        Ok(Self {
+            config,
+            runtime,
+            log: state.join(LOG_DIR),
+        })
+    }
+}
+
+/// Legt die Verzeichnisse fest und migriert Daten vom früheren Speicherort.
+/// `override_dir` hat Vorrang vor der Umgebungsvariable. Liefert Hinweise zur Migration.
+pub fn init(override_dir: Option<PathBuf>) -> Result<Vec<String>> {
+    let override_dir = override_dir.or_else(|| {
+        std::env::var_os(CONFIG_DIR_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    });
+    let mut notes = Vec::new();
+    let paths = match override_dir {
+        Some(dir) => Paths::in_dir(dir),
+        None => {
+            let paths = Paths::xdg()?;
+            if let Some(home) = dirs::home_dir() {
+                notes.extend(migrate_legacy(&home.join(LEGACY_DIR), &paths)?);
+            }
+            paths
+        }
+    };
+    fs::create_dir_all(&paths.config)
+        .map_err(|source| Error::ConfigIo { path: paths.config.clone(), source })?;
+    if PATHS.set(paths).is_err() {
+        return Err(Error::Paths("Verzeichnisse wurden bereits festgelegt".to_string()));
+    }
+    Ok(notes)
+}
+
+fn paths() -> &'static Paths {
+    // Nur ohne vorheriges `init` erreichbar; dann gelten die Standardverzeichnisse
+    PATHS.get_or_init(|| Paths::xdg().unwrap_or_else(|_| Paths::in_dir(std::env::temp_dir().join(APP_NAME))))
+}
+
+/// Programmkonfigurationen, MIDI-Zuordnungen, Projekte und Sicherungen.
+pub fn config_dir() -> PathBuf {
+    paths().config.clone()
+}
+
+/// PID-Dateien und ermittelte JACK-Namen der Programme.
+pub fn runtime_dir() -> PathBuf {
+    paths().runtime.clone()
+}
+
+pub fn log_dir() -> PathBuf {
+    paths().log.clone()
+}
+
+/// Verschiebt `~/.jackstreamingmanager` in die XDG-Verzeichnisse, sofern dort noch nichts liegt.
+fn migrate_legacy(legacy: &Path, paths: &Paths) -> Result<Vec<String>> {
+    let target_used = fs::read_dir(&paths.config).is_ok_and(|mut entries| entries.next().is_some());
+    if !legacy.is_dir() || target_used {
+        return Ok(Vec::new());
+    }
+    let error = |source: io::Error| {
+        Error::ConfigIo { path: legacy.to_path_buf(), source }.context(format!("Fehler beim Verschieben nach {:?}", paths.config))
+    };
+
+    if let Some(parent) = paths.config.parent() {
+        fs::create_dir_all(parent).map_err(error)?;
+    }
+    // Ein leeres Zielverzeichnis steht der Umbenennung im Weg
+    let _ = fs::remove_dir(&paths.config);
+    move_path(legacy, &paths.config).map_err(error)?;
+
+    let mut notes = vec![format!("Konfiguration von {:?} nach {:?} verschoben", legacy, paths.config)];
+    let mut runtime_moved = false;
+    for entry in fs::read_dir(&paths.config).map_err(error)?.flatten() {
+        if !entry.path().is_dir() {
+            continue;
+        }
+        for name in RUNTIME_FILES {
+            let from = entry.path().join(name);
+            if from.exists() {
+                let to = paths.runtime.join(entry.file_name()).join(name);
+                fs::create_dir_all(paths.runtime.join(entry.file_name()))
+                    .and_then(|_| move_path(&from, &to))
+                    .map_err(|source| Error::ConfigIo { path: from.clone(), source })
+                    .map_err(|e| e.context(format!("Fehler beim Verschieben nach {:?}", to)))?;
+                runtime_moved = true;
+            }
+        }
+    }
+    if runtime_moved {
+        notes.push(format!("Laufzeitdaten nach {:?} verschoben", paths.runtime));
+    }
+    Ok(notes)
+}
+
+/// Benennt um und kopiert, wenn Quelle und Ziel auf verschiedenen Dateisystemen liegen
+/// (`$XDG_RUNTIME_DIR` ist meist ein tmpfs).
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)
+    } else {
+        fs::remove_file(from)
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Legt für alle Tests eines Laufs ein gemeinsames Verzeichnis fest, wie mit `--config-dir`.
+/// Die Tests trennen ihre Daten über eindeutige Programm-IDs und Dateinamen.
+#[cfg(test)]
+pub fn init_for_tests() -> PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("{}-test-{}", APP_NAME, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        init(Some(dir.clone())).expect("Testverzeichnis festlegen");
+        dir
+    })
+    .clone()
+}
@@ -0,0 +1,251 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{atomic_file, config_schema};
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{AudioProgramConfig, ManagedAudioProgram};
+use crate::midi_control::MidiConfig;
+
+/// Name des Unterverzeichnisses, in dem die benannten Projekte liegen.
+pub const PROJECTS_DIR: &str = "projects";
+const DEFAULT_PROJECT: &str = "default";
+
+/// Komplettes Studio-Setup in einer Datei: alle Programme mit ihren
+/// Verbindungsregeln (und damit den Use-Cases) sowie die MIDI-Zuordnungen.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Project {
+    pub name: String,
+    pub programs: Vec<AudioProgramConfig>,
+    #[serde(default)]
+    pub midi: MidiConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Vorhandene Programme bleiben erhalten, neue kommen hinzu.
+    Merge,
+    /// Das aktuelle Setup wird vollständig durch das importierte ersetzt.
+    Replace,
+}
+
+impl Project {
+    /// Sammelt das aktuelle Setup aus dem Konfigurationsverzeichnis.
+    pub fn from_current(name: &str) -> Result<Self> {
+        let (programs, errors) = ManagedAudioProgram::load_all();
+        let midi = MidiConfig::load()?;
+        // PID-Probleme und inhaltliche Fehler sind beim Export kein Problem, nicht ladbare Konfigurationen schon
+        Error::from_list(errors.into_iter().filter(|e| matches!(e, Error::LoadProgram { .. })).collect())?;
+        Ok(Self {
+            name: name.to_string(),
+            programs: programs.into_iter().map(|p| p.config).collect(),
+            midi,
+        })
+    }
+
+    /// Liest eine Projektdatei. Enthaltene Programmkonfigurationen älterer Versionen werden migriert,
+    /// doppelte Programmnamen und unbekannte Programmverweise führen zum Abbruch.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|source| Error::ConfigIo { path: path.to_path_buf(), source })?;
+        let mut value: serde_json::Value = config_schema::parse_json(&text, path).map_err(Error::ConfigParse)?;
+
+        let mut programs = Vec::new();
+        let mut issues = Vec::new();
+        if let Some(values) = value.get_mut("programs").and_then(|p| p.as_array_mut()) {
+            for program in values.drain(..) {
+                match config_schema::migrate_program_value(program, path) {
+                    Ok(config) => programs.push(config),
+                    Err(e) => issues.extend(e),
+                }
+            }
+        }
+        if !issues.is_empty() {
+            return Err(Error::ConfigParse(issues));
+        }
+
+        let mut project: Self = config_schema::from_value(value, path).map_err(Error::ConfigParse)?;
+        project.programs = programs;
+
+        let issues = config_schema::validate_setup(&project.programs, &project.midi, path);
+        if issues.is_empty() {
+            Ok(project)
+        } else {
+            Err(Error::ConfigInvalid(issues))
+        }
+    }
+
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|source| Error::Serialize { path: path.to_path_buf(), source })?;
+        atomic_file::write_atomic(path, &data)
+            .map_err(|source| Error::ConfigIo { path: path.to_path_buf(), source })?;
+        tracing::info!(project = %self.name, path = ?path, "Projekt exportiert");
+        Ok(())
+    }
+
+    /// Übernimmt das Projekt in das Konfigurationsverzeichnis.
+    /// Liefert im Erfolgsfall die Liste der Konflikte, die beim Zusammenführen aufgetreten sind.
+    /// Beim Ersetzen wird abgebrochen, solange ein Programm läuft, das dabei entfernt würde.
+    pub fn import(&self, mode: ImportMode) -> Result<Vec<String>> {
+        // Die IDs werden zu Verzeichnisnamen, vor dem ersten Schreibzugriff alle prüfen
+        if let Some(config) = self.programs.iter().find(|c| config_schema::check_program_name(&c.id).is_some()) {
+            return Err(Error::InvalidName(config.id.clone()));
+        }
+        let mut errors = Vec::new();
+        let mut conflicts: Vec<String> = self
+            .programs
+            .iter()
+            .flat_map(|config| config_schema::validate_program(config, Path::new(&self.name)))
+            .map(|issue| issue.to_string())
+            .collect();
+        let (existing, _) = ManagedAudioProgram::load_all();
+
+        match mode {
+            ImportMode::Replace => {
+                // Mit der Konfiguration verschwände auch die PID-Datei, das Programm liefe unkontrolliert weiter
+                let running: Vec<String> = existing
+                    .iter()
+                    .filter(|prog| !self.programs.iter().any(|c| c.id == prog.config.id) && prog.pid().is_some())
+                    .map(|prog| prog.config.program_name.clone())
+                    .collect();
+                if !running.is_empty() {
+                    return Err(Error::ProgramsRunning(running));
+                }
+                for prog in existing.iter() {
+                    if !self.programs.iter().any(|c| c.id == prog.config.id)
+                        && let Err(e) = prog.delete_config()
+                    {
+                        errors.push(e);
+                    }
+                }
+                for config in self.programs.iter() {
+                    if let Err(e) = Self::save_program(config) {
+                        errors.push(e);
+                    }
+                }
+                if let Err(e) = self.midi.save() {
+                    errors.push(e);
+                }
+            }
+            ImportMode::Merge => {
+                for config in self.programs.iter() {
+                    if existing.iter().any(|p| p.config.id == config.id) {
+                        conflicts.push(format!("Programm '{}' existiert bereits und wurde nicht übernommen.", config.program_name));
+                        continue;
+                    }
+                    if existing.iter().any(|p| p.config.program_name == config.program_name) {
+                        conflicts.push(format!("Ein anderes Programm heißt bereits '{}', '{}' wurde nicht übernommen.", config.program_name, config.id));
+                        continue;
+                    }
+                    if let Err(e) = Self::save_program(config) {
+                        errors.push(e);
+                    }
+                }
+
+                let mut midi = MidiConfig::load()?;
+                if midi.input_port.is_empty() {
+                    midi.input_port = self.midi.input_port.clone();
+                }
+                if midi.output_port.is_empty() {
+                    midi.output_port = self.midi.output_port.clone();
+                }
+                for mapping in self.midi.mappings.iter() {
+                    match midi.action_for(&mapping.trigger) {
+                        Some(action) if action != &mapping.action => {
+                            conflicts.push(format!("MIDI {} ist bereits mit '{}' belegt, '{}' wurde nicht übernommen.", mapping.trigger, action, mapping.action));
+                        }
+                        Some(_) => {}
+                        None => midi.mappings.push(mapping.clone()),
+                    }
+                }
+                if let Err(e) = midi.save() {
+                    errors.push(e);
+                }
+            }
+        }
+
+        Error::from_list(errors)?;
+        tracing::info!(project = %self.name, mode = ?mode, conflicts = conflicts.len(), "Projekt importiert");
+        Ok(conflicts)
+    }
+
+    fn save_program(config: &AudioProgramConfig) -> Result<()> {
+        let mut prog = ManagedAudioProgram::from_config(config.clone());
+        prog.save_config()
+    }
+
+    pub fn projects_dir() -> PathBuf {
+        ManagedAudioProgram::config_dir().join(PROJECTS_DIR)
+    }
+
+    fn project_path(name: &str) -> PathBuf {
+        Self::projects_dir().join(format!("{}.json", name))
+    }
+
+    /// Namen aller gespeicherten Projekte, alphabetisch sortiert. Das aktive Projekt ist immer enthalten.
+    pub fn list() -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(Self::projects_dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let path = entry.path();
+                        if path.extension().is_some_and(|ext| ext == "json") {
+                            path.file_stem().map(|s| s.to_string_lossy().to_string())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let active = Self::active();
+        if !names.contains(&active) {
+            names.push(active);
+        }
+        names.sort();
+        names
+    }
+
+    pub fn active() -> String {
+        fs::read_to_string(Self::projects_dir().join("active"))
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| DEFAULT_PROJECT.to_string())
+    }
+
+    fn set_active(name: &str) -> Result<()> {
+        let path = Self::projects_dir().join("active");
+        atomic_file::write_atomic(&path, name.as_bytes()).map_err(|source| Error::ConfigIo { path, source })
+    }
+
+    /// Sichert das aktuelle Setup unter dem Namen des aktiven Projekts und lädt das angegebene.
+    /// Existiert das Projekt noch nicht, wird es als leeres Setup angelegt. Laufende Programme,
+    /// die im neuen Projekt fehlen, müssen vorher beendet werden.
+    pub fn switch_to(name: &str) -> Result<()> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(Error::InvalidName(name.to_string()));
+        }
+        let active = Self::active();
+        if name == active {
+            return Ok(());
+        }
+
+        Self::from_current(&active)?.export(&Self::project_path(&active))?;
+
+        let path = Self::project_path(name);
+        let project = if path.exists() {
+            Self::read(&path)?
+        } else {
+            Self {
+                name: name.to_string(),
+                programs: Vec::new(),
+                midi: MidiConfig::default(),
+            }
+        };
+        project.import(ImportMode::Replace)?;
+        Self::set_active(name)?;
+        tracing::info!(project = %name, previous = %active, "Projekt gewechselt");
+        Ok(())
+    }
+}
@@ -0,0 +1,734 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use jack::{AudioIn, Client, ClientOptions, Control, Port, ProcessHandler, ProcessScope, RingBuffer, RingBufferReader, RingBufferWriter};
+use sysinfo::Disks;
+
+use crate::error::{Error, Result};
+use crate::managed_audio_program::ManagedAudioProgram;
+use crate::{atomic_file, config_schema, meter};
+
+/// Name des JACK-Clients mit den Aufnahmeeingängen.
+const RECORDER_CLIENT: &str = "jackstreamingmanager-recorder";
+
+/// Puffer zwischen Audio-Thread und Schreib-Thread (Sekunden).
+const RING_SECONDS: usize = 4;
+
+/// So oft prüft der Schreib-Thread während einer Aufnahme den freien Speicher.
+const DISK_CHECK: Duration = Duration::from_secs(5);
+
+/// Größter Vollaussteuerungswert bei 24 Bit.
+const I24_MAX: f32 = 8_388_607.0;
+
+/// Bytes je Megabyte, für die Teilung wie für den freien Speicher.
+const MB: u64 = 1_048_576;
+
+/// Eine WAV-Datei darf 4 GiB nicht erreichen (32-Bit-Längenfelder). Unabhängig von den
+/// Einstellungen wird vorher eine neue Datei begonnen.
+const MAX_FILE_BYTES: u64 = 4_000_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileLayout {
+    /// Eine Mono-Datei je Port
+    #[default]
+    PerPort,
+    /// Eine Datei mit allen Ports als Kanälen
+    Interleaved,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    #[default]
+    Wav,
+    /// Wird als WAV geschrieben und nach jedem Abschnitt mit dem Werkzeug `flac` umgewandelt
+    Flac,
+}
+
+/// Einstellungen der Aufnahme.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecorderConfig {
+    pub ports: Vec<String>,
+    #[serde(default)]
+    pub layout: FileLayout,
+    #[serde(default)]
+    pub format: AudioFormat,
+    /// So viele Sekunden vor dem Start werden mit aufgenommen
+    #[serde(default)]
+    pub pre_roll_s: u64,
+    /// Neue Datei nach so vielen Minuten bzw. Megabyte (1 MB = 1 048 576 Byte), 0 = nicht teilen.
+    /// Vor der 4-GiB-Grenze von WAV wird in jedem Fall geteilt.
+    #[serde(default)]
+    pub split_minutes: u64,
+    #[serde(default)]
+    pub split_mb: u64,
+    /// Unterhalb dieses freien Speichers wird nicht aufgenommen bzw. die Aufnahme beendet
+    #[serde(default)]
+    pub min_free_mb: u64,
+    /// Verzeichnis für die Sendungen, leer = Musikverzeichnis des Benutzers
+    #[serde(default)]
+    pub dir: String,
+    /// Use-Cases, bei deren Start automatisch aufgenommen wird
+    #[serde(default)]
+    pub scenarios: Vec<String>,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            ports: Vec::new(),
+            layout: FileLayout::default(),
+            format: AudioFormat::default(),
+            pre_roll_s: 5,
+            split_minutes: 60,
+            split_mb: 0,
+            min_free_mb: 1024,
+            dir: String::new(),
+            scenarios: Vec::new(),
+        }
+    }
+}
+
+impl RecorderConfig {
+    fn config_path() -> PathBuf {
+        ManagedAudioProgram::config_dir().join("recorder.json")
+    }
+
+    /// Lädt die Einstellungen. Fehlt die Datei, gelten die Vorgaben ohne Ports.
+    pub fn load() -> Result<Self> {
+        let path = Self::config_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path).map_err(|source| Error::ConfigIo { path: path.clone(), source })?;
+        config_schema::parse_json(&text, &path).map_err(Error::ConfigParse)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::config_path();
+        let data = serde_json::to_vec_pretty(self).map_err(|source| Error::Serialize { path: path.clone(), source })?;
+        atomic_file::write_atomic(&path, &data).map_err(|source| Error::ConfigIo { path, source })
+    }
+
+    pub fn recordings_dir(&self) -> PathBuf {
+        if self.dir.is_empty() {
+            dirs::audio_dir()
+                .or_else(dirs::home_dir)
+                .unwrap_or_default()
+                .join("jackstreamingmanager")
+        } else {
+            PathBuf::from(&self.dir)
+        }
+    }
+}
+
+/// Zustand der Aufnahme für Anzeige und Abfrage.
+#[derive(Debug, Clone, Default)]
+pub struct RecorderStatus {
+    pub recording: bool,
+    pub show: String,
+    /// Verzeichnis der laufenden oder zuletzt beendeten Sendung
+    pub dir: Option<PathBuf>,
+    /// Aufgenommene Dauer einschließlich Pre-Roll (Sekunden)
+    pub seconds: u64,
+    pub files: usize,
+    /// Verworfene Frames, weil der Schreib-Thread nicht hinterherkam
+    pub dropped_frames: u64,
+    pub free_mb: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl fmt::Display for RecorderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.recording {
+            let s = self.seconds;
+            write!(f, "Aufnahme '{}' läuft: {:02}:{:02}:{:02}, {} Dateien", self.show, s / 3600, s / 60 % 60, s % 60, self.files)?;
+        } else {
+            write!(f, "Bereit")?;
+        }
+        if let Some(free_mb) = self.free_mb {
+            write!(f, ", {:.1} GB frei", free_mb as f64 / 1024.0)?;
+        }
+        if self.dropped_frames > 0 {
+            write!(f, ", {} Frames verloren", self.dropped_frames)?;
+        }
+        Ok(())
+    }
+}
+
+enum RecorderCommand {
+    Record(String),
+    Stop,
+    Quit,
+}
+
+/// Schreibt die Samples aller Ports verschachtelt in den Ringpuffer.
+struct Process {
+    inputs: Vec<Port<AudioIn>>,
+    ring: RingBufferWriter,
+    /// Vorab angelegt, damit im Audio-Thread nichts alloziert wird
+    scratch: Vec<u8>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        let frames = ps.n_frames() as usize;
+        let bytes = frames * self.inputs.len() * 4;
+        // Passt der Block nicht ganz hinein, wird er verworfen, damit die Kanäle nicht verrutschen
+        if bytes > self.scratch.len() || self.ring.space() < bytes {
+            self.dropped.fetch_add(frames as u64, Ordering::Relaxed);
+            return Control::Continue;
+        }
+        let channels = self.inputs.len();
+        for (channel, input) in self.inputs.iter().enumerate() {
+            for (frame, sample) in input.as_slice(ps).iter().enumerate() {
+                let offset = (frame * channels + channel) * 4;
+                self.scratch[offset..offset + 4].copy_from_slice(&sample.to_ne_bytes());
+            }
+        }
+        self.ring.write_buffer(&self.scratch[..bytes]);
+        Control::Continue
+    }
+}
+
+/// Hält einen eigenen JACK-Client mit den ausgewählten Ports bereit, solange die Instanz lebt.
+/// Ohne laufende Aufnahme werden nur die letzten Sekunden für den Pre-Roll aufbewahrt.
+pub struct Recorder {
+    client: jack::AsyncClient<(), Process>,
+    ports: Vec<String>,
+    commands: mpsc::Sender<RecorderCommand>,
+    status: Arc<Mutex<RecorderStatus>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    pub fn start(config: &RecorderConfig) -> Result<Self> {
+        let jack_error = |action: &str| {
+            let action = action.to_string();
+            move |source| Error::JackClient { action, source }
+        };
+        let (client, _) = Client::new(RECORDER_CLIENT, ClientOptions::NO_START_SERVER)
+            .map_err(jack_error("Aufnahme anmelden"))?;
+        let mut inputs = Vec::new();
+        for index in 0..config.ports.len() {
+            inputs.push(client.register_port(&input_name(index), AudioIn).map_err(jack_error("Aufnahmeeingang anlegen"))?);
+        }
+        let rate = client.sample_rate();
+        let channels = config.ports.len();
+        let (reader, writer) = RingBuffer::new(rate * channels * 4 * RING_SECONDS)
+            .map_err(jack_error("Aufnahmepuffer anlegen"))?
+            .into_reader_writer();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let process = Process {
+            inputs,
+            ring: writer,
+            // Reicht für die größte Puffergröße, die JACK zulässt
+            scratch: vec![0; 8192 * channels * 4],
+            dropped: dropped.clone(),
+        };
+        let client = client.activate_async((), process).map_err(jack_error("Aufnahme aktivieren"))?;
+
+        let (commands, queue) = mpsc::channel();
+        let status = Arc::new(Mutex::new(RecorderStatus::default()));
+        let mut disk = DiskWriter {
+            config: config.clone(),
+            rate: rate as u32,
+            channels,
+            reader,
+            queue,
+            status: status.clone(),
+            dropped,
+            pre_roll: VecDeque::new(),
+            session: None,
+        };
+        let writer = std::thread::spawn(move || disk.run());
+        tracing::info!(ports = channels, "Aufnahme bereit");
+        Ok(Self { client, ports: config.ports.clone(), commands, status, writer: Some(writer) })
+    }
+
+    /// Legt die Aufnahmeeingänge auf die ausgewählten Ports bzw. deren Quellen.
+    pub fn sync(&self) -> Vec<Error> {
+        let client = self.client.as_client();
+        let mut errors = Vec::new();
+        for (index, port) in self.ports.iter().enumerate() {
+            let own = format!("{}:{}", client.name(), input_name(index));
+            meter::follow(client, &own, port, &mut errors);
+        }
+        errors
+    }
+
+    /// Beginnt eine Aufnahme für `show` in einem eigenen Verzeichnis, einschließlich Pre-Roll.
+    /// Läuft bereits eine, wird sie beendet.
+    pub fn record(&self, show: &str) {
+        let _ = self.commands.send(RecorderCommand::Record(show.to_string()));
+    }
+
+    pub fn stop(&self) {
+        let _ = self.commands.send(RecorderCommand::Stop);
+    }
+
+    pub fn status(&self) -> RecorderStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Beendet eine laufende Aufnahme, wartet, bis alle Dateien geschrieben sind, und liefert den Endstand.
+    pub fn close(mut self) -> RecorderStatus {
+        self.shutdown();
+        self.status()
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.commands.send(RecorderCommand::Quit);
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Drop for Recorder {
+    /// Schließt die Dateien einer laufenden Aufnahme ordentlich ab.
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[derive(serde::Serialize)]
+struct FileMeta {
+    file: String,
+    ports: Vec<String>,
+    /// Zeitpunkt des ersten Samples
+    started: String,
+    frames: u64,
+}
+
+/// Inhalt der recording.json. Die FLAC-Umwandlung läuft im Hintergrund und trägt den neuen
+/// Dateinamen erst ein, wenn sie gelungen ist; deshalb teilen sich beide Threads die Instanz.
+#[derive(serde::Serialize)]
+struct SessionMeta {
+    show: String,
+    started: String,
+    stopped: Option<String>,
+    sample_rate: u32,
+    pre_roll_s: u64,
+    layout: FileLayout,
+    format: AudioFormat,
+    ports: Vec<String>,
+    files: Vec<FileMeta>,
+    dropped_frames: u64,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl SessionMeta {
+    fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).map_err(|source| Error::Serialize { path: self.path.clone(), source })?;
+        atomic_file::write_atomic(&self.path, &data).map_err(|source| Error::ConfigIo { path: self.path.clone(), source })
+    }
+
+    /// Trägt nach gelungener Umwandlung die FLAC-Datei statt der WAV-Datei ein. `false`, wenn
+    /// die WAV-Datei nicht eingetragen ist.
+    fn converted(&mut self, wav: &Path) -> bool {
+        let name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let (wav_name, flac_name) = (name(wav), name(&wav.with_extension("flac")));
+        match self.files.iter_mut().find(|f| f.file == wav_name) {
+            Some(file) => {
+                file.file = flac_name;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Eine laufende Aufnahme: Verzeichnis, aktueller Abschnitt und bisher geschriebene Dateien.
+struct Session {
+    show: String,
+    dir: PathBuf,
+    started: chrono::DateTime<chrono::Local>,
+    segment: usize,
+    writers: Vec<(PathBuf, hound::WavWriter<BufWriter<File>>)>,
+    segment_started: chrono::DateTime<chrono::Local>,
+    segment_frames: u64,
+    total_frames: u64,
+    meta: Arc<Mutex<SessionMeta>>,
+    dropped_at_start: u64,
+}
+
+struct DiskWriter {
+    config: RecorderConfig,
+    rate: u32,
+    channels: usize,
+    reader: RingBufferReader,
+    queue: mpsc::Receiver<RecorderCommand>,
+    status: Arc<Mutex<RecorderStatus>>,
+    dropped: Arc<AtomicU64>,
+    /// Verschachtelte Samples der letzten `pre_roll_s` Sekunden
+    pre_roll: VecDeque<f32>,
+    session: Option<Session>,
+}
+
+impl DiskWriter {
+    fn run(&mut self) {
+        let frame_bytes = self.channels * 4;
+        let mut bytes = vec![0u8; frame_bytes * self.rate as usize / 10];
+        let mut last_check = Instant::now();
+        loop {
+            match self.queue.try_recv() {
+                Ok(RecorderCommand::Record(show)) => {
+                    self.finish();
+                    if let Err(e) = self.begin(&show) {
+                        self.fail(e);
+                    }
+                }
+                Ok(RecorderCommand::Stop) => self.finish(),
+                Ok(RecorderCommand::Quit) | Err(mpsc::TryRecvError::Disconnected) => {
+                    self.finish();
+                    return;
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+
+            let available = self.reader.space().min(bytes.len()) / frame_bytes * frame_bytes;
+            if available == 0 {
+                std::thread::sleep(Duration::from_millis(20));
+                continue;
+            }
+            let read = self.reader.read_buffer(&mut bytes[..available]);
+            let samples: Vec<f32> = bytes[..read]
+                .chunks_exact(4)
+                .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            if self.session.is_some() {
+                if let Err(e) = self.write(&samples) {
+                    self.fail(e);
+                }
+                if last_check.elapsed() >= DISK_CHECK {
+                    last_check = Instant::now();
+                    if let Err(e) = self.check_disk() {
+                        self.fail(e);
+                    }
+                }
+            } else {
+                self.pre_roll.extend(samples);
+                let keep = self.config.pre_roll_s as usize * self.rate as usize * self.channels;
+                let excess = self.pre_roll.len().saturating_sub(keep);
+                self.pre_roll.drain(..excess);
+            }
+        }
+    }
+
+    fn begin(&mut self, show: &str) -> Result<()> {
+        let root = self.config.recordings_dir();
+        fs::create_dir_all(&root).map_err(|source| Error::ConfigIo { path: root.clone(), source })?;
+        let pre_roll_frames = (self.pre_roll.len() / self.channels.max(1)) as i64;
+        let started = chrono::Local::now() - chrono::Duration::milliseconds(pre_roll_frames * 1000 / i64::from(self.rate));
+        let safe: String = show.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        let dir = root.join(format!("{}_{}", started.format("%Y-%m-%d_%H-%M-%S"), safe));
+        fs::create_dir_all(&dir).map_err(|source| Error::ConfigIo { path: dir.clone(), source })?;
+        let meta = SessionMeta {
+            show: show.to_string(),
+            started: started.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            stopped: None,
+            sample_rate: self.rate,
+            pre_roll_s: self.config.pre_roll_s,
+            layout: self.config.layout,
+            format: self.config.format,
+            ports: self.config.ports.clone(),
+            files: Vec::new(),
+            dropped_frames: 0,
+            path: dir.join("recording.json"),
+        };
+        self.session = Some(Session {
+            show: show.to_string(),
+            dir: dir.clone(),
+            started,
+            segment: 0,
+            writers: Vec::new(),
+            segment_started: started,
+            segment_frames: 0,
+            total_frames: 0,
+            meta: Arc::new(Mutex::new(meta)),
+            dropped_at_start: self.dropped.load(Ordering::Relaxed),
+        });
+        self.check_disk()?;
+        self.open_segment()?;
+        {
+            let mut status = self.status.lock().unwrap();
+            status.recording = true;
+            status.show = show.to_string();
+            status.dir = Some(dir.clone());
+            status.error = None;
+        }
+        tracing::info!(show, dir = %dir.display(), "Aufnahme gestartet");
+        let pre_roll: Vec<f32> = self.pre_roll.drain(..).collect();
+        self.write(&pre_roll)
+    }
+
+    fn open_segment(&mut self) -> Result<()> {
+        let Some(session) = self.session.as_mut() else { return Ok(()) };
+        session.segment += 1;
+        session.segment_frames = 0;
+        session.segment_started = session.started + chrono::Duration::milliseconds((session.total_frames * 1000 / u64::from(self.rate)) as i64);
+        let names: Vec<(String, u16)> = match self.config.layout {
+            FileLayout::PerPort => self.config.ports.iter().map(|port| (port.clone(), 1)).collect(),
+            FileLayout::Interleaved => vec![("alle".to_string(), self.channels as u16)],
+        };
+        for (name, channels) in names {
+            let safe: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+            let path = session.dir.join(format!("{}_{:03}.wav", safe, session.segment));
+            let spec = hound::WavSpec { channels, sample_rate: self.rate, bits_per_sample: 24, sample_format: hound::SampleFormat::Int };
+            let writer = hound::WavWriter::create(&path, spec).map_err(|source| Error::Recording { path: path.clone(), source })?;
+            session.writers.push((path, writer));
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        let channels = self.channels;
+        let Some(session) = self.session.as_mut() else { return Ok(()) };
+        for frame in samples.chunks_exact(channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let writer = match self.config.layout {
+                    FileLayout::PerPort => &mut session.writers[channel],
+                    FileLayout::Interleaved => &mut session.writers[0],
+                };
+                let value = (sample.clamp(-1.0, 1.0) * I24_MAX) as i32;
+                writer.1.write_sample(value).map_err(|source| Error::Recording { path: writer.0.clone(), source })?;
+            }
+        }
+        let frames = (samples.len() / channels) as u64;
+        session.segment_frames += frames;
+        session.total_frames += frames;
+        let split = needs_split(&self.config, self.rate, channels, session.segment_frames);
+        {
+            let files = session.meta.lock().unwrap().files.len();
+            let mut status = self.status.lock().unwrap();
+            status.seconds = session.total_frames / u64::from(self.rate);
+            status.files = files + session.writers.len();
+            status.dropped_frames = self.dropped.load(Ordering::Relaxed) - session.dropped_at_start;
+        }
+        if split {
+            self.close_segment()?;
+            self.open_segment()?;
+        }
+        Ok(())
+    }
+
+    /// Schließt die Dateien des aktuellen Abschnitts ab und schreibt die Metadaten.
+    fn close_segment(&mut self) -> Result<()> {
+        let Some(session) = self.session.as_mut() else { return Ok(()) };
+        let mut errors = Vec::new();
+        for (index, (path, writer)) in session.writers.drain(..).enumerate() {
+            if let Err(source) = writer.finalize() {
+                errors.push(Error::Recording { path: path.clone(), source });
+            }
+            let ports = match self.config.layout {
+                FileLayout::PerPort => vec![self.config.ports[index].clone()],
+                FileLayout::Interleaved => self.config.ports.clone(),
+            };
+            session.meta.lock().unwrap().files.push(FileMeta {
+                file: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                ports,
+                started: session.segment_started.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                frames: session.segment_frames,
+            });
+            if self.config.format == AudioFormat::Flac {
+                encode_flac(path, session.meta.clone());
+            }
+        }
+        if let Err(e) = self.write_metadata(None) {
+            errors.push(e);
+        }
+        Error::from_list(errors)
+    }
+
+    fn write_metadata(&self, stopped: Option<chrono::DateTime<chrono::Local>>) -> Result<()> {
+        let Some(session) = self.session.as_ref() else { return Ok(()) };
+        let mut meta = session.meta.lock().unwrap();
+        if let Some(stopped) = stopped {
+            meta.stopped = Some(stopped.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+        }
+        meta.dropped_frames = self.dropped.load(Ordering::Relaxed) - session.dropped_at_start;
+        meta.save()
+    }
+
+    fn finish(&mut self) {
+        if self.session.is_none() {
+            return;
+        }
+        if let Err(e) = self.close_segment().and_then(|()| self.write_metadata(Some(chrono::Local::now()))) {
+            tracing::error!("{}", e.report());
+            self.status.lock().unwrap().error = Some(e.to_string());
+        }
+        if let Some(session) = self.session.take() {
+            tracing::info!(show = %session.show, seconds = session.total_frames / u64::from(self.rate), "Aufnahme beendet");
+        }
+        self.status.lock().unwrap().recording = false;
+    }
+
+    /// Beendet die Aufnahme mit einer Fehlermeldung.
+    fn fail(&mut self, e: Error) {
+        tracing::error!("{}", e.report());
+        self.finish();
+        self.status.lock().unwrap().error = Some(e.to_string());
+    }
+
+    fn check_disk(&mut self) -> Result<()> {
+        let Some(session) = self.session.as_ref() else { return Ok(()) };
+        let free_mb = free_space(&session.dir).map(|bytes| bytes / MB);
+        self.status.lock().unwrap().free_mb = free_mb;
+        match free_mb {
+            Some(free_mb) if free_mb < self.config.min_free_mb => Err(Error::DiskFull { path: session.dir.clone(), free_mb }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Ob ein Abschnitt mit `frames` Frames je Kanal abgeschlossen werden muss: nach `split_minutes`,
+/// nach `split_mb` über alle Dateien des Abschnitts und in jedem Fall vor `MAX_FILE_BYTES` je Datei.
+fn needs_split(config: &RecorderConfig, rate: u32, channels: usize, frames: u64) -> bool {
+    let segment_bytes = frames * channels as u64 * 3;
+    let file_bytes = match config.layout {
+        FileLayout::PerPort => frames * 3,
+        FileLayout::Interleaved => segment_bytes,
+    };
+    (config.split_minutes > 0 && frames >= config.split_minutes * 60 * u64::from(rate))
+        || (config.split_mb > 0 && segment_bytes >= config.split_mb * MB)
+        || file_bytes >= MAX_FILE_BYTES
+}
+
+/// Freier Speicher des Dateisystems, auf dem `dir` liegt.
+fn free_space(dir: &Path) -> Option<u64> {
+    let dir = dir.canonicalize().ok()?;
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|disk| dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+/// Wandelt einen abgeschlossenen Abschnitt im Hintergrund in FLAC um. Erst wenn das gelungen
+/// ist, steht der neue Name in den Metadaten; sonst bleibt die WAV-Datei eingetragen und erhalten.
+fn encode_flac(wav: PathBuf, meta: Arc<Mutex<SessionMeta>>) {
+    std::thread::spawn(move || {
+        let result = std::process::Command::new("flac")
+            .args(["--silent", "--best", "--delete-input-file"])
+            .arg(&wav)
+            .status();
+        match result {
+            Ok(status) if status.success() => {
+                let mut meta = meta.lock().unwrap();
+                if meta.converted(&wav)
+                    && let Err(e) = meta.save()
+                {
+                    tracing::error!("{}", e.report());
+                }
+            }
+            Ok(status) => tracing::error!(file = %wav.display(), %status, "FLAC-Umwandlung fehlgeschlagen"),
+            Err(e) => tracing::error!(file = %wav.display(), "flac konnte nicht gestartet werden: {}", e),
+        }
+    });
+}
+
+fn input_name(index: usize) -> String {
+    format!("in_{}", index + 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(layout: FileLayout, split_minutes: u64, split_mb: u64) -> RecorderConfig {
+        RecorderConfig { layout, split_minutes, split_mb, ..RecorderConfig::default() }
+    }
+
+    #[test]
+    fn splits_after_minutes() {
+        let config = config(FileLayout::PerPort, 1, 0);
+        assert!(!needs_split(&config, 48_000, 2, 60 * 48_000 - 1));
+        assert!(needs_split(&config, 48_000, 2, 60 * 48_000));
+    }
+
+    #[test]
+    fn split_mb_counts_all_files_in_mib() {
+        // 2 Kanäle zu 3 Byte: gezählt wird 1 MiB über alle Dateien, egal wie die Kanäle verteilt sind
+        let frames = MB.div_ceil(6);
+        for layout in [FileLayout::PerPort, FileLayout::Interleaved] {
+            let config = config(layout, 0, 1);
+            assert!(!needs_split(&config, 48_000, 2, frames - 1));
+            assert!(needs_split(&config, 48_000, 2, frames));
+        }
+    }
+
+    #[test]
+    fn splits_before_wav_limit_without_settings() {
+        // Je Datei ein Kanal: die Grenze gilt für die Datei, nicht für den Abschnitt
+        let per_port = config(FileLayout::PerPort, 0, 0);
+        let frames = MAX_FILE_BYTES.div_ceil(3);
+        assert!(!needs_split(&per_port, 48_000, 8, frames - 1));
+        assert!(needs_split(&per_port, 48_000, 8, frames));
+        let interleaved = config(FileLayout::Interleaved, 0, 0);
+        let frames = MAX_FILE_BYTES.div_ceil(8 * 3);
+        assert!(!needs_split(&interleaved, 48_000, 8, frames - 1));
+        assert!(needs_split(&interleaved, 48_000, 8, frames));
+    }
+
+    #[test]
+    fn zero_disables_split() {
+        // Zwei Stunden je Port bleiben unter der Grenze von WAV
+        let config = config(FileLayout::PerPort, 0, 0);
+        assert!(!needs_split(&config, 48_000, 2, 2 * 60 * 60 * 48_000));
+    }
+
+    fn meta(files: &[&str]) -> SessionMeta {
+        SessionMeta {
+            show: "Sendung".to_string(),
+            started: String::new(),
+            stopped: None,
+            sample_rate: 48_000,
+            pre_roll_s: 0,
+            layout: FileLayout::PerPort,
+            format: AudioFormat::Flac,
+            ports: Vec::new(),
+            files: files
+                .iter()
+                .map(|file| FileMeta { file: file.to_string(), ports: Vec::new(), started: String::new(), frames: 0 })
+                .collect(),
+            dropped_frames: 0,
+            path: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn flac_name_is_recorded_after_conversion() {
+        // Bis zur Umwandlung steht die WAV-Datei in den Metadaten
+        let mut meta = meta(&["in_1_001.wav", "in_2_001.wav"]);
+        assert!(meta.converted(Path::new("/tmp/sendung/in_2_001.wav")));
+        let files: Vec<&str> = meta.files.iter().map(|f| f.file.as_str()).collect();
+        assert_eq!(files, ["in_1_001.wav", "in_2_001.flac"]);
+    }
+
+    #[test]
+    fn unknown_file_is_not_renamed() {
+        let mut meta = meta(&["in_1_001.wav"]);
+        assert!(!meta.converted(Path::new("/tmp/sendung/in_1_002.wav")));
+        assert_eq!(meta.files[0].file, "in_1_001.wav");
+    }
+}
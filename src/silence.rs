@@ -0,0 +1,285 @@
+use std::{
+    fmt,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use crate::error::{Error, Result};
+use crate::meter::Level;
+
+/// So lange muss nach einer Stille wieder Signal anliegen, bevor sie als beendet gilt.
+const RECOVERY: Duration = Duration::from_secs(2);
+
+/// Meldet, wenn an einem Port länger als `duration_s` nichts über `threshold_db` ankommt.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SilenceDetector {
+    pub port: String,
+    /// RMS-Pegel in dBFS, unterhalb dessen das Signal als still gilt
+    pub threshold_db: f32,
+    /// Sekunden
+    pub duration_s: f32,
+    /// In der Oberfläche melden
+    #[serde(default)]
+    pub alert: bool,
+    /// Wird bei Beginn und Ende der Stille über `sh -c` ausgeführt. Port und Ereignis stehen
+    /// in `JACKSTREAMINGMANAGER_SILENCE_PORT` und `JACKSTREAMINGMANAGER_SILENCE_EVENT` (start/end).
+    #[serde(default)]
+    pub hook: String,
+    /// Use-Case, auf den bei Stille umgeschaltet wird, z.B. eine Notfall-Playlist
+    #[serde(default)]
+    pub fallback: String,
+    /// Kommt das Signal zurück, wieder auf den Use-Case von vorher schalten
+    #[serde(default)]
+    pub switch_back: bool,
+}
+
+impl fmt::Display for SilenceDetector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} unter {:.0} dBFS für {:.0} s", self.port, self.threshold_db, self.duration_s)?;
+        let mut actions = Vec::new();
+        if self.alert {
+            actions.push("Meldung".to_string());
+        }
+        if !self.hook.is_empty() {
+            actions.push(format!("Skript '{}'", self.hook));
+        }
+        if !self.fallback.is_empty() {
+            let back = if self.switch_back { " und zurück" } else { "" };
+            actions.push(format!("auf '{}'{}", self.fallback, back));
+        }
+        if !actions.is_empty() {
+            write!(f, " -> {}", actions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SilenceState {
+    Signal,
+    /// Still seit so vielen Sekunden, aber noch nicht lange genug
+    Silent(u64),
+    /// Ausgelöst, still seit so vielen Sekunden
+    Triggered(u64),
+}
+
+impl fmt::Display for SilenceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SilenceState::Signal => write!(f, "Signal"),
+            SilenceState::Silent(seconds) => write!(f, "still seit {} s", seconds),
+            SilenceState::Triggered(seconds) => write!(f, "STILLE seit {} s", seconds),
+        }
+    }
+}
+
+/// Beginn bzw. Ende einer Stille, mit dem Index des Detektors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SilenceEvent {
+    Started(usize),
+    Ended(usize),
+}
+
+#[derive(Debug, Default)]
+struct Detection {
+    silent_since: Option<Instant>,
+    signal_since: Option<Instant>,
+    triggered: bool,
+    /// Use-Case, der vor dem Umschalten auf den Ausweich-Use-Case aktiv war
+    resume: Option<String>,
+}
+
+/// Wertet die gemessenen Pegel für alle Detektoren aus. Hängt nur von den übergebenen
+/// Pegeln und Zeitpunkten ab, daher lässt sich der Ablauf auch mit erzeugten Werten prüfen.
+#[derive(Debug, Default)]
+pub struct SilenceWatch {
+    detections: Vec<Detection>,
+}
+
+impl SilenceWatch {
+    /// Ein Port ohne Messwert (fehlt oder liefert nichts) gilt als still.
+    pub fn update(
+        &mut self,
+        detectors: &[SilenceDetector],
+        levels: &[(String, Option<Level>)],
+        now: Instant,
+    ) -> Vec<SilenceEvent> {
+        self.detections.resize_with(detectors.len(), Detection::default);
+        let mut events = Vec::new();
+        for (index, (detector, detection)) in detectors.iter().zip(self.detections.iter_mut()).enumerate() {
+            let silent = levels
+                .iter()
+                .find(|(port, _)| *port == detector.port)
+                .and_then(|(_, level)| *level)
+                .is_none_or(|level| level.rms < detector.threshold_db);
+            if silent {
+                detection.signal_since = None;
+                let since = *detection.silent_since.get_or_insert(now);
+                if !detection.triggered && now.duration_since(since).as_secs_f32() >= detector.duration_s {
+                    detection.triggered = true;
+                    events.push(SilenceEvent::Started(index));
+                }
+            } else {
+                let since = *detection.signal_since.get_or_insert(now);
+                if !detection.triggered {
+                    detection.silent_since = None;
+                } else if now.duration_since(since) >= RECOVERY {
+                    detection.triggered = false;
+                    detection.silent_since = None;
+                    events.push(SilenceEvent::Ended(index));
+                }
+            }
+        }
+        events
+    }
+
+    pub fn state(&self, index: usize, now: Instant) -> SilenceState {
+        let Some(detection) = self.detections.get(index) else { return SilenceState::Signal };
+        match detection.silent_since {
+            Some(since) if detection.triggered => SilenceState::Triggered(now.duration_since(since).as_secs()),
+            Some(since) => SilenceState::Silent(now.duration_since(since).as_secs()),
+            None => SilenceState::Signal,
+        }
+    }
+
+    pub fn any_triggered(&self) -> bool {
+        self.detections.iter().any(|d| d.triggered)
+    }
+
+    /// Merkt sich den Use-Case, zu dem nach der Stille zurückgeschaltet wird.
+    pub fn set_resume(&mut self, index: usize, use_case: Option<String>) {
+        if let Some(detection) = self.detections.get_mut(index) {
+            detection.resume = use_case;
+        }
+    }
+
+    pub fn take_resume(&mut self, index: usize) -> Option<String> {
+        self.detections.get_mut(index).and_then(|d| d.resume.take())
+    }
+
+    /// Muss zusammen mit dem Detektor entfernt werden, damit die Indizes passen.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.detections.len() {
+            self.detections.remove(index);
+        }
+    }
+}
+
+/// Startet das Skript des Detektors, ohne auf sein Ende zu warten.
+pub fn run_hook(detector: &SilenceDetector, event: &str) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&detector.hook)
+        .env("JACKSTREAMINGMANAGER_SILENCE_PORT", &detector.port)
+        .env("JACKSTREAMINGMANAGER_SILENCE_EVENT", event)
+        .spawn()
+        .map_err(|source| Error::Spawn { command: detector.hook.clone(), source })?;
+    // Beendete Skripte abholen, damit keine Zombies zurückbleiben
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(port: &str) -> SilenceDetector {
+        SilenceDetector {
+            port: port.to_string(),
+            threshold_db: -50.0,
+            duration_s: 5.0,
+            alert: true,
+            hook: String::new(),
+            fallback: String::new(),
+            switch_back: false,
+        }
+    }
+
+    fn level(port: &str, rms: f32) -> (String, Option<Level>) {
+        (port.to_string(), Some(Level { peak: rms + 3.0, rms, true_peak: None }))
+    }
+
+    fn at(start: Instant, seconds: f32) -> Instant {
+        start + Duration::from_secs_f32(seconds)
+    }
+
+    #[test]
+    fn triggers_after_duration() {
+        let detectors = [detector("a")];
+        let mut watch = SilenceWatch::default();
+        let start = Instant::now();
+        assert!(watch.update(&detectors, &[level("a", -70.0)], start).is_empty());
+        assert!(watch.update(&detectors, &[level("a", -70.0)], at(start, 4.9)).is_empty());
+        assert_eq!(watch.state(0, at(start, 4.9)), SilenceState::Silent(4));
+        assert_eq!(watch.update(&detectors, &[level("a", -70.0)], at(start, 5.0)), vec![SilenceEvent::Started(0)]);
+        // Nur einmal auslösen
+        assert!(watch.update(&detectors, &[level("a", -70.0)], at(start, 6.0)).is_empty());
+        assert_eq!(watch.state(0, at(start, 6.0)), SilenceState::Triggered(6));
+        assert!(watch.any_triggered());
+    }
+
+    #[test]
+    fn short_dips_do_not_trigger() {
+        let detectors = [detector("a")];
+        let mut watch = SilenceWatch::default();
+        let start = Instant::now();
+        for second in 0..20 {
+            // Vier Sekunden still, eine Sekunde Signal
+            let rms = if second % 5 == 4 { -20.0 } else { -70.0 };
+            assert!(watch.update(&detectors, &[level("a", rms)], at(start, second as f32)).is_empty());
+        }
+        assert!(!watch.any_triggered());
+    }
+
+    #[test]
+    fn ends_only_after_recovery() {
+        let detectors = [detector("a")];
+        let mut watch = SilenceWatch::default();
+        let start = Instant::now();
+        watch.update(&detectors, &[level("a", -70.0)], start);
+        assert_eq!(watch.update(&detectors, &[level("a", -70.0)], at(start, 5.0)), vec![SilenceEvent::Started(0)]);
+        assert!(watch.update(&detectors, &[level("a", -20.0)], at(start, 6.0)).is_empty());
+        assert!(watch.update(&detectors, &[level("a", -20.0)], at(start, 7.5)).is_empty());
+        assert!(watch.any_triggered());
+        // Kurzes Signal unterbrochen: die Erholung beginnt von vorn
+        watch.update(&detectors, &[level("a", -70.0)], at(start, 7.6));
+        assert!(watch.update(&detectors, &[level("a", -20.0)], at(start, 8.0)).is_empty());
+        assert!(watch.update(&detectors, &[level("a", -20.0)], at(start, 9.9)).is_empty());
+        assert_eq!(watch.update(&detectors, &[level("a", -20.0)], at(start, 10.0)), vec![SilenceEvent::Ended(0)]);
+        assert!(!watch.any_triggered());
+        assert_eq!(watch.state(0, at(start, 10.0)), SilenceState::Signal);
+    }
+
+    #[test]
+    fn missing_port_counts_as_silent() {
+        let detectors = [detector("a")];
+        let mut watch = SilenceWatch::default();
+        let start = Instant::now();
+        // Port fehlt ganz bzw. liefert keinen Messwert
+        assert!(watch.update(&detectors, &[], start).is_empty());
+        let no_value = [("a".to_string(), None)];
+        assert_eq!(watch.update(&detectors, &no_value, at(start, 5.0)), vec![SilenceEvent::Started(0)]);
+    }
+
+    #[test]
+    fn remove_shifts_indices() {
+        let detectors = [detector("a"), detector("b")];
+        let mut watch = SilenceWatch::default();
+        let start = Instant::now();
+        // Nur "b" ist still
+        let levels = [level("a", -20.0), level("b", -70.0)];
+        watch.update(&detectors, &levels, start);
+        assert_eq!(watch.update(&detectors, &levels, at(start, 5.0)), vec![SilenceEvent::Started(1)]);
+        watch.set_resume(1, Some("Sendung".to_string()));
+
+        watch.remove(0);
+        let detectors = [detector("b")];
+        assert_eq!(watch.state(0, at(start, 6.0)), SilenceState::Triggered(6));
+        assert_eq!(watch.take_resume(0), Some("Sendung".to_string()));
+        assert_eq!(
+            watch.update(&detectors, &[level("b", -20.0)], at(start, 7.0)),
+            Vec::<SilenceEvent>::new()
+        );
+        assert_eq!(watch.update(&detectors, &[level("b", -20.0)], at(start, 9.0)), vec![SilenceEvent::Ended(0)]);
+    }
+}
@@ -0,0 +1,217 @@
+use std::{fs, path::PathBuf};
+
+use crate::audit::{self, Record};
+use crate::error::{Error, Result};
+use crate::managed_audio_program::{client_base, owner_of, read_jack_connections, read_jack_ports, JackPortInfo, ManagedAudioProgram};
+use crate::meter::is_internal_port;
+use crate::{atomic_file, config_schema};
+
+/// Name des Unterverzeichnisses, in dem die gespeicherten Verbindungszustände liegen.
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Ein Port, wie er beim Speichern vorhanden war.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PortMeta {
+    pub name: String,
+    pub properties: Vec<String>,
+}
+
+/// Kompletter Verbindungszustand des JACK-Graphen, z.B. von Hand gesteckt.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    #[serde(default)]
+    pub created: String,
+    pub connections: Vec<(String, String)>,
+    #[serde(default)]
+    pub ports: Vec<PortMeta>,
+}
+
+impl Snapshot {
+    /// Hält den aktuellen Zustand des Graphen fest.
+    pub fn capture(name: &str) -> Result<Self> {
+        check_name(name)?;
+        let ports = read_jack_ports()?;
+        let mut connections = read_jack_connections()?;
+        // Messeingänge gehören nicht zum Routing
+        connections.retain(|(_, target)| !is_internal_port(target));
+        Ok(Self {
+            name: name.to_string(),
+            created: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            connections,
+            ports: ports
+                .into_iter()
+                .filter(|p| !is_internal_port(&p.name))
+                .map(|p| PortMeta { name: p.name, properties: p.properties })
+                .collect(),
+        })
+    }
+
+    pub fn dir() -> PathBuf {
+        ManagedAudioProgram::config_dir().join(SNAPSHOTS_DIR)
+    }
+
+    fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", name))
+    }
+
+    /// Namen aller gespeicherten Zustände, alphabetisch sortiert.
+    pub fn list() -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(Self::dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                    .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        check_name(name)?;
+        let path = Self::path(name);
+        let text = fs::read_to_string(&path).map_err(|source| Error::ConfigIo { path: path.clone(), source })?;
+        config_schema::parse_json(&text, &path).map_err(Error::ConfigParse)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path(&self.name);
+        let data = serde_json::to_vec_pretty(self).map_err(|source| Error::Serialize { path: path.clone(), source })?;
+        atomic_file::write_atomic(&path, &data).map_err(|source| Error::ConfigIo { path, source })?;
+        tracing::info!(snapshot = %self.name, connections = self.connections.len(), "Verbindungszustand gespeichert");
+        Ok(())
+    }
+
+    pub fn delete(name: &str) -> Result<()> {
+        check_name(name)?;
+        let path = Self::path(name);
+        fs::remove_file(&path).map_err(|source| Error::ConfigIo { path, source })
+    }
+
+    /// Stellt den gespeicherten Zustand her: fehlende Verbindungen werden hergestellt, alle anderen
+    /// getrennt. Ports von Clients, deren Nummer sich seitdem geändert hat, werden zugeordnet.
+    /// Liefert die Verbindungen, die sich keinem vorhandenen Port zuordnen ließen.
+    pub fn restore(&self, record: &mut Record) -> Result<Vec<String>> {
+        let ports = read_jack_ports()?;
+        let mut wanted = Vec::new();
+        let mut skipped = Vec::new();
+        for (source, target) in self.connections.iter() {
+            match (self.map_port(source, &ports), self.map_port(target, &ports)) {
+                (Some(source), Some(target)) => wanted.push((source, target)),
+                _ => skipped.push(format!("{} -> {}", source, target)),
+            }
+        }
+        audit::restore_snapshot(record, &wanted)?;
+        tracing::info!(snapshot = %self.name, skipped = skipped.len(), "Verbindungszustand wiederhergestellt");
+        Ok(skipped)
+    }
+
+    /// Sucht den passenden aktuellen Port. Gibt es den Namen nicht mehr, zählt ein Port mit
+    /// gleichem Namen und gleicher Richtung bei einem Client, der sich nur in der Nummer unterscheidet.
+    fn map_port(&self, name: &str, ports: &[JackPortInfo]) -> Option<String> {
+        if ports.iter().any(|p| p.name == name) {
+            return Some(name.to_string());
+        }
+        let (client, short) = name.split_once(':')?;
+        let direction = |properties: &[String]| properties.iter().find(|p| *p == "input" || *p == "output").cloned();
+        let saved_direction = self.ports.iter().find(|p| p.name == name).and_then(|p| direction(&p.properties));
+        ports
+            .iter()
+            .find(|p| {
+                p.name.split_once(':').is_some_and(|(c, s)| s == short && client_base(c) == client_base(client))
+                    && (saved_direction.is_none() || direction(&p.properties) == saved_direction)
+            })
+            .map(|p| p.name.clone())
+    }
+
+    /// Übernimmt die Verbindungen als Regeln mit dem Filter `use_case` in die Programme,
+    /// denen das Ziel gehört. Liefert die Verbindungen, deren Ziel zu keinem Programm gehört.
+    pub fn to_use_case(&self, use_case: &str, programs: &mut [ManagedAudioProgram]) -> Result<Vec<String>> {
+        if use_case.trim().is_empty() || use_case.contains(char::is_whitespace) {
+            return Err(Error::InvalidName(use_case.to_string()));
+        }
+        let mut skipped = Vec::new();
+        let mut changed = vec![false; programs.len()];
+        for (source, target) in self.connections.iter() {
+            let Some(index) = owner_of(programs, target) else {
+                skipped.push(format!("{} -> {}", source, target));
+                continue;
+            };
+            if programs[index].add_rule(use_case, source, target) {
+                changed[index] = true;
+            }
+        }
+
+        let mut errors = Vec::new();
+        for (prog, changed) in programs.iter_mut().zip(changed) {
+            if changed && let Err(e) = prog.save_config() {
+                errors.push(e);
+            }
+        }
+        Error::from_list(errors)?;
+        tracing::info!(snapshot = %self.name, use_case, skipped = skipped.len(), "Verbindungszustand als Use-Case übernommen");
+        Ok(skipped)
+    }
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        Err(Error::InvalidName(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(name: &str, direction: &str) -> JackPortInfo {
+        JackPortInfo { name: name.to_string(), properties: vec![direction.to_string(), "physical".to_string()] }
+    }
+
+    fn snapshot(ports: &[JackPortInfo]) -> Snapshot {
+        Snapshot {
+            name: "test".to_string(),
+            created: String::new(),
+            connections: Vec::new(),
+            ports: ports.iter().map(|p| PortMeta { name: p.name.clone(), properties: p.properties.clone() }).collect(),
+        }
+    }
+
+    #[test]
+    fn exact_name_is_kept() {
+        let snapshot = snapshot(&[port("baresip-12:out", "output")]);
+        let ports = [port("baresip-12:out", "output"), port("baresip-40:out", "output")];
+        assert_eq!(snapshot.map_port("baresip-12:out", &ports).as_deref(), Some("baresip-12:out"));
+    }
+
+    #[test]
+    fn changed_suffix_is_mapped() {
+        let snapshot = snapshot(&[port("baresip-12:out", "output")]);
+        let ports = [port("system:playback_1", "input"), port("baresip-40:out", "output")];
+        assert_eq!(snapshot.map_port("baresip-12:out", &ports).as_deref(), Some("baresip-40:out"));
+    }
+
+    #[test]
+    fn direction_must_match() {
+        let snapshot = snapshot(&[port("baresip-12:audio", "output")]);
+        let ports = [port("baresip-40:audio", "input")];
+        assert_eq!(snapshot.map_port("baresip-12:audio", &ports), None);
+        // Ohne gespeicherte Richtung zählt nur der Name
+        let without_meta = Snapshot { ports: Vec::new(), ..snapshot };
+        assert_eq!(without_meta.map_port("baresip-12:audio", &ports).as_deref(), Some("baresip-40:audio"));
+    }
+
+    #[test]
+    fn unknown_client_is_not_mapped() {
+        let snapshot = snapshot(&[port("baresip-12:out", "output")]);
+        let ports = [port("darkice-12:out", "output"), port("baresip-40:in", "input")];
+        assert_eq!(snapshot.map_port("baresip-12:out", &ports), None);
+        assert_eq!(snapshot.map_port("kein-port", &ports), None);
+    }
+}
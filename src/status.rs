@@ -0,0 +1,250 @@
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::managed_audio_program::{owner_of, read_jack_connections, read_jack_ports, resolve_connection, ManagedAudioProgram};
+
+/// Abstand zwischen zwei Abfragen.
+const INTERVAL: Duration = Duration::from_secs(2);
+
+/// So lange nach dem Start gilt ein Programm ohne JACK-Ports noch als startend (Sekunden).
+const STARTUP_GRACE: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgramState {
+    Stopped,
+    /// Prozess läuft, hat sich aber noch nicht bei JACK angemeldet
+    Starting,
+    /// Prozess läuft, aber auch nach der Startzeit ohne JACK-Ports
+    Running,
+    /// Prozess läuft und hat JACK-Ports
+    Ready,
+    /// Prozess ist verschwunden, ohne dass er über den Manager beendet wurde
+    Crashed,
+}
+
+impl fmt::Display for ProgramState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramState::Stopped => write!(f, "gestoppt"),
+            ProgramState::Starting => write!(f, "startet"),
+            ProgramState::Running => write!(f, "läuft, ohne JACK-Ports"),
+            ProgramState::Ready => write!(f, "bereit"),
+            ProgramState::Crashed => write!(f, "abgestürzt"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteState {
+    Connected,
+    Disconnected,
+    /// Quelle oder Ziel gibt es gerade nicht
+    Unresolvable,
+    /// Der JACK-Server antwortet nicht, über die Verbindung ist nichts bekannt
+    JackUnavailable,
+}
+
+impl fmt::Display for RouteState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteState::Connected => write!(f, "verbunden"),
+            RouteState::Disconnected => write!(f, "getrennt"),
+            RouteState::Unresolvable => write!(f, "nicht auflösbar"),
+            RouteState::JackUnavailable => write!(f, "JACK nicht erreichbar"),
+        }
+    }
+}
+
+/// Zustand einer konfigurierten Verbindung.
+#[derive(Debug, Clone)]
+pub struct RouteStatus {
+    pub filter: String,
+    pub source: String,
+    /// Aufgelöster Zielport, sonst das konfigurierte Ziel
+    pub target: String,
+    pub state: RouteState,
+}
+
+impl fmt::Display for RouteStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let filter = if self.filter.is_empty() { "immer" } else { &self.filter };
+        write!(f, "[{}] {} -> {}: {}", filter, self.source, self.target, self.state)
+    }
+}
+
+/// Zustand eines Programms zum Zeitpunkt der Abfrage.
+#[derive(Debug, Clone)]
+pub struct ProgramStatus {
+    pub program: String,
+    pub state: ProgramState,
+    pub pid: Option<u32>,
+    /// Laufzeit in Sekunden
+    pub uptime: Option<u64>,
+    /// CPU-Last in Prozent eines Kerns
+    pub cpu: Option<f32>,
+    /// Speicherbedarf in Bytes
+    pub memory: Option<u64>,
+    pub jack_node_name: String,
+    pub routes: Vec<RouteStatus>,
+}
+
+impl ProgramStatus {
+    /// Kurzbeschreibung mit PID, Laufzeit, Last und JACK-Namen, soweit bekannt.
+    pub fn details(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(pid) = self.pid {
+            parts.push(format!("PID {}", pid));
+        }
+        if let Some(uptime) = self.uptime {
+            parts.push(format!("{:02}:{:02}:{:02}", uptime / 3600, uptime / 60 % 60, uptime % 60));
+        }
+        if let Some(cpu) = self.cpu {
+            parts.push(format!("CPU {:.1} %", cpu));
+        }
+        if let Some(memory) = self.memory {
+            parts.push(format!("{:.1} MB", memory as f64 / 1_000_000.0));
+        }
+        if !self.jack_node_name.is_empty() {
+            parts.push(format!("JACK {}", self.jack_node_name));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Fragt den Zustand der Programme regelmäßig in einem eigenen Thread ab, solange die Instanz lebt.
+pub struct StatusMonitor {
+    stop: Arc<AtomicBool>,
+}
+
+impl StatusMonitor {
+    /// Ruft `on_update` nach jeder Abfrage im Thread des Monitors auf.
+    pub fn start<F>(programs: Arc<Mutex<Vec<ManagedAudioProgram>>>, on_update: F) -> Self
+    where
+        F: Fn(Vec<ProgramStatus>) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        std::thread::spawn(move || {
+            // CPU-Last ergibt sich erst aus zwei Abfragen, daher bleibt System erhalten
+            let mut sys = System::new();
+            while !stopped.load(Ordering::Relaxed) {
+                on_update(collect(&programs, &mut sys));
+                std::thread::sleep(INTERVAL);
+            }
+        });
+        Self { stop }
+    }
+}
+
+impl Drop for StatusMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Ermittelt den Zustand aller Programme. JACK und die Prozesse werden außerhalb der Sperre
+/// abgefragt, damit die Oberfläche nicht darauf warten muss.
+pub fn collect(programs: &Mutex<Vec<ManagedAudioProgram>>, sys: &mut System) -> Vec<ProgramStatus> {
+    // Ohne JACK ließe sich keine Verbindung auflösen; das soll nicht wie ein Konfigurationsfehler aussehen
+    let jack = read_jack_ports().and_then(|ports| Ok((ports, read_jack_connections()?)));
+    let jack_available = jack.is_ok();
+    let (ports, connections) = jack.unwrap_or_default();
+
+    // Vom Manager gestartete Prozesse, die sich inzwischen beendet haben, und ob JACK-Ports da sind
+    let mut collected = Vec::new();
+    {
+        let Ok(mut programs) = programs.lock() else {
+            return Vec::new();
+        };
+        let names: Vec<String> = programs.iter().map(|p| p.jack_node_name.clone()).collect();
+        for index in 0..programs.len() {
+            let has_ports = ports.iter().any(|p| owner_of(&programs, &p.name) == Some(index));
+            let prog = &mut programs[index];
+            let exited = prog.process.as_mut().and_then(|child| child.try_wait().ok().flatten());
+            let pid = prog.process.as_ref().map(|child| child.id()).or_else(|| prog.pid());
+            let routes = prog
+                .config
+                .jack_ports
+                .iter()
+                .map(|port| {
+                    if !jack_available {
+                        let (source, target) = (port.source_name.clone(), port.target_name.clone());
+                        return RouteStatus { filter: port.filter.clone(), source, target, state: RouteState::JackUnavailable };
+                    }
+                    let (target, state) = match resolve_connection(&ports, &names, prog.jack_node_name.clone(), port) {
+                        Ok((source, target)) => {
+                            let state = if connections.iter().any(|(s, t)| *s == source && *t == target) {
+                                RouteState::Connected
+                            } else {
+                                RouteState::Disconnected
+                            };
+                            (target, state)
+                        }
+                        Err(_) => (port.target_name.clone(), RouteState::Unresolvable),
+                    };
+                    RouteStatus { filter: port.filter.clone(), source: port.source_name.clone(), target, state }
+                })
+                .collect();
+            let status = ProgramStatus {
+                program: prog.config.program_name.clone(),
+                state: ProgramState::Stopped,
+                pid,
+                uptime: None,
+                cpu: None,
+                memory: None,
+                jack_node_name: prog.jack_node_name.clone(),
+                routes,
+            };
+            collected.push((status, exited, has_ports));
+        }
+    }
+
+    let pids: Vec<Pid> = collected
+        .iter()
+        .filter_map(|(status, _, _)| status.pid)
+        .map(|pid| Pid::from(pid as usize))
+        .collect();
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&pids),
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+
+    collected
+        .into_iter()
+        .map(|(mut status, exited, has_ports)| {
+            let process = status.pid.and_then(|pid| sys.process(Pid::from(pid as usize)));
+            status.state = match (exited, process) {
+                (Some(exit), _) if exit.success() => ProgramState::Stopped,
+                (Some(_), _) => ProgramState::Crashed,
+                // Eine PID-Datei ohne Prozess bleibt nur zurück, wenn nicht über den Manager beendet wurde
+                (None, None) if status.pid.is_some() => ProgramState::Crashed,
+                (None, None) => ProgramState::Stopped,
+                (None, Some(process)) => {
+                    status.uptime = Some(process.run_time());
+                    status.cpu = Some(process.cpu_usage());
+                    status.memory = Some(process.memory());
+                    if has_ports {
+                        ProgramState::Ready
+                    } else if process.run_time() < STARTUP_GRACE {
+                        ProgramState::Starting
+                    } else {
+                        ProgramState::Running
+                    }
+                }
+            };
+            if status.state == ProgramState::Stopped {
+                status.pid = None;
+            }
+            status
+        })
+        .collect()
+}
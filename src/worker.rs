@@ -0,0 +1,106 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+
+use crate::error::{Error, Result};
+
+/// Zustand des Workers für die Anzeige.
+pub enum WorkerEvent {
+    /// Ein Auftrag beginnt, mit seiner Bezeichnung
+    Busy(String),
+    /// Zwischenstand des laufenden Auftrags
+    Progress(String),
+    /// Auftrag beendet
+    Idle,
+}
+
+/// Wird an den laufenden Auftrag übergeben: Zwischenstände melden und auf Abbruch prüfen.
+pub struct Progress<'a> {
+    cancel: &'a AtomicBool,
+    on_event: &'a dyn Fn(WorkerEvent),
+}
+
+impl Progress<'_> {
+    pub fn report(&self, message: impl Into<String>) {
+        (self.on_event)(WorkerEvent::Progress(message.into()));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// `Error::Cancelled`, sobald der Auftrag abgebrochen wurde.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() { Err(Error::Cancelled) } else { Ok(()) }
+    }
+}
+
+type Job = Box<dyn FnOnce(&Progress) + Send>;
+
+/// Führt Prozess- und JACK-Operationen nacheinander in einem eigenen Thread aus, damit die
+/// Oberfläche dabei nicht blockiert. Ergebnisse meldet der Auftrag selbst zurück.
+#[derive(Clone)]
+pub struct Worker {
+    /// Bezeichnung, ohne Anzeige, Auftrag
+    jobs: mpsc::Sender<(String, bool, Job)>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Worker {
+    /// Ruft `on_event` im Thread des Workers auf, wenn ein Auftrag beginnt, Fortschritt meldet oder endet.
+    pub fn start<F>(on_event: F) -> Self
+    where
+        F: Fn(WorkerEvent) + Send + 'static,
+    {
+        let (jobs, queue) = mpsc::channel::<(String, bool, Job)>();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancelled = cancel.clone();
+        std::thread::spawn(move || {
+            let silent = |_: WorkerEvent| {};
+            while let Ok((label, quiet, job)) = queue.recv() {
+                // Ein Abbruch gilt nur für den Auftrag, der gerade läuft
+                cancelled.store(false, Ordering::Relaxed);
+                tracing::debug!(job = %label, "Auftrag gestartet");
+                let on_event: &dyn Fn(WorkerEvent) = if quiet { &silent } else { &on_event };
+                on_event(WorkerEvent::Busy(label.clone()));
+                job(&Progress { cancel: &cancelled, on_event });
+                if cancelled.load(Ordering::Relaxed) {
+                    tracing::info!(job = %label, "Auftrag abgebrochen");
+                }
+                on_event(WorkerEvent::Idle);
+            }
+        });
+        Self { jobs, cancel }
+    }
+
+    /// Stellt einen Auftrag hinten an die Warteschlange.
+    pub fn run<F>(&self, label: impl Into<String>, job: F)
+    where
+        F: FnOnce(&Progress) + Send + 'static,
+    {
+        self.send(label.into(), false, Box::new(job));
+    }
+
+    /// Wie `run`, aber ohne Anzeige. Für regelmäßige Abfragen, die sonst alle paar
+    /// Sekunden die Statuszeile einblenden würden.
+    pub fn run_quiet<F>(&self, label: impl Into<String>, job: F)
+    where
+        F: FnOnce(&Progress) + Send + 'static,
+    {
+        self.send(label.into(), true, Box::new(job));
+    }
+
+    fn send(&self, label: String, quiet: bool, job: Job) {
+        // Schlägt nur fehl, wenn der Thread nicht mehr läuft
+        if self.jobs.send((label, quiet, job)).is_err() {
+            tracing::error!("Worker-Thread läuft nicht mehr, Auftrag verworfen");
+        }
+    }
+
+    /// Bricht den laufenden Auftrag an der nächsten Prüfstelle ab.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}